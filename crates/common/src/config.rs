@@ -0,0 +1,61 @@
+//! Loading and saving of human-editable RON config files.
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+use std::path::Path;
+
+/// An error while loading or saving a config file.
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    Serialize(ron::Error),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(err) => write!(f, "could not access config: {err}"),
+            ConfigError::Parse(err) => write!(f, "invalid config: {err}"),
+            ConfigError::Serialize(err) => write!(f, "could not write config: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Parses a value from RON.
+pub fn from_ron<T: DeserializeOwned>(source: &str) -> Result<T, ConfigError> {
+    ron::from_str(source).map_err(ConfigError::Parse)
+}
+
+/// Serializes a value to pretty-printed RON.
+pub fn to_ron<T: Serialize>(value: &T) -> Result<String, ConfigError> {
+    ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())
+        .map_err(ConfigError::Serialize)
+}
+
+/// Loads a value from a RON file.
+pub fn load<T: DeserializeOwned>(path: &Path) -> Result<T, ConfigError> {
+    let source = std::fs::read_to_string(path).map_err(ConfigError::Io)?;
+    from_ron(&source)
+}
+
+/// Saves a value to a RON file, creating its directory if needed.
+pub fn save<T: Serialize>(value: &T, path: &Path) -> Result<(), ConfigError> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(ConfigError::Io)?;
+    }
+    std::fs::write(path, to_ron(value)?).map_err(ConfigError::Io)
+}
+
+/// Loads a value from a RON file, falling back to its default if the file is missing or invalid.
+pub fn load_or_default<T: DeserializeOwned + Default>(path: &Path) -> T {
+    if !path.exists() {
+        return T::default();
+    }
+    load(path).unwrap_or_else(|err| {
+        bevy::log::warn!("Using defaults instead of {:?}: {}", path, err);
+        T::default()
+    })
+}
@@ -0,0 +1,498 @@
+//! Named input actions, their rebindable device bindings, and the per-frame action state.
+//!
+//! Gameplay systems read `ActionState` instead of raw device input, so keys can be rebound and
+//! tests can drive the game by pressing actions directly.
+use crate::config::{self, ConfigError};
+use bevy::ecs::system::SystemParam;
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+/// Where the input bindings are loaded from and saved to by default.
+pub const DEFAULT_BINDINGS_PATH: &str = "config/input.ron";
+/// Analog values below this magnitude are treated as zero.
+const DEFAULT_DEAD_ZONE: f32 = 0.15;
+
+/// A named action the player can perform, independent of the device used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum InputAction {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    Sprint,
+    Crouch,
+    Jump,
+    ToggleCamera,
+    Build,
+    Fire,
+    Aim,
+    SwapShoulder,
+    ToggleFreeFly,
+    RotateBuildPiece,
+    CycleBuildPiece,
+}
+
+impl InputAction {
+    /// Every action, in declaration order.
+    pub const ALL: [InputAction; 15] = [
+        InputAction::MoveForward,
+        InputAction::MoveBackward,
+        InputAction::MoveLeft,
+        InputAction::MoveRight,
+        InputAction::Sprint,
+        InputAction::Crouch,
+        InputAction::Jump,
+        InputAction::ToggleCamera,
+        InputAction::Build,
+        InputAction::Fire,
+        InputAction::Aim,
+        InputAction::SwapShoulder,
+        InputAction::ToggleFreeFly,
+        InputAction::RotateBuildPiece,
+        InputAction::CycleBuildPiece,
+    ];
+}
+
+/// A physical input that can trigger an action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum InputBinding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    GamepadButton(GamepadButtonType),
+    /// One half of a gamepad axis; `positive` selects which direction triggers the action.
+    GamepadAxis {
+        axis: GamepadAxisType,
+        positive: bool,
+    },
+}
+
+/// Rescales an analog value so the dead zone reads as zero and the rest still spans `0.0..=1.0`.
+pub fn apply_dead_zone(value: f32, dead_zone: f32) -> f32 {
+    let value = value.clamp(0.0, 1.0);
+    if value <= dead_zone {
+        0.0
+    } else {
+        (value - dead_zone) / (1.0 - dead_zone)
+    }
+}
+
+/// The device bindings for each action. Every binding of an action triggers it.
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputBindings {
+    /// The dead zone applied to gamepad axes.
+    pub dead_zone: f32,
+    bindings: BTreeMap<InputAction, Vec<InputBinding>>,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        use GamepadAxisType::{LeftStickX, LeftStickY};
+        use InputBinding::{GamepadAxis, GamepadButton, Key, Mouse};
+
+        let defaults = [
+            (
+                InputAction::MoveForward,
+                vec![
+                    Key(KeyCode::KeyW),
+                    GamepadAxis {
+                        axis: LeftStickY,
+                        positive: true,
+                    },
+                ],
+            ),
+            (
+                InputAction::MoveBackward,
+                vec![
+                    Key(KeyCode::KeyS),
+                    GamepadAxis {
+                        axis: LeftStickY,
+                        positive: false,
+                    },
+                ],
+            ),
+            (
+                InputAction::MoveLeft,
+                vec![
+                    Key(KeyCode::KeyA),
+                    GamepadAxis {
+                        axis: LeftStickX,
+                        positive: false,
+                    },
+                ],
+            ),
+            (
+                InputAction::MoveRight,
+                vec![
+                    Key(KeyCode::KeyD),
+                    GamepadAxis {
+                        axis: LeftStickX,
+                        positive: true,
+                    },
+                ],
+            ),
+            (
+                InputAction::Sprint,
+                vec![
+                    Key(KeyCode::ShiftLeft),
+                    GamepadButton(GamepadButtonType::LeftThumb),
+                ],
+            ),
+            (
+                InputAction::Crouch,
+                vec![Key(KeyCode::KeyC), GamepadButton(GamepadButtonType::East)],
+            ),
+            (
+                InputAction::Jump,
+                vec![Key(KeyCode::Space), GamepadButton(GamepadButtonType::South)],
+            ),
+            (
+                InputAction::ToggleCamera,
+                vec![Key(KeyCode::KeyV), GamepadButton(GamepadButtonType::Select)],
+            ),
+            (
+                InputAction::Build,
+                vec![Key(KeyCode::KeyB), GamepadButton(GamepadButtonType::North)],
+            ),
+            (
+                InputAction::Fire,
+                vec![
+                    Mouse(MouseButton::Left),
+                    GamepadButton(GamepadButtonType::RightTrigger2),
+                ],
+            ),
+            (
+                InputAction::Aim,
+                vec![
+                    Mouse(MouseButton::Right),
+                    GamepadButton(GamepadButtonType::LeftTrigger2),
+                ],
+            ),
+            (
+                InputAction::SwapShoulder,
+                vec![
+                    Key(KeyCode::KeyX),
+                    GamepadButton(GamepadButtonType::RightThumb),
+                ],
+            ),
+            (InputAction::ToggleFreeFly, vec![Key(KeyCode::F1)]),
+            (
+                InputAction::RotateBuildPiece,
+                vec![
+                    Key(KeyCode::KeyR),
+                    GamepadButton(GamepadButtonType::DPadRight),
+                ],
+            ),
+            (
+                InputAction::CycleBuildPiece,
+                vec![Key(KeyCode::Tab), GamepadButton(GamepadButtonType::DPadUp)],
+            ),
+        ];
+
+        Self {
+            dead_zone: DEFAULT_DEAD_ZONE,
+            bindings: defaults.into_iter().collect(),
+        }
+    }
+}
+
+impl InputBindings {
+    /// The bindings that trigger `action`.
+    pub fn bindings(&self, action: InputAction) -> &[InputBinding] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Adds a binding to `action`, keeping its existing ones.
+    pub fn bind(&mut self, action: InputAction, binding: InputBinding) {
+        let bindings = self.bindings.entry(action).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    /// Removes a binding from `action`.
+    pub fn unbind(&mut self, action: InputAction, binding: InputBinding) {
+        if let Some(bindings) = self.bindings.get_mut(&action) {
+            bindings.retain(|existing| *existing != binding);
+        }
+    }
+
+    /// Replaces `old` with `new` on `action`, or adds `new` if `old` wasn't bound.
+    pub fn rebind(&mut self, action: InputAction, old: InputBinding, new: InputBinding) {
+        let bindings = self.bindings.entry(action).or_default();
+        bindings.retain(|existing| *existing != new);
+        match bindings.iter().position(|existing| *existing == old) {
+            Some(index) => bindings[index] = new,
+            None => bindings.push(new),
+        }
+    }
+
+    /// Loads bindings from a config file.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        config::load(path)
+    }
+
+    /// Saves bindings to a config file, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> Result<(), ConfigError> {
+        config::save(self, path)
+    }
+}
+
+/// The current value of a single action.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct ActionData {
+    value: f32,
+    just_pressed: bool,
+    just_released: bool,
+}
+
+/// The state of every action this frame, which gameplay systems read instead of raw devices.
+#[derive(Resource, Debug, Clone, Default, PartialEq)]
+pub struct ActionState {
+    actions: HashMap<InputAction, ActionData>,
+}
+
+impl ActionState {
+    /// Sets the analog value of `action` in `0.0..=1.0`; any non-zero value counts as pressed.
+    pub fn set_value(&mut self, action: InputAction, value: f32) {
+        let data = self.actions.entry(action).or_default();
+        let was_pressed = data.value > 0.0;
+        data.value = value.clamp(0.0, 1.0);
+        let pressed = data.value > 0.0;
+        data.just_pressed = pressed && !was_pressed;
+        data.just_released = !pressed && was_pressed;
+    }
+
+    /// Fully presses `action`.
+    pub fn press(&mut self, action: InputAction) {
+        self.set_value(action, 1.0);
+    }
+
+    /// Releases `action`.
+    pub fn release(&mut self, action: InputAction) {
+        self.set_value(action, 0.0);
+    }
+
+    /// The analog value of `action`, `1.0` for fully pressed buttons.
+    pub fn value(&self, action: InputAction) -> f32 {
+        self.actions.get(&action).map_or(0.0, |data| data.value)
+    }
+
+    /// Whether `action` is held.
+    pub fn pressed(&self, action: InputAction) -> bool {
+        self.value(action) > 0.0
+    }
+
+    /// Whether `action` started being held this frame.
+    pub fn just_pressed(&self, action: InputAction) -> bool {
+        self.actions
+            .get(&action)
+            .is_some_and(|data| data.just_pressed)
+    }
+
+    /// Whether `action` stopped being held this frame.
+    pub fn just_released(&self, action: InputAction) -> bool {
+        self.actions
+            .get(&action)
+            .is_some_and(|data| data.just_released)
+    }
+
+    /// The movement input as a 2D vector (x: right, y: forward), with length at most 1.
+    pub fn move_axis(&self) -> Vec2 {
+        Vec2::new(
+            self.value(InputAction::MoveRight) - self.value(InputAction::MoveLeft),
+            self.value(InputAction::MoveForward) - self.value(InputAction::MoveBackward),
+        )
+        .clamp_length_max(1.0)
+    }
+}
+
+/// The path the input bindings are saved to when they change.
+///
+/// Only present while the bindings' own config file owns them. Another store that saves the
+/// bindings, like a player profile, removes it so changes aren't written to both.
+#[derive(Resource, Debug, Clone, PartialEq, Eq)]
+pub struct InputBindingsPath(pub PathBuf);
+
+/// While present, device input isn't mapped to actions, so something else, like a replay, can
+/// drive `ActionState` without the player's devices overwriting it.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SuspendDeviceInput;
+
+/// The raw device input the bindings are resolved against.
+#[derive(SystemParam)]
+struct InputDevices<'w> {
+    keys: Res<'w, ButtonInput<KeyCode>>,
+    mouse_buttons: Res<'w, ButtonInput<MouseButton>>,
+    gamepads: Res<'w, Gamepads>,
+    gamepad_buttons: Res<'w, ButtonInput<GamepadButton>>,
+    gamepad_axes: Res<'w, Axis<GamepadAxis>>,
+}
+
+impl InputDevices<'_> {
+    /// The current value of a single binding, taking the strongest of all connected gamepads.
+    fn value(&self, binding: InputBinding, dead_zone: f32) -> f32 {
+        let held = |pressed: bool| if pressed { 1.0 } else { 0.0 };
+        match binding {
+            InputBinding::Key(key) => held(self.keys.pressed(key)),
+            InputBinding::Mouse(button) => held(self.mouse_buttons.pressed(button)),
+            InputBinding::GamepadButton(button_type) => held(self.gamepads.iter().any(|gamepad| {
+                self.gamepad_buttons
+                    .pressed(GamepadButton::new(gamepad, button_type))
+            })),
+            InputBinding::GamepadAxis { axis, positive } => self
+                .gamepads
+                .iter()
+                .filter_map(|gamepad| self.gamepad_axes.get(GamepadAxis::new(gamepad, axis)))
+                .map(|value| if positive { value } else { -value })
+                .map(|value| apply_dead_zone(value, dead_zone))
+                .fold(0.0, f32::max),
+        }
+    }
+}
+
+/// Resolves every action's bindings against the devices and updates `ActionState`.
+fn update_action_state(
+    bindings: Res<InputBindings>,
+    devices: InputDevices,
+    mut action_state: ResMut<ActionState>,
+) {
+    for action in InputAction::ALL {
+        let value = bindings
+            .bindings(action)
+            .iter()
+            .map(|binding| devices.value(*binding, bindings.dead_zone))
+            .fold(0.0, f32::max);
+        action_state.set_value(action, value);
+    }
+}
+
+/// Writes the bindings back to their config file whenever they are changed at runtime.
+fn save_changed_bindings(bindings: Res<InputBindings>, path: Option<Res<InputBindingsPath>>) {
+    let Some(path) = path else {
+        return;
+    };
+    if bindings.is_changed() && !bindings.is_added() {
+        if let Err(err) = bindings.save(&path.0) {
+            warn!("Failed to save input bindings to {:?}: {}", path.0, err);
+        }
+    }
+}
+
+/// Maps keyboard, mouse and gamepad input to `ActionState`. Requires bevy's `InputPlugin`.
+///
+/// Bindings already inserted by another store are kept, and left to that store to save.
+pub struct InputActionsPlugin {
+    /// The config file bindings are loaded from and saved to.
+    pub bindings_path: PathBuf,
+}
+
+impl Default for InputActionsPlugin {
+    fn default() -> Self {
+        Self {
+            bindings_path: PathBuf::from(DEFAULT_BINDINGS_PATH),
+        }
+    }
+}
+
+impl Plugin for InputActionsPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<InputBindings>() {
+            let bindings: InputBindings = config::load_or_default(&self.bindings_path);
+            app.insert_resource(bindings)
+                .insert_resource(InputBindingsPath(self.bindings_path.clone()));
+        }
+        app.init_resource::<ActionState>()
+            .add_systems(
+                PreUpdate,
+                update_action_state
+                    .after(InputSystem)
+                    .run_if(not(resource_exists::<SuspendDeviceInput>)),
+            )
+            .add_systems(Last, save_changed_bindings);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::input::InputPlugin;
+
+    #[test]
+    fn test_dead_zone_rescales_remaining_range() {
+        assert_eq!(apply_dead_zone(0.1, 0.2), 0.0);
+        assert_eq!(apply_dead_zone(-0.5, 0.2), 0.0);
+        assert!((apply_dead_zone(0.6, 0.2) - 0.5).abs() < 1e-6);
+        assert_eq!(apply_dead_zone(1.0, 0.2), 1.0);
+    }
+
+    #[test]
+    fn test_action_state_edges_and_move_axis() {
+        let mut state = ActionState::default();
+        state.press(InputAction::Jump);
+        assert!(state.just_pressed(InputAction::Jump));
+
+        state.press(InputAction::Jump);
+        assert!(state.pressed(InputAction::Jump));
+        assert!(!state.just_pressed(InputAction::Jump));
+
+        state.release(InputAction::Jump);
+        assert!(state.just_released(InputAction::Jump));
+
+        state.press(InputAction::MoveForward);
+        state.press(InputAction::MoveRight);
+        let axis = state.move_axis();
+        assert!((axis.length() - 1.0).abs() < 1e-6);
+        assert!(axis.x > 0.0 && axis.y > 0.0);
+    }
+
+    #[test]
+    fn test_bindings_round_trip_through_config_file() {
+        let mut bindings = InputBindings::default();
+        bindings.rebind(
+            InputAction::Jump,
+            InputBinding::Key(KeyCode::Space),
+            InputBinding::Key(KeyCode::KeyJ),
+        );
+        bindings.dead_zone = 0.25;
+
+        let path = std::env::temp_dir()
+            .join(format!("protocol-zero-input-{}", std::process::id()))
+            .join("input.ron");
+        bindings.save(&path).unwrap();
+        let loaded = InputBindings::load(&path).unwrap();
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+        assert_eq!(loaded, bindings);
+        assert_eq!(
+            loaded.bindings(InputAction::Jump)[0],
+            InputBinding::Key(KeyCode::KeyJ)
+        );
+    }
+
+    #[test]
+    fn test_devices_map_to_actions() {
+        let mut app = App::new();
+        app.add_plugins((
+            InputPlugin,
+            InputActionsPlugin {
+                bindings_path: PathBuf::from("does-not-exist/input.ron"),
+            },
+        ));
+        app.world
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .press(KeyCode::KeyV);
+        app.update();
+
+        let state = app.world.resource::<ActionState>();
+        assert!(state.just_pressed(InputAction::ToggleCamera));
+        assert!(!state.pressed(InputAction::Jump));
+
+        app.update();
+        let state = app.world.resource::<ActionState>();
+        assert!(state.pressed(InputAction::ToggleCamera));
+        assert!(!state.just_pressed(InputAction::ToggleCamera));
+    }
+}
@@ -1,5 +1,8 @@
 //! Shared data structures, math utilities, and configuration types.
 
+pub mod config;
+pub mod input;
+
 pub fn add(left: usize, right: usize) -> usize {
     left + right
 }
@@ -10,6 +10,8 @@ use bevy::render::mesh::Mesh;
 use bevy::scene::ScenePlugin;
 use bevy::MinimalPlugins;
 use bevy_rapier3d::prelude::*;
+use common::input::InputActionsPlugin;
+use gameplay::profile::ProfilePlugin;
 use gameplay::GameplayPlugin;
 use world::{MaterialId, Voxel}; // Import world data structures
 
@@ -20,12 +22,16 @@ pub fn app() -> App {
         .add_plugins(LogPlugin::default()) // For printing info messages
         .add_plugins(AssetPlugin::default()) // For loading assets
         .add_plugins(InputPlugin) // For keyboard input
+        .add_plugins(InputActionsPlugin::default()) // Maps devices to rebindable actions
         .add_plugins(ScenePlugin) // For SceneSpawner resource
         .add_plugins((
             RapierPhysicsPlugin::<NoUserData>::default(),
             GameplayPlugin,
         ))
+        .add_plugins(ProfilePlugin::default()) // Takes over the settings loaded above
         .init_resource::<world::WorldData>() // Initialize the world data resource
+        .init_resource::<world::MaterialRegistry>() // Material properties for the voxel world
+        .init_resource::<world::WorldSeed>() // The seed the world is generated from
         .init_resource::<Assets<Mesh>>() // Manually init for Rapier
         .init_resource::<Assets<StandardMaterial>>() // Manually init for player spawn
         .add_systems(Startup, setup_world); // Add setup systems
@@ -1,5 +1,64 @@
 //! A temporary binary to run the engine for development purposes.
+//!
+//! `--record <path>` saves the session's input as a replay on exit, and `--replay <path>` plays
+//! one back headlessly and exits when it ends. `--cinematic <path>` plays a camera path.
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use gameplay::camera::cinematic::{start_cinematic, CinematicPath};
+use gameplay::player::Player;
+use gameplay::replay::{start_playback, start_recording, Replay, ReplayFinished};
+use std::path::PathBuf;
 
 fn main() {
-    engine::app().run();
+    let mut app = engine::app();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.as_slice() {
+        [flag, path] if flag == "--record" => {
+            start_recording(&mut app.world, Some(PathBuf::from(path)));
+        }
+        [flag, path] if flag == "--replay" => match Replay::load(path.as_ref()) {
+            Ok(replay) => {
+                start_playback(&mut app.world, replay);
+                app.add_systems(Update, exit_when_replay_finished);
+            }
+            Err(err) => {
+                eprintln!("Failed to load replay {path}: {err}");
+                std::process::exit(1);
+            }
+        },
+        [flag, path] if flag == "--cinematic" => match CinematicPath::load(path.as_ref()) {
+            Ok(cinematic) => start_cinematic(&mut app.world, cinematic),
+            Err(err) => {
+                eprintln!("Failed to load cinematic {path}: {err}");
+                std::process::exit(1);
+            }
+        },
+        [] => {}
+        _ => {
+            eprintln!("Usage: engine [--record <path> | --replay <path> | --cinematic <path>]");
+            std::process::exit(2);
+        }
+    }
+
+    app.run();
+}
+
+/// Reports where the player ended up and quits once a replay has been played back.
+fn exit_when_replay_finished(
+    mut finished_events: EventReader<ReplayFinished>,
+    player_query: Query<&Transform, With<Player>>,
+    mut exit_events: EventWriter<AppExit>,
+) {
+    if finished_events.read().next().is_none() {
+        return;
+    }
+    if let Ok(transform) = player_query.get_single() {
+        info!(
+            "Replay finished with the player at {}",
+            transform.translation
+        );
+    }
+    exit_events.send(AppExit);
 }
@@ -0,0 +1,490 @@
+//! Blueprints: saved layouts of pieces that can be built again elsewhere.
+//!
+//! A blueprint records the kind, material and transform of each piece relative to the grid cell
+//! it was captured from, and is saved as a RON file so it can be shared. Placing one checks its
+//! pieces the same way single pieces are checked, then raises them one at a time, supporting
+//! pieces first. A blueprint that has to be built whole is paid for in full when it starts, and
+//! pieces that stop fitting along the way are refunded; otherwise each piece is paid for as it
+//! goes up.
+use super::catalogue::{BuildPieceCatalogue, BuildPieceDefinition, PiecePlacement};
+use super::placement::{self, PlacementCheck, BUILD_GRID_SIZE};
+use super::{BuildMaterialRegistry, BuildPiece, BuildPieceKind, BuildRejectReason, BuildRejected};
+use super::{Material, MaterialId};
+use crate::inventory::items::ItemRegistry;
+use crate::inventory::stack::{InventoryError, ItemContainer, ItemStack};
+use crate::inventory::{Inventory, ItemId};
+use crate::player::Player;
+use bevy::prelude::*;
+use common::config::{self, ConfigError};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+
+/// The time between pieces of a blueprint going up, in seconds.
+pub const DEFAULT_STAGE_INTERVAL: f32 = 0.25;
+
+/// A piece of a blueprint, placed relative to the blueprint's origin.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BlueprintPiece {
+    pub kind: BuildPieceKind,
+    pub material: MaterialId,
+    pub translation: Vec3,
+    #[serde(default)]
+    pub rotation: Quat,
+}
+
+/// A layout of pieces that can be built in one go.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Blueprint {
+    pub name: String,
+    /// The pieces in the order they're built, so each comes after what holds it up.
+    pub pieces: Vec<BlueprintPiece>,
+}
+
+impl Blueprint {
+    /// Captures placed pieces relative to the grid cell at their lowest corner.
+    ///
+    /// Pieces are ordered from the most stable down, so every piece is built after the pieces it
+    /// rests on.
+    pub fn capture<'a>(
+        name: impl Into<String>,
+        pieces: impl IntoIterator<Item = (&'a BuildPiece, &'a Transform)>,
+    ) -> Self {
+        let mut pieces: Vec<_> = pieces.into_iter().collect();
+        pieces.sort_by(|(a, _), (b, _)| b.stability.total_cmp(&a.stability));
+        let corner = pieces
+            .iter()
+            .map(|(_, transform)| transform.translation)
+            .reduce(Vec3::min)
+            .unwrap_or(Vec3::ZERO);
+        let origin = (corner / BUILD_GRID_SIZE).floor() * BUILD_GRID_SIZE;
+        Self {
+            name: name.into(),
+            pieces: pieces
+                .into_iter()
+                .map(|(piece, transform)| BlueprintPiece {
+                    kind: piece.kind,
+                    material: piece.material_id,
+                    translation: transform.translation - origin,
+                    rotation: transform.rotation,
+                })
+                .collect(),
+        }
+    }
+
+    /// Loads a blueprint from a file.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        config::load(path)
+    }
+
+    /// Saves the blueprint to a file, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> Result<(), ConfigError> {
+        config::save(self, path)
+    }
+
+    /// The items building every piece takes. Pieces of unknown kinds or materials are left out.
+    pub fn cost(
+        &self,
+        catalogue: &BuildPieceCatalogue,
+        materials: &BuildMaterialRegistry,
+    ) -> HashMap<ItemId, usize> {
+        let mut cost = HashMap::new();
+        for piece in &self.pieces {
+            if let (Some(definition), Some(material)) =
+                (catalogue.get(piece.kind), materials.get(piece.material))
+            {
+                *cost.entry(material.item).or_default() += definition.cost;
+            }
+        }
+        cost
+    }
+
+    /// Where each piece goes with the blueprint's origin at `origin`.
+    pub fn layout<'a>(
+        &'a self,
+        origin: &'a Transform,
+    ) -> impl Iterator<Item = (&'a BlueprintPiece, Transform)> + 'a {
+        self.pieces.iter().map(move |piece| {
+            let translation = origin.transform_point(piece.translation);
+            // Quarter turns leave float noise; keep positions exactly on the grid.
+            let translation = (translation * 2.0 / BUILD_GRID_SIZE).round() * BUILD_GRID_SIZE / 2.0;
+            let rotation = (origin.rotation * piece.rotation).normalize();
+            (
+                piece,
+                Transform::from_translation(translation).with_rotation(rotation),
+            )
+        })
+    }
+}
+
+/// What to do when some of a blueprint's pieces can't be built.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlueprintPolicy {
+    /// Build nothing unless every piece fits and the player can pay for all of them. The whole
+    /// cost is taken up front, so the pieces can't run out of materials part way through.
+    #[default]
+    AllOrNothing,
+    /// Build the pieces that fit and skip the rest.
+    SkipBlocked,
+}
+
+/// How quickly blueprints go up.
+#[derive(Resource, Debug, Clone)]
+pub struct BlueprintStaging {
+    /// The time between pieces going up, in seconds. At most one piece goes up per frame.
+    pub interval: f32,
+}
+
+impl Default for BlueprintStaging {
+    fn default() -> Self {
+        Self {
+            interval: DEFAULT_STAGE_INTERVAL,
+        }
+    }
+}
+
+/// An event requesting a blueprint of the given placed pieces.
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct CaptureBlueprint {
+    pub name: String,
+    pub pieces: Vec<Entity>,
+}
+
+/// An event sent with the blueprint a `CaptureBlueprint` asked for.
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct BlueprintCaptured {
+    pub blueprint: Blueprint,
+}
+
+/// An event requesting that a blueprint be built, its origin snapped to the grid cell containing
+/// `position`.
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct PlaceBlueprint {
+    pub blueprint: Blueprint,
+    pub position: Vec3,
+    pub rotation: Quat,
+    pub policy: BlueprintPolicy,
+}
+
+/// An event sent when a blueprint's construction starts.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlueprintStarted {
+    pub construction: Entity,
+    /// How many pieces will go up.
+    pub pieces: usize,
+    /// How many pieces were left out because they couldn't be built.
+    pub skipped: usize,
+}
+
+/// An event sent when a blueprint's last piece has gone up.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlueprintCompleted {
+    pub construction: Entity,
+    pub placed: usize,
+    /// Pieces left out up front or that stopped fitting while construction was under way.
+    pub skipped: usize,
+}
+
+/// A blueprint going up, one piece at a time.
+#[derive(Component, Debug, Clone)]
+pub struct BlueprintConstruction {
+    remaining: VecDeque<(BuildPieceKind, MaterialId, Transform)>,
+    until_next: f32,
+    /// Whether every piece was paid for when construction started.
+    prepaid: bool,
+    pub placed: usize,
+    pub skipped: usize,
+}
+
+impl BlueprintConstruction {
+    /// How many pieces are still to go up.
+    pub fn remaining(&self) -> usize {
+        self.remaining.len()
+    }
+}
+
+/// Answers `CaptureBlueprint`s from the placed pieces they name.
+pub(super) fn capture_blueprints(
+    mut capture_events: EventReader<CaptureBlueprint>,
+    piece_query: Query<(&BuildPiece, &Transform)>,
+    mut captured_events: EventWriter<BlueprintCaptured>,
+) {
+    for event in capture_events.read() {
+        let pieces = piece_query.iter_many(&event.pieces);
+        captured_events.send(BlueprintCaptured {
+            blueprint: Blueprint::capture(event.name.clone(), pieces),
+        });
+    }
+}
+
+/// Checks requested blueprints against their policy and starts building those that pass.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn start_blueprints(
+    mut commands: Commands,
+    mut place_events: EventReader<PlaceBlueprint>,
+    check: PlacementCheck,
+    catalogue: Res<BuildPieceCatalogue>,
+    materials: Res<BuildMaterialRegistry>,
+    mut inventory_query: Query<&mut Inventory, With<Player>>,
+    registry: Res<ItemRegistry>,
+    mut started_events: EventWriter<BlueprintStarted>,
+    mut rejected_events: EventWriter<BuildRejected>,
+) {
+    for event in place_events.read() {
+        let origin = placement::snap_to_grid(PiecePlacement::Cell, event.position, event.rotation);
+        let mut pieces: Vec<(&BuildPieceDefinition, &Material, Transform)> = Vec::new();
+        let mut unknown = false;
+        for (piece, transform) in event.blueprint.layout(&origin) {
+            match (catalogue.get(piece.kind), materials.get(piece.material)) {
+                (Some(definition), Some(material)) => {
+                    pieces.push((definition, material, transform));
+                }
+                _ => unknown = true,
+            }
+        }
+        let results = check.plan(&pieces);
+
+        let prepaid = event.policy == BlueprintPolicy::AllOrNothing;
+        if prepaid {
+            let rejection = if unknown {
+                Some(BuildRejectReason::UnknownPiece)
+            } else {
+                results.iter().find_map(|result| result.err())
+            };
+            let cost = event.blueprint.cost(&catalogue, &materials);
+            let mut inventory = inventory_query.get_single_mut().ok().filter(|inventory| {
+                cost.iter()
+                    .all(|(item, count)| inventory.count(*item) >= *count)
+            });
+            let rejection = rejection.or(inventory
+                .is_none()
+                .then_some(BuildRejectReason::InsufficientResources));
+            if let Some(reason) = rejection {
+                rejected_events.send(BuildRejected { reason });
+                continue;
+            }
+            if let Some(inventory) = inventory.as_deref_mut() {
+                if pay(inventory, &cost, &registry).is_err() {
+                    rejected_events.send(BuildRejected {
+                        reason: BuildRejectReason::InsufficientResources,
+                    });
+                    continue;
+                }
+            }
+        }
+
+        let remaining: VecDeque<_> = pieces
+            .iter()
+            .zip(&results)
+            .filter(|(_, result)| result.is_ok())
+            .map(|((definition, material, transform), _)| {
+                (definition.kind, material.id, *transform)
+            })
+            .collect();
+        let skipped = event.blueprint.pieces.len() - remaining.len();
+        let construction = BlueprintConstruction {
+            remaining,
+            until_next: 0.0,
+            prepaid,
+            placed: 0,
+            skipped,
+        };
+        let pieces = construction.remaining();
+        let construction = commands
+            .spawn((construction, Name::new("Blueprint Construction")))
+            .id();
+        started_events.send(BlueprintStarted {
+            construction,
+            pieces,
+            skipped,
+        });
+    }
+}
+
+/// Takes a whole blueprint's cost from the inventory. If any of it can't be taken, what was
+/// already taken is given back.
+fn pay(
+    inventory: &mut Inventory,
+    cost: &HashMap<ItemId, usize>,
+    registry: &ItemRegistry,
+) -> Result<(), InventoryError> {
+    let mut paid = Vec::new();
+    for (&item, &count) in cost {
+        if let Err(err) = inventory.remove(item, count) {
+            for (item, count) in paid {
+                refund(inventory, item, count, registry);
+            }
+            return Err(err);
+        }
+        paid.push((item, count));
+    }
+    Ok(())
+}
+
+/// Gives back the items a prepaid piece that couldn't go up was paid with.
+fn refund(inventory: &mut Inventory, item: ItemId, count: usize, registry: &ItemRegistry) {
+    if count == 0 {
+        return;
+    }
+    if let Err(err) = inventory.add(ItemStack::new(item, count as u32), registry) {
+        warn!("Failed to refund a blueprint piece: {}", err);
+    }
+}
+
+/// Raises the next piece of each blueprint under construction when it's due, checking and paying
+/// for it like any single piece. Pieces paid for up front are refunded if they can't go up.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn build_blueprints(
+    mut commands: Commands,
+    time: Res<Time>,
+    staging: Res<BlueprintStaging>,
+    mut construction_query: Query<(Entity, &mut BlueprintConstruction)>,
+    check: PlacementCheck,
+    mut inventory_query: Query<&mut Inventory, With<Player>>,
+    registry: Res<ItemRegistry>,
+    mut completed_events: EventWriter<BlueprintCompleted>,
+    mut rejected_events: EventWriter<BuildRejected>,
+) {
+    let mut inventory = inventory_query.get_single_mut().ok();
+    // Pieces spawned this frame aren't visible to the checks yet, so only one blueprint piece
+    // goes up per frame.
+    let mut built = false;
+
+    for (entity, mut construction) in construction_query.iter_mut() {
+        construction.until_next -= time.delta_seconds();
+        if construction.until_next > 0.0 || built {
+            continue;
+        }
+        if let Some((kind, material_id, transform)) = construction.remaining.pop_front() {
+            construction.until_next = staging.interval;
+            built = true;
+            let (Some(definition), Some(material)) =
+                (check.definition(kind), check.material_by_id(material_id))
+            else {
+                construction.skipped += 1;
+                continue;
+            };
+            let result = if construction.prepaid {
+                check.check_placement(definition, material, &transform)
+            } else {
+                check
+                    .check_material(definition, material, &transform, inventory.as_deref())
+                    .and_then(|placement| match inventory.as_deref_mut() {
+                        Some(inventory) => inventory
+                            .remove(material.item, definition.cost)
+                            .map(|()| placement)
+                            .map_err(|_| BuildRejectReason::InsufficientResources),
+                        None => Ok(placement),
+                    })
+            };
+            match result {
+                Ok(placement) => {
+                    placement::spawn_piece(
+                        &mut commands,
+                        definition,
+                        material,
+                        placement,
+                        transform,
+                    );
+                    construction.placed += 1;
+                }
+                Err(reason) => {
+                    rejected_events.send(BuildRejected { reason });
+                    construction.skipped += 1;
+                    if let Some(inventory) = inventory.as_deref_mut() {
+                        if construction.prepaid {
+                            refund(inventory, material.item, definition.cost, &registry);
+                        }
+                    }
+                }
+            }
+        }
+        if construction.remaining.is_empty() {
+            completed_events.send(BlueprintCompleted {
+                construction: entity,
+                placed: construction.placed,
+                skipped: construction.skipped,
+            });
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn piece(kind: BuildPieceKind, stability: f32) -> BuildPiece {
+        BuildPiece {
+            kind,
+            material_id: MaterialId::WOOD,
+            health: 100.0,
+            stability,
+        }
+    }
+
+    /// A wall with a floor resting on it, captured with the floor first.
+    fn shelter() -> Blueprint {
+        let floor = piece(BuildPieceKind::FLOOR, 0.9);
+        let wall = piece(BuildPieceKind::WALL, 1.0);
+        let floor_at = Transform::from_xyz(4.0, 2.0, 6.0);
+        let wall_at = Transform::from_xyz(4.0, 0.0, 7.0);
+        Blueprint::capture("Shelter", [(&floor, &floor_at), (&wall, &wall_at)])
+    }
+
+    #[test]
+    fn test_capture_is_relative_and_supports_first() {
+        let blueprint = shelter();
+        assert_eq!(blueprint.pieces[0].kind, BuildPieceKind::WALL);
+        assert_eq!(blueprint.pieces[0].translation, Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(blueprint.pieces[1].translation, Vec3::new(0.0, 2.0, 0.0));
+
+        let cost = blueprint.cost(
+            &BuildPieceCatalogue::default(),
+            &BuildMaterialRegistry::default(),
+        );
+        let catalogue = BuildPieceCatalogue::default();
+        let expected = catalogue.get(BuildPieceKind::WALL).unwrap().cost
+            + catalogue.get(BuildPieceKind::FLOOR).unwrap().cost;
+        assert_eq!(cost, HashMap::from([(ItemId(1), expected)]));
+    }
+
+    #[test]
+    fn test_layout_turns_with_the_origin() {
+        let blueprint = shelter();
+        let origin =
+            Transform::from_xyz(2.0, 0.0, 0.0).with_rotation(Quat::from_rotation_y(FRAC_PI_2));
+        let layout: Vec<_> = blueprint
+            .layout(&origin)
+            .map(|(_, transform)| transform.translation)
+            .collect();
+        // The wall on the cell's +Z edge ends up on its +X edge.
+        assert_eq!(layout, [Vec3::new(3.0, 0.0, 0.0), Vec3::new(2.0, 2.0, 0.0)]);
+    }
+
+    #[test]
+    fn test_blueprints_round_trip_through_ron() {
+        let blueprint = shelter();
+        let source = config::to_ron(&blueprint).unwrap();
+        let loaded: Blueprint = config::from_ron(&source).unwrap();
+        assert_eq!(loaded, blueprint);
+    }
+
+    #[test]
+    fn test_failed_payment_gives_back_what_was_taken() {
+        let registry = ItemRegistry::default();
+        let wood = registry.id("wood").unwrap();
+        let metal = registry.id("metal").unwrap();
+        let mut inventory = Inventory::default();
+        inventory.add(ItemStack::new(wood, 4), &registry).unwrap();
+
+        let cost = HashMap::from([(wood, 3), (metal, 1)]);
+        assert!(pay(&mut inventory, &cost, &registry).is_err());
+        assert_eq!(inventory.count(wood), 4);
+
+        inventory.add(ItemStack::new(metal, 1), &registry).unwrap();
+        assert!(pay(&mut inventory, &cost, &registry).is_ok());
+        assert_eq!((inventory.count(wood), inventory.count(metal)), (1, 0));
+    }
+}
@@ -0,0 +1,623 @@
+//! The data-driven catalogue of pieces players can build.
+//!
+//! Each piece is a `BuildPieceDefinition`: its shape, where it sits on the grid, what it costs,
+//! how it carries load, where other pieces snap onto it, and any door or trap behaviour. Sockets
+//! accept pieces by tag, so a new kind snaps onto the built-in pieces by carrying their tags. The
+//! built-in pieces can be replaced or extended from a RON file without code changes; pieces in the
+//! file override built-ins of the same kind.
+use super::placement::BUILD_GRID_SIZE;
+use super::BuildPieceKind;
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use common::config::{self, ConfigError};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI, SQRT_2};
+use std::fmt;
+use std::path::Path;
+
+/// Where the piece catalogue is loaded from by default.
+pub const DEFAULT_BUILD_CATALOGUE_PATH: &str = "config/building.ron";
+
+/// The thickness of walls, floors, ramps and roofs.
+const PIECE_THICKNESS: f32 = 0.2;
+
+/// Which part of the building grid a piece takes up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PiecePlacement {
+    /// A whole cell, like a floor.
+    Cell,
+    /// The edge between two cells, like a wall. The piece's forward faces out of the cell.
+    Edge,
+}
+
+/// A solid box, relative to the origin of the piece it belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PieceBox {
+    pub center: Vec3,
+    #[serde(default)]
+    pub rotation: Quat,
+    pub half_extents: Vec3,
+}
+
+impl PieceBox {
+    /// An axis-aligned box spanning `min` to `max`.
+    pub fn from_corners(min: Vec3, max: Vec3) -> Self {
+        Self {
+            center: (min + max) / 2.0,
+            rotation: Quat::IDENTITY,
+            half_extents: (max - min) / 2.0,
+        }
+    }
+
+    /// The collider of the box.
+    pub fn collider(&self) -> (Vec3, Quat, Collider) {
+        let Vec3 { x, y, z } = self.half_extents;
+        (self.center, self.rotation, Collider::cuboid(x, y, z))
+    }
+
+    /// The height of the box's lowest corner.
+    pub fn bottom(&self) -> f32 {
+        let extent = (self.rotation * Vec3::X * self.half_extents.x).y.abs()
+            + (self.rotation * Vec3::Y * self.half_extents.y).y.abs()
+            + (self.rotation * Vec3::Z * self.half_extents.z).y.abs();
+        self.center.y - extent
+    }
+}
+
+/// A point on a piece that other pieces snap onto.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BuildSocket {
+    /// Where an attached piece's origin goes, relative to this piece.
+    pub position: Vec3,
+    /// The rotation of an attached piece, relative to this piece.
+    #[serde(default)]
+    pub rotation: Quat,
+    /// The tags of the pieces that can attach here.
+    pub accepts: Vec<String>,
+}
+
+impl BuildSocket {
+    /// Whether a piece can attach here, by having one of the accepted tags.
+    pub fn accepts_piece(&self, definition: &BuildPieceDefinition) -> bool {
+        self.accepts.iter().any(|tag| definition.tags.contains(tag))
+    }
+}
+
+/// A hinged door leaf that opens and closes.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DoorDefinition {
+    /// The leaf's box while closed.
+    pub leaf: PieceBox,
+    /// The point the leaf swings around, relative to the piece.
+    pub hinge: Vec3,
+    /// How far the leaf swings around the vertical when open, in radians.
+    pub open_angle: f32,
+}
+
+/// A volume that hurts whatever steps into it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TrapDefinition {
+    /// The trigger volume, relative to the piece.
+    pub trigger: PieceBox,
+    pub damage: f32,
+    /// How long the trap takes to re-arm after going off, in seconds.
+    pub cooldown: f32,
+}
+
+/// Everything about one kind of piece.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BuildPieceDefinition {
+    pub kind: BuildPieceKind,
+    pub name: String,
+    pub placement: PiecePlacement,
+    /// What sort of piece this is, for sockets to accept it by.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// How many of its material's items the piece takes to build.
+    pub cost: usize,
+    /// How much stability the piece loses relative to what holds it up, before its material's
+    /// strength.
+    pub stability_loss: f32,
+    /// The solid boxes making up the piece.
+    pub shape: Vec<PieceBox>,
+    #[serde(default)]
+    pub sockets: Vec<BuildSocket>,
+    /// Foundations can sink into terrain, and rest on the ground from their bottom.
+    #[serde(default)]
+    pub foundation: bool,
+    #[serde(default)]
+    pub door: Option<DoorDefinition>,
+    #[serde(default)]
+    pub trap: Option<TrapDefinition>,
+}
+
+impl BuildPieceDefinition {
+    /// The piece's collider, made up of its shape's boxes.
+    pub fn collider(&self) -> Collider {
+        Collider::compound(self.shape.iter().map(PieceBox::collider).collect())
+    }
+
+    /// The height of the piece's lowest point, relative to its origin.
+    pub fn bottom(&self) -> f32 {
+        self.shape
+            .iter()
+            .map(PieceBox::bottom)
+            .reduce(f32::min)
+            .unwrap_or(0.0)
+    }
+}
+
+/// Why a set of piece definitions was rejected.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuildCatalogueError {
+    /// Two pieces share a kind.
+    DuplicateKind(BuildPieceKind),
+}
+
+impl fmt::Display for BuildCatalogueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildCatalogueError::DuplicateKind(kind) => {
+                write!(f, "build piece kind {} is used twice", kind.0)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BuildCatalogueError {}
+
+/// The definitions of every piece, as written in data files.
+#[derive(Serialize, Deserialize)]
+struct PieceList {
+    pieces: Vec<BuildPieceDefinition>,
+}
+
+/// Every piece that can be built.
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "PieceList", into = "PieceList")]
+pub struct BuildPieceCatalogue {
+    pieces: Vec<BuildPieceDefinition>,
+}
+
+impl TryFrom<PieceList> for BuildPieceCatalogue {
+    type Error = BuildCatalogueError;
+
+    fn try_from(list: PieceList) -> Result<Self, Self::Error> {
+        Self::new(list.pieces)
+    }
+}
+
+impl From<BuildPieceCatalogue> for PieceList {
+    fn from(catalogue: BuildPieceCatalogue) -> Self {
+        Self {
+            pieces: catalogue.pieces,
+        }
+    }
+}
+
+impl Default for BuildPieceCatalogue {
+    fn default() -> Self {
+        Self::new(built_in_pieces()).expect("built-in pieces have distinct kinds")
+    }
+}
+
+impl BuildPieceCatalogue {
+    /// Builds a catalogue from definitions, rejecting them if two share a kind.
+    pub fn new(pieces: Vec<BuildPieceDefinition>) -> Result<Self, BuildCatalogueError> {
+        let mut kinds = HashSet::new();
+        for piece in &pieces {
+            if !kinds.insert(piece.kind) {
+                return Err(BuildCatalogueError::DuplicateKind(piece.kind));
+            }
+        }
+        Ok(Self { pieces })
+    }
+
+    /// Loads a catalogue from a config file.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        config::load(path)
+    }
+
+    /// Loads a config file's pieces over the built-in ones. A missing file leaves just the
+    /// built-ins.
+    pub fn load_over_built_ins(path: &Path) -> Result<Self, ConfigError> {
+        let mut catalogue = Self::default();
+        if path.exists() {
+            catalogue.extend(Self::load(path)?);
+        }
+        Ok(catalogue)
+    }
+
+    /// Saves the catalogue to a config file, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> Result<(), ConfigError> {
+        config::save(self, path)
+    }
+
+    /// Adds a piece, replacing any previous one of the same kind.
+    pub fn register(&mut self, definition: BuildPieceDefinition) {
+        match self
+            .pieces
+            .iter_mut()
+            .find(|piece| piece.kind == definition.kind)
+        {
+            Some(piece) => *piece = definition,
+            None => self.pieces.push(definition),
+        }
+    }
+
+    /// Adds every piece of another catalogue, replacing any of the same kinds.
+    pub fn extend(&mut self, other: BuildPieceCatalogue) {
+        for definition in other.pieces {
+            self.register(definition);
+        }
+    }
+
+    /// Gets the definition of a kind of piece.
+    pub fn get(&self, kind: BuildPieceKind) -> Option<&BuildPieceDefinition> {
+        self.pieces.iter().find(|piece| piece.kind == kind)
+    }
+
+    /// Every piece, in catalogue order.
+    pub fn iter(&self) -> impl Iterator<Item = &BuildPieceDefinition> {
+        self.pieces.iter()
+    }
+
+    /// The kind after `kind` in catalogue order, wrapping around.
+    pub fn next(&self, kind: BuildPieceKind) -> BuildPieceKind {
+        let index = self.pieces.iter().position(|piece| piece.kind == kind);
+        let next = index.map_or(0, |index| (index + 1) % self.pieces.len());
+        self.pieces.get(next).map_or(kind, |piece| piece.kind)
+    }
+}
+
+/// The tag of pieces that fill a cell.
+pub const CELL_TAG: &str = "cell";
+/// The tag of pieces that stand on an edge.
+pub const EDGE_TAG: &str = "edge";
+/// The tag of pieces that can rest on top of walls, like floors and roofs.
+pub const SPAN_TAG: &str = "span";
+
+/// A list of tags, for sockets to accept or pieces to carry.
+fn tags(tags: &[&str]) -> Vec<String> {
+    tags.iter().map(|tag| tag.to_string()).collect()
+}
+
+/// Sockets for the four edges of a cell at `height`, and the four cells beside it.
+fn cell_sockets(height: f32) -> Vec<BuildSocket> {
+    let half_cell = BUILD_GRID_SIZE / 2.0;
+    let edges = [0.0, FRAC_PI_2, PI, -FRAC_PI_2].map(|yaw| {
+        let rotation = Quat::from_rotation_y(yaw);
+        BuildSocket {
+            position: rotation * Vec3::Z * half_cell + Vec3::Y * height,
+            rotation,
+            accepts: tags(&[EDGE_TAG]),
+        }
+    });
+    let cells = [Vec3::X, Vec3::NEG_X, Vec3::Z, Vec3::NEG_Z].map(|direction| BuildSocket {
+        position: direction * BUILD_GRID_SIZE + Vec3::Y * height,
+        rotation: Quat::IDENTITY,
+        accepts: tags(&[CELL_TAG]),
+    });
+    edges.into_iter().chain(cells).collect()
+}
+
+/// Sockets on top of an edge piece: another edge piece stacked on it, and the cells on either
+/// side at its top, where floors and roofs rest on it.
+fn edge_sockets(height: f32) -> Vec<BuildSocket> {
+    let half_cell = BUILD_GRID_SIZE / 2.0;
+    let top = Vec3::Y * height;
+    vec![
+        BuildSocket {
+            position: top,
+            rotation: Quat::IDENTITY,
+            accepts: tags(&[EDGE_TAG]),
+        },
+        // Roofs rise away from the wall they rest on, towards their forward.
+        BuildSocket {
+            position: top + Vec3::NEG_Z * half_cell,
+            rotation: Quat::IDENTITY,
+            accepts: tags(&[SPAN_TAG]),
+        },
+        BuildSocket {
+            position: top + Vec3::Z * half_cell,
+            rotation: Quat::from_rotation_y(PI),
+            accepts: tags(&[SPAN_TAG]),
+        },
+    ]
+}
+
+/// A slab across a cell's diagonal, rising one level towards the piece's forward (-Z).
+fn slope_box() -> PieceBox {
+    let half_cell = BUILD_GRID_SIZE / 2.0;
+    PieceBox {
+        center: Vec3::Y * half_cell,
+        rotation: Quat::from_rotation_x(FRAC_PI_4),
+        half_extents: Vec3::new(half_cell, PIECE_THICKNESS / 2.0, half_cell * SQRT_2),
+    }
+}
+
+/// An edge piece spanning the cell's width, from `bottom` to `top`.
+fn edge_box(from_x: f32, to_x: f32, bottom: f32, top: f32) -> PieceBox {
+    let half_thickness = PIECE_THICKNESS / 2.0;
+    PieceBox::from_corners(
+        Vec3::new(from_x, bottom, -half_thickness),
+        Vec3::new(to_x, top, half_thickness),
+    )
+}
+
+/// The pieces available out of the box.
+fn built_in_pieces() -> Vec<BuildPieceDefinition> {
+    let half_cell = BUILD_GRID_SIZE / 2.0;
+    let level = BUILD_GRID_SIZE;
+    let piece = |kind, name: &str, placement, cost, stability_loss, shape| BuildPieceDefinition {
+        kind,
+        name: name.to_string(),
+        placement,
+        tags: match placement {
+            PiecePlacement::Cell => tags(&[CELL_TAG]),
+            PiecePlacement::Edge => tags(&[EDGE_TAG]),
+        },
+        cost,
+        stability_loss,
+        shape,
+        sockets: Vec::new(),
+        foundation: false,
+        door: None,
+        trap: None,
+    };
+    let floor_box = PieceBox::from_corners(
+        Vec3::new(-half_cell, 0.0, -half_cell),
+        Vec3::new(half_cell, PIECE_THICKNESS, half_cell),
+    );
+    let steps = 4;
+    let step_height = level / steps as f32;
+    let step_depth = BUILD_GRID_SIZE / steps as f32;
+
+    // Walls carry load well; floors, ramps and roofs reach three pieces out from a grounded wall.
+    vec![
+        BuildPieceDefinition {
+            sockets: edge_sockets(level),
+            ..piece(
+                BuildPieceKind::WALL,
+                "Wall",
+                PiecePlacement::Edge,
+                2,
+                0.1,
+                vec![edge_box(-half_cell, half_cell, 0.0, level)],
+            )
+        },
+        BuildPieceDefinition {
+            tags: tags(&[CELL_TAG, SPAN_TAG]),
+            sockets: cell_sockets(0.0),
+            ..piece(
+                BuildPieceKind::FLOOR,
+                "Floor",
+                PiecePlacement::Cell,
+                2,
+                0.25,
+                vec![floor_box],
+            )
+        },
+        BuildPieceDefinition {
+            sockets: vec![BuildSocket {
+                position: Vec3::new(0.0, level, -BUILD_GRID_SIZE),
+                rotation: Quat::IDENTITY,
+                accepts: tags(&[CELL_TAG]),
+            }],
+            ..piece(
+                BuildPieceKind::RAMP,
+                "Ramp",
+                PiecePlacement::Cell,
+                3,
+                0.25,
+                vec![slope_box()],
+            )
+        },
+        BuildPieceDefinition {
+            tags: tags(&[CELL_TAG, SPAN_TAG]),
+            ..piece(
+                BuildPieceKind::ROOF,
+                "Roof",
+                PiecePlacement::Cell,
+                3,
+                0.25,
+                vec![slope_box()],
+            )
+        },
+        BuildPieceDefinition {
+            door: Some(DoorDefinition {
+                leaf: edge_box(-0.5, 0.5, 0.0, 1.8),
+                hinge: Vec3::new(-0.5, 0.0, 0.0),
+                open_angle: FRAC_PI_2,
+            }),
+            sockets: edge_sockets(level),
+            ..piece(
+                BuildPieceKind::DOOR,
+                "Door",
+                PiecePlacement::Edge,
+                3,
+                0.1,
+                vec![
+                    edge_box(-half_cell, -0.5, 0.0, level),
+                    edge_box(0.5, half_cell, 0.0, level),
+                    edge_box(-0.5, 0.5, 1.8, level),
+                ],
+            )
+        },
+        BuildPieceDefinition {
+            sockets: edge_sockets(level),
+            ..piece(
+                BuildPieceKind::WINDOW,
+                "Window Frame",
+                PiecePlacement::Edge,
+                2,
+                0.1,
+                vec![
+                    edge_box(-half_cell, half_cell, 0.0, 0.8),
+                    edge_box(-half_cell, half_cell, 1.6, level),
+                    edge_box(-half_cell, -0.6, 0.8, 1.6),
+                    edge_box(0.6, half_cell, 0.8, 1.6),
+                ],
+            )
+        },
+        BuildPieceDefinition {
+            sockets: vec![BuildSocket {
+                position: Vec3::new(0.0, level, -BUILD_GRID_SIZE),
+                rotation: Quat::IDENTITY,
+                accepts: tags(&[CELL_TAG]),
+            }],
+            // Solid steps rising towards the piece's forward (-Z).
+            ..piece(
+                BuildPieceKind::STAIRS,
+                "Stairs",
+                PiecePlacement::Cell,
+                3,
+                0.25,
+                (0..steps)
+                    .map(|step| {
+                        let back = half_cell - step as f32 * step_depth;
+                        PieceBox::from_corners(
+                            Vec3::new(-half_cell, 0.0, back - step_depth),
+                            Vec3::new(half_cell, (step + 1) as f32 * step_height, back),
+                        )
+                    })
+                    .collect(),
+            )
+        },
+        piece(
+            BuildPieceKind::HALF_WALL,
+            "Half Wall",
+            PiecePlacement::Edge,
+            1,
+            0.1,
+            vec![edge_box(-half_cell, half_cell, 0.0, level / 2.0)],
+        ),
+        BuildPieceDefinition {
+            foundation: true,
+            sockets: cell_sockets(0.0),
+            ..piece(
+                BuildPieceKind::FOUNDATION,
+                "Foundation",
+                PiecePlacement::Cell,
+                4,
+                0.25,
+                vec![PieceBox::from_corners(
+                    Vec3::new(-half_cell, -half_cell, -half_cell),
+                    Vec3::new(half_cell, PIECE_THICKNESS, half_cell),
+                )],
+            )
+        },
+        BuildPieceDefinition {
+            trap: Some(TrapDefinition {
+                trigger: PieceBox::from_corners(
+                    Vec3::new(-0.9, PIECE_THICKNESS, -0.9),
+                    Vec3::new(0.9, 1.0, 0.9),
+                ),
+                damage: 20.0,
+                cooldown: 1.0,
+            }),
+            ..piece(
+                BuildPieceKind::SPIKE_TRAP,
+                "Spike Trap",
+                PiecePlacement::Cell,
+                3,
+                0.25,
+                vec![floor_box],
+            )
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalogue_round_trips_through_ron() {
+        let catalogue = BuildPieceCatalogue::default();
+        let source = config::to_ron(&catalogue).unwrap();
+        let loaded: BuildPieceCatalogue = config::from_ron(&source).unwrap();
+        assert_eq!(loaded, catalogue);
+    }
+
+    #[test]
+    fn test_new_kinds_register_without_code() {
+        let source = r#"(
+            pieces: [(
+                kind: (100),
+                name: "Pillar",
+                placement: Cell,
+                tags: ["cell"],
+                cost: 1,
+                stability_loss: 0.05,
+                shape: [(center: (0.0, 1.0, 0.0), half_extents: (0.2, 1.0, 0.2))],
+            )],
+        )"#;
+        let mut catalogue = BuildPieceCatalogue::default();
+        catalogue.extend(config::from_ron(source).unwrap());
+
+        let pillar = catalogue.get(BuildPieceKind(100)).unwrap();
+        assert_eq!(pillar.name, "Pillar");
+        assert_eq!(pillar.shape[0].rotation, Quat::IDENTITY);
+        assert!(pillar.sockets.is_empty() && pillar.door.is_none());
+        // Its tag lets it snap beside a built-in floor, but not onto a wall's edge.
+        let floor = catalogue.get(BuildPieceKind::FLOOR).unwrap();
+        let wall = catalogue.get(BuildPieceKind::WALL).unwrap();
+        assert!(floor
+            .sockets
+            .iter()
+            .any(|socket| socket.accepts_piece(pillar)));
+        assert!(!wall
+            .sockets
+            .iter()
+            .any(|socket| socket.accepts_piece(pillar)));
+        // Cycling reaches the new piece after the built-ins, then wraps around.
+        let last_built_in = BuildPieceKind::SPIKE_TRAP;
+        assert_eq!(catalogue.next(last_built_in), BuildPieceKind(100));
+        assert_eq!(
+            catalogue.next(BuildPieceKind(100)),
+            catalogue.iter().next().unwrap().kind
+        );
+    }
+
+    #[test]
+    fn test_data_overrides_built_ins_and_rejects_duplicates() {
+        let mut floor = BuildPieceCatalogue::default()
+            .get(BuildPieceKind::FLOOR)
+            .unwrap()
+            .clone();
+        floor.cost = 5;
+        let mut catalogue = BuildPieceCatalogue::default();
+        catalogue.extend(BuildPieceCatalogue::new(vec![floor.clone()]).unwrap());
+        assert_eq!(catalogue.get(BuildPieceKind::FLOOR).unwrap().cost, 5);
+        assert_eq!(catalogue.iter().count(), built_in_pieces().len());
+
+        assert_eq!(
+            BuildPieceCatalogue::new(vec![floor.clone(), floor.clone()]),
+            Err(BuildCatalogueError::DuplicateKind(BuildPieceKind::FLOOR))
+        );
+        let source = config::to_ron(&PieceList {
+            pieces: vec![floor.clone(), floor],
+        })
+        .unwrap();
+        let err = config::from_ron::<BuildPieceCatalogue>(&source).unwrap_err();
+        assert!(matches!(err, ConfigError::Parse(_)));
+        assert!(err.to_string().contains("kind 1 is used twice"));
+    }
+
+    #[test]
+    fn test_missing_file_leaves_the_built_ins() {
+        let path = Path::new("does/not/exist/building.ron");
+        let catalogue = BuildPieceCatalogue::load_over_built_ins(path).unwrap();
+        assert_eq!(catalogue, BuildPieceCatalogue::default());
+    }
+
+    #[test]
+    fn test_piece_bottoms() {
+        let foundation = BuildPieceCatalogue::default();
+        let foundation = foundation.get(BuildPieceKind::FOUNDATION).unwrap();
+        assert_eq!(foundation.bottom(), -BUILD_GRID_SIZE / 2.0);
+        assert!(slope_box().bottom() < 0.0);
+    }
+}
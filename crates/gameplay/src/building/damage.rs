@@ -0,0 +1,311 @@
+//! Damaging, repairing and upgrading placed pieces.
+//!
+//! Damage is reduced by the resistances of a piece's material, and a piece whose health runs out
+//! is destroyed, which can bring down whatever it held up. Repairs and upgrades are paid for from
+//! the player's inventory; upgrading swaps the material of the same piece for the next tier.
+use super::catalogue::{BuildPieceCatalogue, BuildPieceDefinition};
+use super::placement::BuildSlot;
+use super::{BuildMaterialRegistry, BuildPiece, BuildPieceKind, BuildRejectReason, BuildRejected};
+use super::{Material, MaterialId};
+use crate::health::DamageSource;
+use crate::inventory::stack::ItemContainer;
+use crate::inventory::Inventory;
+use crate::player::Player;
+use bevy::prelude::*;
+
+/// How worn a piece looks, for picking its model and effects.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum BuildDamageState {
+    #[default]
+    Intact,
+    Damaged,
+    Critical,
+}
+
+impl BuildDamageState {
+    /// The state of a piece with `health` out of `max_health` left.
+    pub fn from_health(health: f32, max_health: f32) -> Self {
+        let fraction = if max_health > 0.0 {
+            health / max_health
+        } else {
+            0.0
+        };
+        if fraction > 2.0 / 3.0 {
+            BuildDamageState::Intact
+        } else if fraction > 1.0 / 3.0 {
+            BuildDamageState::Damaged
+        } else {
+            BuildDamageState::Critical
+        }
+    }
+}
+
+/// An event requesting that damage be dealt to a placed piece.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct BuildPieceDamage {
+    pub target: Entity,
+    pub amount: f32,
+    pub source: DamageSource,
+}
+
+/// An event requesting that the player repair a piece to full health.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildPieceRepair {
+    pub target: Entity,
+}
+
+/// An event requesting that the player upgrade a piece to its material's next tier.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildPieceUpgrade {
+    pub target: Entity,
+}
+
+/// An event sent when a piece takes damage, after its material's resistance.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct BuildPieceDamaged {
+    pub entity: Entity,
+    pub dealt: f32,
+    pub source: DamageSource,
+}
+
+/// An event sent when a piece's damage state changes, in either direction.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildDamageStateChanged {
+    pub entity: Entity,
+    pub state: BuildDamageState,
+}
+
+/// An event sent when a piece's health runs out, just before it's removed.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildPieceDestroyed {
+    pub entity: Entity,
+    pub kind: BuildPieceKind,
+    pub slot: BuildSlot,
+    pub source: DamageSource,
+}
+
+/// An event sent when a piece is repaired.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct BuildPieceRepaired {
+    pub entity: Entity,
+    pub health: f32,
+}
+
+/// An event sent when a piece is upgraded to a new material.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildPieceUpgraded {
+    pub entity: Entity,
+    pub from: MaterialId,
+    pub to: MaterialId,
+}
+
+/// How many of its material's items fully repairing a piece with `health` left takes.
+pub fn repair_cost(definition: &BuildPieceDefinition, health: f32, material: &Material) -> usize {
+    if material.base_hp <= 0.0 {
+        return 0;
+    }
+    let missing = ((material.base_hp - health) / material.base_hp).clamp(0.0, 1.0);
+    (missing * definition.cost as f32).ceil() as usize
+}
+
+/// Updates a piece's damage state from its health, reporting when it changes.
+fn refresh_damage_state(
+    entity: Entity,
+    piece: &BuildPiece,
+    material: &Material,
+    state: &mut BuildDamageState,
+    state_events: &mut EventWriter<BuildDamageStateChanged>,
+) {
+    let new_state = BuildDamageState::from_health(piece.health, material.base_hp);
+    if *state != new_state {
+        *state = new_state;
+        state_events.send(BuildDamageStateChanged {
+            entity,
+            state: new_state,
+        });
+    }
+}
+
+/// Applies queued `BuildPieceDamage`, destroying pieces whose health runs out.
+pub(super) fn apply_build_piece_damage(
+    mut commands: Commands,
+    mut damage_events: EventReader<BuildPieceDamage>,
+    mut piece_query: Query<(&mut BuildPiece, &mut BuildDamageState, &BuildSlot)>,
+    materials: Res<BuildMaterialRegistry>,
+    mut damaged_events: EventWriter<BuildPieceDamaged>,
+    mut state_events: EventWriter<BuildDamageStateChanged>,
+    mut destroyed_events: EventWriter<BuildPieceDestroyed>,
+) {
+    for event in damage_events.read() {
+        let Ok((mut piece, mut state, slot)) = piece_query.get_mut(event.target) else {
+            continue;
+        };
+        // Already destroyed earlier this frame.
+        if piece.health <= 0.0 {
+            continue;
+        }
+        let Some(material) = materials.get(piece.material_id) else {
+            continue;
+        };
+
+        let amount = event.amount.max(0.0) * (1.0 - material.resistance(event.source));
+        let dealt = amount.max(0.0).min(piece.health);
+        piece.health -= dealt;
+        damaged_events.send(BuildPieceDamaged {
+            entity: event.target,
+            dealt,
+            source: event.source,
+        });
+        refresh_damage_state(
+            event.target,
+            &piece,
+            material,
+            &mut state,
+            &mut state_events,
+        );
+
+        if piece.health <= 0.0 {
+            destroyed_events.send(BuildPieceDestroyed {
+                entity: event.target,
+                kind: piece.kind,
+                slot: *slot,
+                source: event.source,
+            });
+            commands.entity(event.target).despawn_recursive();
+        }
+    }
+}
+
+/// Repairs pieces to full health, paying for the missing share of their cost.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn repair_build_pieces(
+    mut repair_events: EventReader<BuildPieceRepair>,
+    mut piece_query: Query<(&mut BuildPiece, &mut BuildDamageState)>,
+    mut inventory_query: Query<&mut Inventory, With<Player>>,
+    materials: Res<BuildMaterialRegistry>,
+    catalogue: Res<BuildPieceCatalogue>,
+    mut repaired_events: EventWriter<BuildPieceRepaired>,
+    mut state_events: EventWriter<BuildDamageStateChanged>,
+    mut rejected_events: EventWriter<BuildRejected>,
+) {
+    for event in repair_events.read() {
+        let Ok((mut piece, mut state)) = piece_query.get_mut(event.target) else {
+            continue;
+        };
+        let (Some(material), Some(definition)) =
+            (materials.get(piece.material_id), catalogue.get(piece.kind))
+        else {
+            continue;
+        };
+        if piece.health >= material.base_hp {
+            continue;
+        }
+
+        let cost = repair_cost(definition, piece.health, material);
+        let paid = inventory_query
+            .get_single_mut()
+            .is_ok_and(|mut inventory| inventory.remove(material.item, cost).is_ok());
+        if !paid {
+            rejected_events.send(BuildRejected {
+                reason: BuildRejectReason::InsufficientResources,
+            });
+            continue;
+        }
+
+        piece.health = material.base_hp;
+        repaired_events.send(BuildPieceRepaired {
+            entity: event.target,
+            health: piece.health,
+        });
+        refresh_damage_state(
+            event.target,
+            &piece,
+            material,
+            &mut state,
+            &mut state_events,
+        );
+    }
+}
+
+/// Swaps pieces over to their material's next tier, keeping the share of health they had.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn upgrade_build_pieces(
+    mut upgrade_events: EventReader<BuildPieceUpgrade>,
+    mut piece_query: Query<(&mut BuildPiece, &mut BuildDamageState)>,
+    mut inventory_query: Query<&mut Inventory, With<Player>>,
+    materials: Res<BuildMaterialRegistry>,
+    catalogue: Res<BuildPieceCatalogue>,
+    mut upgraded_events: EventWriter<BuildPieceUpgraded>,
+    mut state_events: EventWriter<BuildDamageStateChanged>,
+    mut rejected_events: EventWriter<BuildRejected>,
+) {
+    for event in upgrade_events.read() {
+        let Ok((mut piece, mut state)) = piece_query.get_mut(event.target) else {
+            continue;
+        };
+        let (Some(from), Some(definition)) =
+            (materials.get(piece.material_id), catalogue.get(piece.kind))
+        else {
+            continue;
+        };
+        let Some(to) = from.upgrade.and_then(|id| materials.get(id)) else {
+            rejected_events.send(BuildRejected {
+                reason: BuildRejectReason::FullyUpgraded,
+            });
+            continue;
+        };
+
+        let paid = inventory_query
+            .get_single_mut()
+            .is_ok_and(|mut inventory| inventory.remove(to.item, definition.cost).is_ok());
+        if !paid {
+            rejected_events.send(BuildRejected {
+                reason: BuildRejectReason::InsufficientResources,
+            });
+            continue;
+        }
+
+        if from.base_hp > 0.0 {
+            piece.health = piece.health / from.base_hp * to.base_hp;
+        }
+        piece.material_id = to.id;
+        upgraded_events.send(BuildPieceUpgraded {
+            entity: event.target,
+            from: from.id,
+            to: to.id,
+        });
+        refresh_damage_state(event.target, &piece, to, &mut state, &mut state_events);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_damage_state_thresholds() {
+        assert_eq!(
+            BuildDamageState::from_health(100.0, 100.0),
+            BuildDamageState::Intact
+        );
+        assert_eq!(
+            BuildDamageState::from_health(50.0, 100.0),
+            BuildDamageState::Damaged
+        );
+        assert_eq!(
+            BuildDamageState::from_health(10.0, 100.0),
+            BuildDamageState::Critical
+        );
+    }
+
+    #[test]
+    fn test_repair_cost_scales_with_missing_health() {
+        let registry = BuildMaterialRegistry::default();
+        let wood = registry.get(MaterialId::WOOD).unwrap();
+        let catalogue = BuildPieceCatalogue::default();
+        let ramp = catalogue.get(BuildPieceKind::RAMP).unwrap();
+        assert_eq!(repair_cost(ramp, wood.base_hp, wood), 0);
+        assert_eq!(repair_cost(ramp, wood.base_hp * 0.9, wood), 1);
+        assert_eq!(repair_cost(ramp, 0.0, wood), ramp.cost);
+    }
+}
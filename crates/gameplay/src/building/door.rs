@@ -0,0 +1,93 @@
+//! Doors: pieces with a hinged leaf that opens, closes and locks.
+use super::catalogue::{BuildPieceCatalogue, DoorDefinition};
+use super::BuildPiece;
+use bevy::prelude::*;
+
+/// The state of a door piece.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Door {
+    pub open: bool,
+    /// Locked doors stay as they are until unlocked.
+    pub locked: bool,
+}
+
+/// The swinging part of a door, spawned as a child of the door piece.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct DoorLeaf;
+
+/// An event requesting that a door be opened if it's closed, or closed if it's open.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ToggleDoor {
+    pub target: Entity,
+}
+
+/// An event requesting that a door be locked or unlocked.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockDoor {
+    pub target: Entity,
+    pub locked: bool,
+}
+
+/// An event sent whenever a door opens, closes, locks or unlocks.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DoorChanged {
+    pub entity: Entity,
+    pub door: Door,
+}
+
+/// Where a door's leaf sits relative to the door piece.
+pub fn leaf_transform(definition: &DoorDefinition, open: bool) -> Transform {
+    let mut transform =
+        Transform::from_translation(definition.leaf.center).with_rotation(definition.leaf.rotation);
+    if open {
+        transform.rotate_around(
+            definition.hinge,
+            Quat::from_rotation_y(definition.open_angle),
+        );
+    }
+    transform
+}
+
+/// Opens, closes, locks and unlocks doors, swinging their leaves to match.
+pub(super) fn operate_doors(
+    mut toggle_events: EventReader<ToggleDoor>,
+    mut lock_events: EventReader<LockDoor>,
+    mut door_query: Query<(&mut Door, &BuildPiece, Option<&Children>)>,
+    mut leaf_query: Query<&mut Transform, With<DoorLeaf>>,
+    catalogue: Res<BuildPieceCatalogue>,
+    mut changed_events: EventWriter<DoorChanged>,
+) {
+    let locks = lock_events
+        .read()
+        .map(|event| (event.target, Some(event.locked)));
+    let toggles = toggle_events.read().map(|event| (event.target, None));
+    for (target, lock) in locks.chain(toggles).collect::<Vec<_>>() {
+        let Ok((mut door, piece, children)) = door_query.get_mut(target) else {
+            continue;
+        };
+        let before = *door;
+        match lock {
+            Some(locked) => door.locked = locked,
+            None if !door.locked => door.open = !door.open,
+            None => {}
+        }
+        if *door == before {
+            continue;
+        }
+
+        let definition = catalogue
+            .get(piece.kind)
+            .and_then(|definition| definition.door.as_ref());
+        if let Some(definition) = definition {
+            for child in children.into_iter().flatten() {
+                if let Ok(mut transform) = leaf_query.get_mut(*child) {
+                    *transform = leaf_transform(definition, door.open);
+                }
+            }
+        }
+        changed_events.send(DoorChanged {
+            entity: target,
+            door: *door,
+        });
+    }
+}
@@ -0,0 +1,239 @@
+//! Player building logic and APIs.
+use crate::health::{DamageEvent, DamageSource};
+use crate::inventory::ItemId;
+use bevy::prelude::*;
+use catalogue::{BuildPieceCatalogue, DEFAULT_BUILD_CATALOGUE_PATH};
+use common::input::ActionState;
+use preview::BuildMode;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+pub mod blueprint;
+pub mod catalogue;
+pub mod damage;
+pub mod door;
+pub mod placement;
+pub mod preview;
+pub mod sockets;
+pub mod support;
+pub mod trap;
+
+/// A unique identifier for a material.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MaterialId(pub u32);
+
+impl MaterialId {
+    /// The basic material every piece is built from.
+    pub const WOOD: Self = Self(0);
+    /// The tier wood upgrades to.
+    pub const METAL: Self = Self(1);
+    /// The top tier, upgraded from metal.
+    pub const REINFORCED: Self = Self(2);
+}
+
+/// Represents a material that can be used for building.
+#[derive(Debug, Clone)]
+pub struct Material {
+    pub id: MaterialId,
+    pub base_hp: f32,
+    /// The item consumed when building with this material.
+    pub item: ItemId,
+    /// How well pieces of this material hold each other up; divides the stability they lose.
+    pub strength: f32,
+    /// The fraction of each source's damage the material shrugs off. Negative values take extra.
+    pub resistances: HashMap<DamageSource, f32>,
+    /// The material pieces of this one upgrade to, if any.
+    pub upgrade: Option<MaterialId>,
+}
+
+impl Material {
+    /// The fraction of damage from `source` the material resists.
+    pub fn resistance(&self, source: DamageSource) -> f32 {
+        self.resistances.get(&source).copied().unwrap_or(0.0)
+    }
+}
+
+/// A resource that maps building material IDs to their properties.
+#[derive(Resource, Debug, Clone)]
+pub struct BuildMaterialRegistry {
+    materials: HashMap<MaterialId, Material>,
+}
+
+impl Default for BuildMaterialRegistry {
+    fn default() -> Self {
+        let mut registry = Self {
+            materials: HashMap::new(),
+        };
+        registry.register(Material {
+            id: MaterialId::WOOD,
+            base_hp: 100.0,
+            item: ItemId(1),
+            strength: 1.0,
+            resistances: HashMap::from([
+                (DamageSource::Bullet, 0.5),
+                (DamageSource::Explosion, -0.5),
+            ]),
+            upgrade: Some(MaterialId::METAL),
+        });
+        registry.register(Material {
+            id: MaterialId::METAL,
+            base_hp: 250.0,
+            item: ItemId(2),
+            strength: 1.5,
+            resistances: HashMap::from([
+                (DamageSource::Melee, 0.5),
+                (DamageSource::Bullet, 0.8),
+                (DamageSource::Explosion, 0.25),
+            ]),
+            upgrade: Some(MaterialId::REINFORCED),
+        });
+        registry.register(Material {
+            id: MaterialId::REINFORCED,
+            base_hp: 500.0,
+            item: ItemId(3),
+            strength: 2.0,
+            resistances: HashMap::from([
+                (DamageSource::Melee, 0.75),
+                (DamageSource::Bullet, 0.9),
+                (DamageSource::Explosion, 0.5),
+            ]),
+            upgrade: None,
+        });
+        registry
+    }
+}
+
+impl BuildMaterialRegistry {
+    /// Registers a material, replacing any previous one with the same ID.
+    pub fn register(&mut self, material: Material) {
+        self.materials.insert(material.id, material);
+    }
+
+    /// Gets the properties of a material.
+    pub fn get(&self, id: MaterialId) -> Option<&Material> {
+        self.materials.get(&id)
+    }
+}
+
+/// Represents a piece that can be built.
+#[derive(Component, Debug, Clone)]
+pub struct BuildPiece {
+    pub kind: BuildPieceKind,
+    pub material_id: MaterialId,
+    pub health: f32,
+    /// How well the piece is held up, from `0.0` to `support::GROUNDED_STABILITY` on the ground.
+    pub stability: f32,
+}
+
+/// The kind of a build piece, defined by its entry in the `BuildPieceCatalogue`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BuildPieceKind(pub u32);
+
+impl BuildPieceKind {
+    pub const WALL: Self = Self(0);
+    pub const FLOOR: Self = Self(1);
+    pub const RAMP: Self = Self(2);
+    pub const ROOF: Self = Self(3);
+    pub const DOOR: Self = Self(4);
+    pub const WINDOW: Self = Self(5);
+    pub const STAIRS: Self = Self(6);
+    pub const HALF_WALL: Self = Self(7);
+    pub const FOUNDATION: Self = Self(8);
+    pub const SPIKE_TRAP: Self = Self(9);
+}
+
+/// An event triggered when a player wants to build something.
+#[derive(Event)]
+pub struct BuildEvent {
+    pub piece: BuildPieceKind,
+    /// The material to build the piece from, paid for with its item.
+    pub material: MaterialId,
+    pub position: Vec3,
+    pub rotation: Quat,
+}
+
+/// Why a `BuildEvent` didn't result in a piece.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildRejectReason {
+    /// The piece would be inside solid terrain.
+    BlockedByTerrain,
+    /// Another piece already takes up the grid slot.
+    Occupied,
+    /// The piece would cut into another piece.
+    Overlapping,
+    /// Nothing would hold the piece up.
+    Unsupported,
+    /// The catalogue has no such piece, or there's no such building material.
+    UnknownPiece,
+    /// The player can't pay for the piece, its repair or its upgrade.
+    InsufficientResources,
+    /// The piece's material is already the top tier.
+    FullyUpgraded,
+}
+
+/// An event sent when a requested piece can't be built, repaired or upgraded.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildRejected {
+    pub reason: BuildRejectReason,
+}
+
+pub struct BuildingPlugin;
+
+impl Plugin for BuildingPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<BuildPieceCatalogue>() {
+            let path = Path::new(DEFAULT_BUILD_CATALOGUE_PATH);
+            let catalogue = BuildPieceCatalogue::load_over_built_ins(path).unwrap_or_else(|err| {
+                warn!("Using the built-in pieces instead of {:?}: {}", path, err);
+                BuildPieceCatalogue::default()
+            });
+            app.insert_resource(catalogue);
+        }
+        app.add_event::<BuildEvent>()
+            .add_event::<BuildRejected>()
+            .add_event::<support::BuildPieceCollapsed>()
+            .add_event::<damage::BuildPieceDamage>()
+            .add_event::<damage::BuildPieceRepair>()
+            .add_event::<damage::BuildPieceUpgrade>()
+            .add_event::<damage::BuildPieceDamaged>()
+            .add_event::<damage::BuildDamageStateChanged>()
+            .add_event::<damage::BuildPieceDestroyed>()
+            .add_event::<damage::BuildPieceRepaired>()
+            .add_event::<damage::BuildPieceUpgraded>()
+            .add_event::<door::ToggleDoor>()
+            .add_event::<door::LockDoor>()
+            .add_event::<door::DoorChanged>()
+            .add_event::<trap::TrapTriggered>()
+            .add_event::<blueprint::CaptureBlueprint>()
+            .add_event::<blueprint::BlueprintCaptured>()
+            .add_event::<blueprint::PlaceBlueprint>()
+            .add_event::<blueprint::BlueprintStarted>()
+            .add_event::<blueprint::BlueprintCompleted>()
+            .add_event::<DamageEvent>()
+            .init_resource::<BuildMaterialRegistry>()
+            .init_resource::<BuildMode>()
+            .init_resource::<blueprint::BlueprintStaging>()
+            .init_resource::<ActionState>()
+            .add_systems(
+                Update,
+                (
+                    preview::handle_build_input,
+                    preview::sync_build_ghost,
+                    preview::update_build_ghost,
+                    preview::colour_build_ghost,
+                    preview::confirm_build,
+                    placement::place_build_pieces,
+                    blueprint::capture_blueprints,
+                    blueprint::start_blueprints,
+                    blueprint::build_blueprints,
+                    damage::apply_build_piece_damage,
+                    damage::repair_build_pieces,
+                    damage::upgrade_build_pieces,
+                    support::update_structural_support,
+                )
+                    .chain(),
+            )
+            .add_systems(Update, (door::operate_doors, trap::trigger_traps));
+    }
+}
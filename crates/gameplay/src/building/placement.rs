@@ -0,0 +1,482 @@
+//! Turning `BuildEvent`s into placed pieces: snapping, validation and payment.
+use super::catalogue::{BuildPieceCatalogue, BuildPieceDefinition, PiecePlacement};
+use super::damage::BuildDamageState;
+use super::door::{self, Door, DoorLeaf};
+use super::sockets::{self, SOCKET_MATCH_DISTANCE};
+use super::support::{self, MIN_STABILITY};
+use super::trap::Trap;
+use super::{
+    BuildEvent, BuildMaterialRegistry, BuildPiece, BuildPieceKind, BuildRejectReason,
+    BuildRejected, Material, MaterialId,
+};
+use crate::inventory::stack::ItemContainer;
+use crate::inventory::Inventory;
+use crate::player::Player;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::f32::consts::FRAC_PI_2;
+use world::{MaterialRegistry, WorldData};
+
+/// The width of a building grid cell, and the height of one building level, in meters.
+pub const BUILD_GRID_SIZE: f32 = 2.0;
+/// The spacing of the points sampled inside a piece when checking it against terrain.
+const TERRAIN_PROBE_STEP: f32 = 0.25;
+/// How far inside a piece's surface terrain sampling starts, so pieces can rest on terrain.
+const TERRAIN_PROBE_MARGIN: f32 = 0.05;
+/// Aim points this close below a level boundary still count as the level above.
+const LEVEL_SNAP_TOLERANCE: f32 = 0.01;
+/// Placed pieces further than this from a new piece are too far away to overlap it.
+const OVERLAP_RANGE: f32 = BUILD_GRID_SIZE * 2.0;
+
+/// The grid slot a placed piece occupies.
+///
+/// Slots are in half-cell units horizontally: cell pieces like floors take up a cell (even
+/// coordinates) and edge pieces like walls take up the edge between two cells (one odd
+/// coordinate), so walls built from either side of the same edge collide.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BuildSlot(pub IVec3);
+
+impl BuildSlot {
+    /// The slot a piece snapped to `transform` occupies.
+    pub fn of(transform: &Transform) -> Self {
+        let half_cells = transform.translation * 2.0 / BUILD_GRID_SIZE;
+        Self(IVec3::new(
+            half_cells.x.round() as i32,
+            (transform.translation.y / BUILD_GRID_SIZE).round() as i32,
+            half_cells.z.round() as i32,
+        ))
+    }
+}
+
+/// Snaps a requested placement to the building grid.
+///
+/// The rotation is snapped to a quarter turn around Y and the position to the grid cell and
+/// level containing it. Edge pieces are then moved to the edge of the cell they face.
+pub fn snap_to_grid(placement: PiecePlacement, position: Vec3, rotation: Quat) -> Transform {
+    let (yaw, _, _) = rotation.to_euler(EulerRot::YXZ);
+    let rotation = Quat::from_rotation_y((yaw / FRAC_PI_2).round() * FRAC_PI_2);
+
+    let cell = Vec3::new(
+        (position.x / BUILD_GRID_SIZE).round(),
+        ((position.y + LEVEL_SNAP_TOLERANCE) / BUILD_GRID_SIZE).floor(),
+        (position.z / BUILD_GRID_SIZE).round(),
+    ) * BUILD_GRID_SIZE;
+    let translation = match placement {
+        PiecePlacement::Edge => cell + rotation * Vec3::Z * (BUILD_GRID_SIZE / 2.0),
+        PiecePlacement::Cell => cell,
+    };
+    // Snapping the yaw leaves float noise in the edge offset; keep positions exactly on the grid.
+    let translation = (translation * 2.0 / BUILD_GRID_SIZE).round() * BUILD_GRID_SIZE / 2.0;
+    Transform::from_translation(translation).with_rotation(rotation)
+}
+
+/// Whether a piece snapped to `transform` would be inside a solid voxel.
+///
+/// Foundations are meant to sink into terrain, so they are never blocked.
+pub fn blocked_by_terrain(
+    definition: &BuildPieceDefinition,
+    transform: &Transform,
+    world: &WorldData,
+    materials: &MaterialRegistry,
+) -> bool {
+    if definition.foundation {
+        return false;
+    }
+    let to_world = transform.compute_matrix();
+    definition.shape.iter().any(|piece_box| {
+        let inner = (piece_box.half_extents - Vec3::splat(TERRAIN_PROBE_MARGIN)).max(Vec3::ZERO);
+        let steps = (inner * 2.0 / TERRAIN_PROBE_STEP).ceil().as_uvec3();
+        (0..=steps.x).any(|x| {
+            (0..=steps.y).any(|y| {
+                (0..=steps.z).any(|z| {
+                    let t = UVec3::new(x, y, z).as_vec3() / steps.max(UVec3::ONE).as_vec3();
+                    let local = piece_box.center + piece_box.rotation * (-inner + inner * 2.0 * t);
+                    world
+                        .material_at(to_world.transform_point3(local))
+                        .and_then(|id| materials.get(id))
+                        .is_some_and(|material| material.is_solid)
+                })
+            })
+        })
+    })
+}
+
+/// Where a piece that passed its checks goes, and how well it would be held up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ValidPlacement {
+    pub slot: BuildSlot,
+    pub stability: f32,
+}
+
+/// Everything placement is validated against, shared by actual placement and previews.
+#[derive(SystemParam)]
+pub struct PlacementCheck<'w, 's> {
+    pieces: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static BuildSlot,
+            &'static BuildPiece,
+            &'static Transform,
+        ),
+    >,
+    world_data: Option<Res<'w, WorldData>>,
+    voxel_materials: Option<Res<'w, MaterialRegistry>>,
+    build_materials: Res<'w, BuildMaterialRegistry>,
+    catalogue: Res<'w, BuildPieceCatalogue>,
+}
+
+impl PlacementCheck<'_, '_> {
+    /// The definition of a kind of piece.
+    pub fn definition(&self, kind: BuildPieceKind) -> Option<&BuildPieceDefinition> {
+        self.catalogue.get(kind)
+    }
+
+    /// The properties of a material pieces can be built from.
+    pub fn material_by_id(&self, id: MaterialId) -> Option<&Material> {
+        self.build_materials.get(id)
+    }
+
+    /// The stability a piece of `material` snapped to `transform` would have, held up by the
+    /// placed pieces.
+    pub fn stability(
+        &self,
+        definition: &BuildPieceDefinition,
+        material: &Material,
+        transform: &Transform,
+    ) -> f32 {
+        let loss = support::stability_loss(definition, material);
+        self.supported_by(definition, loss, transform, &HashMap::new())
+    }
+
+    /// The stability of a piece losing `loss`, held up by the placed pieces and those `planned`.
+    fn supported_by(
+        &self,
+        definition: &BuildPieceDefinition,
+        loss: f32,
+        transform: &Transform,
+        planned: &HashMap<BuildSlot, f32>,
+    ) -> f32 {
+        if support::is_grounded(
+            definition,
+            transform,
+            self.world_data.as_deref(),
+            self.voxel_materials.as_deref(),
+        ) {
+            return support::GROUNDED_STABILITY;
+        }
+        let neighbours = support::support_neighbours(BuildSlot::of(transform));
+        let placed = self
+            .pieces
+            .iter()
+            .filter(|(_, slot, _, _)| neighbours.contains(slot))
+            .map(|(_, _, piece, _)| piece.stability);
+        let planned = neighbours
+            .iter()
+            .filter_map(|slot| planned.get(slot).copied());
+        placed
+            .chain(planned)
+            .map(|stability| stability - loss)
+            .fold(0.0, f32::max)
+    }
+
+    /// Snaps a requested placement onto a placed piece's socket it's on, or else to the grid.
+    pub fn snap(
+        &self,
+        definition: &BuildPieceDefinition,
+        position: Vec3,
+        rotation: Quat,
+    ) -> Transform {
+        self.pieces
+            .iter()
+            .filter_map(|(_, _, piece, transform)| {
+                Some((self.catalogue.get(piece.kind)?, transform))
+            })
+            .flat_map(|(placed, transform)| sockets::socket_poses(placed, transform))
+            .find(|(pose, socket)| {
+                socket.accepts_piece(definition)
+                    && pose.translation.distance(position) <= SOCKET_MATCH_DISTANCE
+            })
+            .map_or_else(
+                || snap_to_grid(definition.placement, position, rotation),
+                |(pose, _)| pose,
+            )
+    }
+
+    /// Finds the free socket accepting a piece closest to the ray the player aims along.
+    pub fn socket_snap(
+        &self,
+        definition: &BuildPieceDefinition,
+        origin: Vec3,
+        direction: Vec3,
+        max_distance: f32,
+    ) -> Option<sockets::SocketSnap> {
+        let placed = self
+            .pieces
+            .iter()
+            .filter_map(|(entity, _, piece, transform)| {
+                Some((entity, self.catalogue.get(piece.kind)?, transform))
+            });
+        sockets::best_socket(
+            definition,
+            origin,
+            direction,
+            max_distance,
+            placed,
+            |pose| self.fits(definition, pose).is_err(),
+        )
+    }
+
+    /// Checks that a piece snapped to `transform` has room among the placed pieces.
+    pub fn fits(
+        &self,
+        definition: &BuildPieceDefinition,
+        transform: &Transform,
+    ) -> Result<(), BuildRejectReason> {
+        let slot = BuildSlot::of(transform);
+        if self.pieces.iter().any(|(_, placed, _, _)| *placed == slot) {
+            return Err(BuildRejectReason::Occupied);
+        }
+        let overlapping = self.pieces.iter().any(|(_, _, piece, placed_at)| {
+            placed_at.translation.distance(transform.translation) <= OVERLAP_RANGE
+                && self.catalogue.get(piece.kind).is_some_and(|placed| {
+                    sockets::pieces_overlap(placed, placed_at, definition, transform)
+                })
+        });
+        if overlapping {
+            return Err(BuildRejectReason::Overlapping);
+        }
+        Ok(())
+    }
+
+    /// Checks that a piece snapped to `transform` has room among the placed pieces and terrain.
+    fn obstruction(
+        &self,
+        definition: &BuildPieceDefinition,
+        transform: &Transform,
+    ) -> Result<(), BuildRejectReason> {
+        self.fits(definition, transform)?;
+        if let (Some(world), Some(materials)) = (&self.world_data, &self.voxel_materials) {
+            if blocked_by_terrain(definition, transform, world, materials) {
+                return Err(BuildRejectReason::BlockedByTerrain);
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks whether a piece of the material `material_id` can be built at a snapped
+    /// `transform`, paid for from `inventory`.
+    pub fn check(
+        &self,
+        definition: &BuildPieceDefinition,
+        material_id: MaterialId,
+        transform: &Transform,
+        inventory: Option<&Inventory>,
+    ) -> Result<ValidPlacement, BuildRejectReason> {
+        let material = self
+            .material_by_id(material_id)
+            .ok_or(BuildRejectReason::UnknownPiece)?;
+        self.check_material(definition, material, transform, inventory)
+    }
+
+    /// Checks whether a piece of `material` can be built at a snapped `transform`, paid for from
+    /// `inventory`.
+    pub fn check_material(
+        &self,
+        definition: &BuildPieceDefinition,
+        material: &Material,
+        transform: &Transform,
+        inventory: Option<&Inventory>,
+    ) -> Result<ValidPlacement, BuildRejectReason> {
+        let placement = self.check_placement(definition, material, transform)?;
+        let affordable =
+            inventory.is_some_and(|inventory| inventory.count(material.item) >= definition.cost);
+        if !affordable {
+            return Err(BuildRejectReason::InsufficientResources);
+        }
+        Ok(placement)
+    }
+
+    /// Checks whether a piece of `material` fits and is held up at a snapped `transform`, for
+    /// pieces that are already paid for.
+    pub fn check_placement(
+        &self,
+        definition: &BuildPieceDefinition,
+        material: &Material,
+        transform: &Transform,
+    ) -> Result<ValidPlacement, BuildRejectReason> {
+        self.obstruction(definition, transform)?;
+        let loss = support::stability_loss(definition, material);
+        let stability = self.supported_by(definition, loss, transform, &HashMap::new());
+        if stability <= MIN_STABILITY {
+            return Err(BuildRejectReason::Unsupported);
+        }
+        Ok(ValidPlacement {
+            slot: BuildSlot::of(transform),
+            stability,
+        })
+    }
+
+    /// Checks pieces to be built together, in order, as if every earlier one that passed were
+    /// already built. Their cost isn't checked, and neither are overlaps between them.
+    pub fn plan(
+        &self,
+        pieces: &[(&BuildPieceDefinition, &Material, Transform)],
+    ) -> Vec<Result<ValidPlacement, BuildRejectReason>> {
+        let mut planned = HashMap::new();
+        pieces
+            .iter()
+            .map(|(definition, material, transform)| {
+                let slot = BuildSlot::of(transform);
+                if planned.contains_key(&slot) {
+                    return Err(BuildRejectReason::Occupied);
+                }
+                self.obstruction(definition, transform)?;
+                let loss = support::stability_loss(definition, material);
+                let stability = self.supported_by(definition, loss, transform, &planned);
+                if stability <= MIN_STABILITY {
+                    return Err(BuildRejectReason::Unsupported);
+                }
+                planned.insert(slot, stability);
+                Ok(ValidPlacement { slot, stability })
+            })
+            .collect()
+    }
+}
+
+/// Spawns a piece of `material` that passed its checks, with its collider and any door or trap.
+pub(super) fn spawn_piece(
+    commands: &mut Commands,
+    definition: &BuildPieceDefinition,
+    material: &Material,
+    placement: ValidPlacement,
+    transform: Transform,
+) -> Entity {
+    let mut piece = commands.spawn((
+        BuildPiece {
+            kind: definition.kind,
+            material_id: material.id,
+            health: material.base_hp,
+            stability: placement.stability,
+        },
+        placement.slot,
+        BuildDamageState::default(),
+        TransformBundle::from_transform(transform),
+        RigidBody::Fixed,
+        definition.collider(),
+        Name::new(definition.name.clone()),
+    ));
+    if let Some(door) = &definition.door {
+        let (_, _, leaf_collider) = door.leaf.collider();
+        piece.insert(Door::default()).with_children(|parent| {
+            parent.spawn((
+                DoorLeaf,
+                TransformBundle::from_transform(door::leaf_transform(door, false)),
+                leaf_collider,
+                Name::new("Door Leaf"),
+            ));
+        });
+    }
+    if definition.trap.is_some() {
+        piece.insert(Trap::default());
+    }
+    piece.id()
+}
+
+/// Builds the pieces requested by `BuildEvent`s, paying for them from the player's inventory.
+pub(super) fn place_build_pieces(
+    mut commands: Commands,
+    mut build_events: EventReader<BuildEvent>,
+    mut rejected_events: EventWriter<BuildRejected>,
+    check: PlacementCheck,
+    mut inventory_query: Query<&mut Inventory, With<Player>>,
+) {
+    // Pieces spawned this frame aren't visible to the piece query yet, so they also can't hold
+    // each other up until the next frame.
+    let mut placed_slots = HashSet::new();
+    let mut inventory = inventory_query.get_single_mut().ok();
+
+    for event in build_events.read() {
+        let (Some(definition), Some(material)) = (
+            check.definition(event.piece),
+            check.material_by_id(event.material),
+        ) else {
+            rejected_events.send(BuildRejected {
+                reason: BuildRejectReason::UnknownPiece,
+            });
+            continue;
+        };
+        let transform = check.snap(definition, event.position, event.rotation);
+        let result = check
+            .check_material(definition, material, &transform, inventory.as_deref())
+            .and_then(|placement| {
+                if placed_slots.insert(placement.slot) {
+                    Ok(placement)
+                } else {
+                    Err(BuildRejectReason::Occupied)
+                }
+            });
+        let placement = match result {
+            Ok(placement) => placement,
+            Err(reason) => {
+                rejected_events.send(BuildRejected { reason });
+                continue;
+            }
+        };
+        if let Some(inventory) = inventory.as_deref_mut() {
+            if inventory.remove(material.item, definition.cost).is_err() {
+                rejected_events.send(BuildRejected {
+                    reason: BuildRejectReason::InsufficientResources,
+                });
+                continue;
+            }
+        }
+        spawn_piece(&mut commands, definition, material, placement, transform);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapping_aligns_to_cells_and_edges() {
+        let rotation = Quat::from_rotation_y(0.3);
+        let floor = snap_to_grid(PiecePlacement::Cell, Vec3::new(2.7, 0.0, -1.2), rotation);
+        assert_eq!(floor.translation, Vec3::new(2.0, 0.0, -2.0));
+        assert_eq!(floor.rotation, Quat::IDENTITY);
+
+        // A wall facing +X sits on the cell's +X edge.
+        let wall = snap_to_grid(
+            PiecePlacement::Edge,
+            Vec3::new(0.4, 2.5, 0.3),
+            Quat::from_rotation_y(1.4),
+        );
+        assert_eq!(wall.translation, Vec3::new(1.0, 2.0, 0.0));
+
+        // Aiming exactly at a level's floor counts as that level, not the one below.
+        let upper = snap_to_grid(PiecePlacement::Cell, Vec3::new(0.0, 1.999, 0.0), rotation);
+        assert_eq!(upper.translation.y, 2.0);
+    }
+
+    #[test]
+    fn test_walls_from_either_side_share_a_slot() {
+        let facing_east = snap_to_grid(
+            PiecePlacement::Edge,
+            Vec3::ZERO,
+            Quat::from_rotation_y(FRAC_PI_2),
+        );
+        let facing_west = snap_to_grid(
+            PiecePlacement::Edge,
+            Vec3::new(2.0, 0.0, 0.0),
+            Quat::from_rotation_y(-FRAC_PI_2),
+        );
+        assert_eq!(BuildSlot::of(&facing_east), BuildSlot::of(&facing_west));
+
+        let floor = snap_to_grid(PiecePlacement::Cell, Vec3::ZERO, Quat::IDENTITY);
+        assert_ne!(BuildSlot::of(&floor), BuildSlot::of(&facing_east));
+    }
+}
@@ -0,0 +1,266 @@
+//! Build mode: a translucent ghost of the piece about to be built, placed where the camera aims.
+use super::catalogue::BuildPieceCatalogue;
+use super::placement::PlacementCheck;
+use super::{BuildEvent, BuildPiece, BuildPieceKind, BuildRejectReason, MaterialId};
+use crate::camera::MainCamera;
+use crate::inventory::Inventory;
+use crate::player::Player;
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use common::input::{ActionState, InputAction};
+use std::f32::consts::FRAC_PI_2;
+use world::{MaterialRegistry, WorldData};
+
+/// How far from the camera pieces can be placed, in meters.
+const BUILD_RANGE: f32 = 12.0;
+/// The spacing of the samples taken along the aim ray when checking voxels.
+const VOXEL_PROBE_STEP: f32 = 0.1;
+/// The ghost's colour where the piece can be built.
+const VALID_GHOST_COLOUR: Color = Color::rgba(0.3, 0.9, 0.4, 0.4);
+/// The ghost's colour where the piece can't be built.
+const INVALID_GHOST_COLOUR: Color = Color::rgba(0.9, 0.2, 0.2, 0.4);
+
+/// Whether the player is in build mode, and what they are about to build.
+#[derive(Resource, Debug, Clone)]
+pub struct BuildMode {
+    pub active: bool,
+    pub piece: BuildPieceKind,
+    /// The material pieces are built from.
+    pub material: MaterialId,
+    /// Quarter turns added to the camera's heading, `0..4`.
+    pub quarter_turns: u8,
+}
+
+impl Default for BuildMode {
+    fn default() -> Self {
+        Self {
+            active: false,
+            piece: BuildPieceKind::FLOOR,
+            material: MaterialId::WOOD,
+            quarter_turns: 0,
+        }
+    }
+}
+
+/// The preview of the piece about to be built.
+#[derive(Component, Debug)]
+pub struct BuildGhost {
+    pub piece: BuildPieceKind,
+    /// Where the camera aims, or the socket the ghost snapped to; confirming sends a `BuildEvent`
+    /// for this point.
+    pub aim: Vec3,
+    pub rotation: Quat,
+    /// Why the piece can't be built here, if it can't.
+    pub rejection: Option<BuildRejectReason>,
+    /// The ghost's own material, recoloured with its validity. Absent when running headless.
+    material: Option<Handle<StandardMaterial>>,
+}
+
+impl BuildGhost {
+    /// Whether the piece can be built where the ghost is.
+    pub fn is_valid(&self) -> bool {
+        self.rejection.is_none()
+    }
+}
+
+/// Enters and leaves build mode, and rotates and cycles the piece while in it.
+pub(super) fn handle_build_input(
+    actions: Res<ActionState>,
+    catalogue: Res<BuildPieceCatalogue>,
+    mut mode: ResMut<BuildMode>,
+) {
+    if actions.just_pressed(InputAction::Build) {
+        mode.active = !mode.active;
+    }
+    if !mode.active {
+        return;
+    }
+    if actions.just_pressed(InputAction::RotateBuildPiece) {
+        mode.quarter_turns = (mode.quarter_turns + 1) % 4;
+    }
+    if actions.just_pressed(InputAction::CycleBuildPiece) {
+        mode.piece = catalogue.next(mode.piece);
+    }
+}
+
+/// Spawns the ghost in build mode, replaces it when the piece changes and removes it afterwards.
+pub(super) fn sync_build_ghost(
+    mut commands: Commands,
+    mode: Res<BuildMode>,
+    catalogue: Res<BuildPieceCatalogue>,
+    ghost_query: Query<(Entity, &BuildGhost)>,
+    meshes: Option<ResMut<Assets<Mesh>>>,
+    materials: Option<ResMut<Assets<StandardMaterial>>>,
+) {
+    let mut current = false;
+    for (entity, ghost) in ghost_query.iter() {
+        if mode.active && ghost.piece == mode.piece {
+            current = true;
+        } else {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+    if !mode.active || current {
+        return;
+    }
+
+    let Some(definition) = catalogue.get(mode.piece) else {
+        return;
+    };
+    let visuals = meshes.zip(materials).map(|(mut meshes, mut materials)| {
+        let material = materials.add(StandardMaterial {
+            base_color: VALID_GHOST_COLOUR,
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..default()
+        });
+        let shape: Vec<_> = definition
+            .shape
+            .iter()
+            .map(|piece_box| PbrBundle {
+                mesh: meshes.add(Mesh::from(Cuboid::from_size(piece_box.half_extents * 2.0))),
+                material: material.clone(),
+                transform: Transform::from_translation(piece_box.center)
+                    .with_rotation(piece_box.rotation),
+                ..default()
+            })
+            .collect();
+        (material, shape)
+    });
+    let (material, shape) = visuals.unzip();
+
+    let mut ghost = commands.spawn((
+        BuildGhost {
+            piece: mode.piece,
+            aim: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+            rejection: None,
+            material,
+        },
+        SpatialBundle::default(),
+        Name::new("Build Ghost"),
+    ));
+    if let Some(shape) = shape {
+        ghost.with_children(|parent| {
+            for piece_box in shape {
+                parent.spawn(piece_box);
+            }
+        });
+    }
+}
+
+/// Moves the ghost to the snapped spot the camera aims at and checks whether it can be built.
+///
+/// A free socket near the aim ray wins over the grid, so pieces attach to what's already built.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn update_build_ghost(
+    mut ghost_query: Query<(&mut BuildGhost, &mut Transform), Without<BuildPiece>>,
+    mode: Res<BuildMode>,
+    camera_query: Query<&GlobalTransform, With<MainCamera>>,
+    player_query: Query<(Entity, Option<&Inventory>), With<Player>>,
+    rapier_context: Option<Res<RapierContext>>,
+    world_data: Option<Res<WorldData>>,
+    voxel_materials: Option<Res<MaterialRegistry>>,
+    check: PlacementCheck,
+) {
+    let Ok(camera) = camera_query.get_single() else {
+        return;
+    };
+    let (player, inventory) = player_query
+        .get_single()
+        .map_or((None, None), |(entity, inventory)| {
+            (Some(entity), inventory)
+        });
+
+    let origin = camera.translation();
+    let direction = camera.forward();
+    let mut filter = QueryFilter::new().exclude_sensors();
+    if let Some(player) = player {
+        filter = filter.exclude_collider(player);
+    }
+    let collider_hit = rapier_context.and_then(|context| {
+        context
+            .cast_ray(origin, direction, BUILD_RANGE, true, filter)
+            .map(|(_, distance)| distance)
+    });
+    // Voxel hits are only found once a sample is inside; step back to the last free sample.
+    let voxel_hit = world_data
+        .zip(voxel_materials)
+        .and_then(|(world, materials)| {
+            world
+                .solid_hit_distance(&materials, origin, direction, BUILD_RANGE, VOXEL_PROBE_STEP)
+                .map(|distance| distance - VOXEL_PROBE_STEP)
+        });
+    let distance = [collider_hit, voxel_hit]
+        .into_iter()
+        .flatten()
+        .fold(BUILD_RANGE, f32::min);
+    let aim = origin + direction * distance;
+
+    let heading = (-direction.x).atan2(-direction.z);
+    let rotation = Quat::from_rotation_y(heading + f32::from(mode.quarter_turns) * FRAC_PI_2);
+
+    for (mut ghost, mut transform) in ghost_query.iter_mut() {
+        let Some(definition) = check.definition(ghost.piece) else {
+            continue;
+        };
+        let (requested, snapped) =
+            match check.socket_snap(definition, origin, direction, BUILD_RANGE) {
+                Some(snap) => (
+                    (snap.transform.translation, snap.transform.rotation),
+                    snap.transform,
+                ),
+                None => ((aim, rotation), check.snap(definition, aim, rotation)),
+            };
+        *transform = snapped;
+        let rejection = check
+            .check(definition, mode.material, &transform, inventory)
+            .err();
+        (ghost.aim, ghost.rotation) = requested;
+        ghost.rejection = rejection;
+    }
+}
+
+/// Tints ghosts green where their piece can be built and red where it can't.
+pub(super) fn colour_build_ghost(
+    ghost_query: Query<&BuildGhost, Changed<BuildGhost>>,
+    materials: Option<ResMut<Assets<StandardMaterial>>>,
+) {
+    let Some(mut materials) = materials else {
+        return;
+    };
+    for ghost in ghost_query.iter() {
+        let Some(material) = ghost
+            .material
+            .as_ref()
+            .and_then(|handle| materials.get_mut(handle))
+        else {
+            continue;
+        };
+        material.base_color = if ghost.is_valid() {
+            VALID_GHOST_COLOUR
+        } else {
+            INVALID_GHOST_COLOUR
+        };
+    }
+}
+
+/// Requests the previewed piece when the player confirms. Placement validates it again.
+pub(super) fn confirm_build(
+    actions: Res<ActionState>,
+    mode: Res<BuildMode>,
+    ghost_query: Query<&BuildGhost>,
+    mut build_events: EventWriter<BuildEvent>,
+) {
+    if !mode.active || !actions.just_pressed(InputAction::Fire) {
+        return;
+    }
+    for ghost in ghost_query.iter() {
+        build_events.send(BuildEvent {
+            piece: ghost.piece,
+            material: mode.material,
+            position: ghost.aim,
+            rotation: ghost.rotation,
+        });
+    }
+}
@@ -0,0 +1,260 @@
+//! Socket snapping: attaching pieces to the sockets of pieces already placed.
+//!
+//! Grid snapping only knows cells and edges; sockets let a piece say exactly where others attach
+//! to it, like a roof on the top of a wall. The solver picks the accepting socket closest to the
+//! cursor ray, skipping any whose piece would overlap what's already built.
+use super::catalogue::{BuildPieceDefinition, BuildSocket, PieceBox};
+use bevy::prelude::*;
+
+/// How far from the cursor ray a socket can be and still be snapped to, in meters.
+pub const SOCKET_SNAP_RADIUS: f32 = 1.0;
+/// How close a requested position has to be to a socket to be placed on it exactly.
+pub const SOCKET_MATCH_DISTANCE: f32 = 0.05;
+/// How far pieces can sink into each other without overlapping, so walls can stand on the edges
+/// of floors and pieces can meet at their thickness.
+const OVERLAP_TOLERANCE: f32 = 0.08;
+
+/// Where a piece would go when attached to a socket.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SocketSnap {
+    /// The placed piece the socket belongs to.
+    pub owner: Entity,
+    /// The attached piece's transform.
+    pub transform: Transform,
+}
+
+/// The sockets of a piece placed at `transform`, with the transform of a piece attached to each.
+pub fn socket_poses<'a>(
+    definition: &'a BuildPieceDefinition,
+    transform: &'a Transform,
+) -> impl Iterator<Item = (Transform, &'a BuildSocket)> + 'a {
+    definition.sockets.iter().map(move |socket| {
+        let pose = Transform::from_translation(transform.transform_point(socket.position))
+            .with_rotation(transform.rotation * socket.rotation);
+        (pose, socket)
+    })
+}
+
+/// Finds the socket accepting `piece` closest to a ray, among placed pieces.
+///
+/// Sockets behind the ray's origin, beyond `max_distance` along it or further than
+/// `SOCKET_SNAP_RADIUS` from it are ignored, as are those `blocked` rejects. Ties go to the socket
+/// nearer the origin.
+pub fn best_socket<'a>(
+    piece: &BuildPieceDefinition,
+    origin: Vec3,
+    direction: Vec3,
+    max_distance: f32,
+    placed: impl IntoIterator<Item = (Entity, &'a BuildPieceDefinition, &'a Transform)>,
+    mut blocked: impl FnMut(&Transform) -> bool,
+) -> Option<SocketSnap> {
+    let direction = direction.normalize_or_zero();
+    let mut best: Option<(f32, f32, SocketSnap)> = None;
+    for (owner, definition, transform) in placed {
+        for (pose, socket) in socket_poses(definition, transform) {
+            if !socket.accepts_piece(piece) {
+                continue;
+            }
+            let along = (pose.translation - origin).dot(direction);
+            if !(0.0..=max_distance).contains(&along) {
+                continue;
+            }
+            let off_ray = pose.translation.distance(origin + direction * along);
+            if off_ray > SOCKET_SNAP_RADIUS {
+                continue;
+            }
+            let better = best.as_ref().is_none_or(|(best_off, best_along, _)| {
+                (off_ray, along) < (*best_off, *best_along)
+            });
+            if better && !blocked(&pose) {
+                best = Some((
+                    off_ray,
+                    along,
+                    SocketSnap {
+                        owner,
+                        transform: pose,
+                    },
+                ));
+            }
+        }
+    }
+    best.map(|(_, _, snap)| snap)
+}
+
+/// A box of a piece placed at `transform`, in world space: center, axes and half extents.
+fn world_box(piece_box: &PieceBox, transform: &Transform) -> (Vec3, [Vec3; 3], Vec3) {
+    let rotation = transform.rotation * piece_box.rotation;
+    let half_extents = (piece_box.half_extents - Vec3::splat(OVERLAP_TOLERANCE)).max(Vec3::ZERO);
+    (
+        transform.transform_point(piece_box.center),
+        [rotation * Vec3::X, rotation * Vec3::Y, rotation * Vec3::Z],
+        half_extents,
+    )
+}
+
+/// Whether two oriented boxes overlap, by the separating axis test.
+fn boxes_overlap(a: (Vec3, [Vec3; 3], Vec3), b: (Vec3, [Vec3; 3], Vec3)) -> bool {
+    let (a_center, a_axes, a_half) = a;
+    let (b_center, b_axes, b_half) = b;
+    let offset = b_center - a_center;
+    let radius = |axes: &[Vec3; 3], half: Vec3, axis: Vec3| {
+        half.x * axes[0].dot(axis).abs()
+            + half.y * axes[1].dot(axis).abs()
+            + half.z * axes[2].dot(axis).abs()
+    };
+    let separated = |axis: Vec3| {
+        if axis.length_squared() < 1e-6 {
+            return false;
+        }
+        let axis = axis.normalize();
+        offset.dot(axis).abs() >= radius(&a_axes, a_half, axis) + radius(&b_axes, b_half, axis)
+    };
+
+    let mut axes = a_axes.to_vec();
+    axes.extend(b_axes);
+    for a_axis in a_axes {
+        axes.extend(b_axes.map(|b_axis| a_axis.cross(b_axis)));
+    }
+    !axes.into_iter().any(separated)
+}
+
+/// Whether two placed pieces overlap by more than they're allowed to sink into each other.
+pub fn pieces_overlap(
+    a: &BuildPieceDefinition,
+    a_transform: &Transform,
+    b: &BuildPieceDefinition,
+    b_transform: &Transform,
+) -> bool {
+    a.shape.iter().any(|a_box| {
+        b.shape.iter().any(|b_box| {
+            boxes_overlap(world_box(a_box, a_transform), world_box(b_box, b_transform))
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::building::catalogue::BuildPieceCatalogue;
+    use crate::building::BuildPieceKind;
+    use std::f32::consts::FRAC_PI_2;
+
+    /// A floor at the origin with a wall on its +X edge.
+    fn layout(catalogue: &BuildPieceCatalogue) -> Vec<(Entity, &BuildPieceDefinition, Transform)> {
+        vec![
+            (
+                Entity::from_raw(1),
+                catalogue.get(BuildPieceKind::FLOOR).unwrap(),
+                Transform::IDENTITY,
+            ),
+            (
+                Entity::from_raw(2),
+                catalogue.get(BuildPieceKind::WALL).unwrap(),
+                Transform::from_xyz(1.0, 0.0, 0.0).with_rotation(Quat::from_rotation_y(FRAC_PI_2)),
+            ),
+        ]
+    }
+
+    /// Snaps a piece of `kind` onto `layout`, aiming from above and behind at `target`.
+    fn solve(
+        catalogue: &BuildPieceCatalogue,
+        layout: &[(Entity, &BuildPieceDefinition, Transform)],
+        kind: BuildPieceKind,
+        target: Vec3,
+    ) -> Option<SocketSnap> {
+        let attached = catalogue.get(kind).unwrap();
+        let origin = Vec3::new(0.0, 5.0, 6.0);
+        best_socket(
+            attached,
+            origin,
+            target - origin,
+            20.0,
+            layout
+                .iter()
+                .map(|(entity, definition, transform)| (*entity, *definition, transform)),
+            |pose| {
+                layout.iter().any(|(_, definition, transform)| {
+                    pieces_overlap(definition, transform, attached, pose)
+                })
+            },
+        )
+    }
+
+    #[test]
+    fn test_walls_snap_to_floor_edges() {
+        let catalogue = BuildPieceCatalogue::default();
+        let layout = layout(&catalogue);
+        let snap = solve(
+            &catalogue,
+            &layout,
+            BuildPieceKind::WALL,
+            Vec3::new(0.2, 0.0, 0.9),
+        )
+        .unwrap();
+        assert_eq!(snap.owner, Entity::from_raw(1));
+        assert!(snap.transform.translation.abs_diff_eq(Vec3::Z, 1e-5));
+        assert!(snap.transform.rotation.abs_diff_eq(Quat::IDENTITY, 1e-5));
+    }
+
+    #[test]
+    fn test_roofs_snap_to_wall_tops() {
+        let catalogue = BuildPieceCatalogue::default();
+        let layout = layout(&catalogue);
+        // Aiming over the top of the wall, on the floor's side.
+        let snap = solve(
+            &catalogue,
+            &layout,
+            BuildPieceKind::ROOF,
+            Vec3::new(0.2, 2.0, 0.0),
+        )
+        .unwrap();
+        assert_eq!(snap.owner, Entity::from_raw(2));
+        assert!(snap
+            .transform
+            .translation
+            .abs_diff_eq(Vec3::new(0.0, 2.0, 0.0), 1e-5));
+    }
+
+    #[test]
+    fn test_occupied_sockets_are_skipped() {
+        let catalogue = BuildPieceCatalogue::default();
+        let layout = layout(&catalogue);
+        // Aiming nearest the floor's +X edge, which already has the wall, picks the +Z edge instead.
+        let snap = solve(
+            &catalogue,
+            &layout,
+            BuildPieceKind::WALL,
+            Vec3::new(0.9, 0.0, 0.6),
+        )
+        .unwrap();
+        assert!(snap.transform.translation.abs_diff_eq(Vec3::Z, 1e-5));
+        // Nothing accepts a piece nowhere near the ray.
+        assert!(solve(
+            &catalogue,
+            &layout,
+            BuildPieceKind::WALL,
+            Vec3::new(0.0, 0.0, -40.0)
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_touching_pieces_dont_overlap() {
+        let catalogue = BuildPieceCatalogue::default();
+        let [(_, floor, floor_at), (_, wall, wall_at)] = layout(&catalogue).try_into().unwrap();
+        assert!(!pieces_overlap(floor, &floor_at, wall, &wall_at));
+
+        let stairs = catalogue.get(BuildPieceKind::STAIRS).unwrap();
+        assert!(pieces_overlap(
+            floor,
+            &floor_at,
+            stairs,
+            &Transform::IDENTITY
+        ));
+        let beside = Transform::from_xyz(2.0, 0.0, 0.0);
+        assert!(!pieces_overlap(floor, &floor_at, floor, &beside));
+        // A wall across the middle of the floor's cell cuts through it.
+        let across = Transform::from_xyz(0.0, -0.5, 0.0);
+        assert!(pieces_overlap(floor, &floor_at, wall, &across));
+    }
+}
@@ -0,0 +1,293 @@
+//! Structural support: what holds each placed piece up, and collapsing pieces that lose it.
+//!
+//! Pieces resting on the ground have full stability. Every other piece takes the best stability
+//! of the pieces it's connected to, minus a loss set by its kind and material, so stability drops
+//! with distance from the ground and floors can only reach so far out from a supporting wall.
+//! Pieces left without stability collapse.
+use super::catalogue::{BuildPieceCatalogue, BuildPieceDefinition};
+use super::damage::BuildPieceUpgraded;
+use super::placement::BuildSlot;
+use super::{BuildMaterialRegistry, BuildPiece, BuildPieceKind, Material};
+use bevy::prelude::*;
+use std::collections::{HashMap, VecDeque};
+use world::{MaterialRegistry, WorldData};
+
+/// The stability of a piece resting on the ground.
+pub const GROUNDED_STABILITY: f32 = 1.0;
+/// Pieces at or below this stability are unsupported and collapse.
+pub const MIN_STABILITY: f32 = 1e-3;
+/// How far below a piece's base terrain is looked for when checking whether it's grounded.
+const GROUND_PROBE_DEPTH: f32 = 0.05;
+
+/// How much stability a piece built from `material` loses relative to what holds it up.
+pub fn stability_loss(definition: &BuildPieceDefinition, material: &Material) -> f32 {
+    definition.stability_loss / material.strength.max(f32::EPSILON)
+}
+
+/// The slots a piece in `slot` can pass support to and take support from.
+///
+/// Cell pieces connect to the cells beside them, the edge pieces standing on their edges and the
+/// edge pieces below them. Edge pieces connect to the edge pieces stacked on and under them, the
+/// cells they stand in and the cells resting on top of them.
+pub fn support_neighbours(slot: BuildSlot) -> Vec<BuildSlot> {
+    let IVec3 { x, y, z } = slot.0;
+    let at = |x, y, z| BuildSlot(IVec3::new(x, y, z));
+    if x % 2 == 0 && z % 2 == 0 {
+        let mut neighbours = vec![
+            at(x + 2, y, z),
+            at(x - 2, y, z),
+            at(x, y, z + 2),
+            at(x, y, z - 2),
+        ];
+        for level in [y, y - 1] {
+            neighbours.extend([
+                at(x + 1, level, z),
+                at(x - 1, level, z),
+                at(x, level, z + 1),
+                at(x, level, z - 1),
+            ]);
+        }
+        neighbours
+    } else {
+        // The cells on either side of the edge.
+        let across = if x % 2 != 0 { IVec3::X } else { IVec3::Z };
+        let mut neighbours = vec![at(x, y + 1, z), at(x, y - 1, z)];
+        for level in [y, y + 1] {
+            for cell in [slot.0 + across, slot.0 - across] {
+                neighbours.push(at(cell.x, level, cell.z));
+            }
+        }
+        neighbours
+    }
+}
+
+/// Whether a piece snapped to `transform` rests on the ground.
+///
+/// Pieces rest on the ground from their origin, and foundations from their bottom. Without
+/// terrain data the ground is the plane at level zero.
+pub fn is_grounded(
+    definition: &BuildPieceDefinition,
+    transform: &Transform,
+    world: Option<&WorldData>,
+    materials: Option<&MaterialRegistry>,
+) -> bool {
+    let base = if definition.foundation {
+        transform.translation + Vec3::Y * definition.bottom()
+    } else {
+        transform.translation
+    };
+    match world.zip(materials) {
+        Some((world, materials)) => world
+            .material_at(base - Vec3::Y * GROUND_PROBE_DEPTH)
+            .and_then(|id| materials.get(id))
+            .is_some_and(|material| material.is_solid),
+        None => base.y <= 0.0,
+    }
+}
+
+/// Placed pieces and how they hold each other up.
+#[derive(Debug, Clone, Default)]
+pub struct SupportGraph {
+    nodes: HashMap<BuildSlot, SupportNode>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SupportNode {
+    loss: f32,
+    grounded: bool,
+}
+
+impl SupportGraph {
+    /// Adds a piece losing `loss` stability, which may rest on the ground.
+    pub fn insert(&mut self, slot: BuildSlot, loss: f32, grounded: bool) {
+        self.nodes.insert(slot, SupportNode { loss, grounded });
+    }
+
+    /// The stability of every piece, from `0.0` for pieces nothing holds up to
+    /// `GROUNDED_STABILITY`.
+    pub fn stabilities(&self) -> HashMap<BuildSlot, f32> {
+        let mut stabilities: HashMap<_, _> = self.nodes.keys().map(|slot| (*slot, 0.0)).collect();
+        let mut queue = VecDeque::new();
+        for (slot, node) in &self.nodes {
+            if node.grounded {
+                stabilities.insert(*slot, GROUNDED_STABILITY);
+                queue.push_back(*slot);
+            }
+        }
+
+        // Losses are positive, so relaxing from the ground outwards settles.
+        while let Some(slot) = queue.pop_front() {
+            let stability = stabilities[&slot];
+            for neighbour in support_neighbours(slot) {
+                let Some(node) = self.nodes.get(&neighbour) else {
+                    continue;
+                };
+                let passed = stability - node.loss;
+                if passed > stabilities[&neighbour] {
+                    stabilities.insert(neighbour, passed);
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+        stabilities
+    }
+}
+
+/// An event sent for every piece that collapses after losing its support.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct BuildPieceCollapsed {
+    pub entity: Entity,
+    pub kind: BuildPieceKind,
+    pub slot: BuildSlot,
+}
+
+/// Recomputes stability whenever pieces are built, destroyed or upgraded, collapsing unsupported
+/// pieces.
+///
+/// Every piece that's lost its support collapses at once, so destroying a load-bearing piece
+/// brings down everything that depended on it.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn update_structural_support(
+    mut commands: Commands,
+    added_query: Query<(), Added<BuildSlot>>,
+    mut removed: RemovedComponents<BuildPiece>,
+    mut upgraded_events: EventReader<BuildPieceUpgraded>,
+    mut piece_query: Query<(Entity, &mut BuildPiece, &BuildSlot, &Transform)>,
+    build_materials: Res<BuildMaterialRegistry>,
+    catalogue: Res<BuildPieceCatalogue>,
+    world_data: Option<Res<WorldData>>,
+    voxel_materials: Option<Res<MaterialRegistry>>,
+    mut collapsed_events: EventWriter<BuildPieceCollapsed>,
+) {
+    let removed_any = removed.read().count() > 0;
+    let upgraded_any = upgraded_events.read().count() > 0;
+    if !removed_any && !upgraded_any && added_query.is_empty() {
+        return;
+    }
+
+    let mut graph = SupportGraph::default();
+    for (_, piece, slot, transform) in piece_query.iter() {
+        // Pieces whose kind has left the catalogue have nothing to rest on, and collapse.
+        let Some(definition) = catalogue.get(piece.kind) else {
+            continue;
+        };
+        let loss = build_materials
+            .get(piece.material_id)
+            .map_or(definition.stability_loss, |material| {
+                stability_loss(definition, material)
+            });
+        let grounded = is_grounded(
+            definition,
+            transform,
+            world_data.as_deref(),
+            voxel_materials.as_deref(),
+        );
+        graph.insert(*slot, loss, grounded);
+    }
+    let stabilities = graph.stabilities();
+
+    for (entity, mut piece, slot, _) in piece_query.iter_mut() {
+        let stability = stabilities.get(slot).copied().unwrap_or(0.0);
+        if piece.stability != stability {
+            piece.stability = stability;
+        }
+        if stability <= MIN_STABILITY {
+            commands.entity(entity).despawn_recursive();
+            collapsed_events.send(BuildPieceCollapsed {
+                entity,
+                kind: piece.kind,
+                slot: *slot,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::building::catalogue::PiecePlacement;
+    use crate::building::placement::snap_to_grid;
+    use crate::building::MaterialId;
+    use crate::inventory::ItemId;
+
+    fn material(strength: f32) -> Material {
+        Material {
+            id: MaterialId::WOOD,
+            base_hp: 100.0,
+            item: ItemId(1),
+            strength,
+            resistances: HashMap::new(),
+            upgrade: None,
+        }
+    }
+
+    /// A grounded wall with a row of floors on top of it, reaching out along +X.
+    fn cantilever(floors: i32, material: &Material) -> HashMap<BuildSlot, f32> {
+        let catalogue = BuildPieceCatalogue::default();
+        let wall_definition = catalogue.get(BuildPieceKind::WALL).unwrap();
+        let floor_definition = catalogue.get(BuildPieceKind::FLOOR).unwrap();
+        let mut graph = SupportGraph::default();
+        let wall = snap_to_grid(PiecePlacement::Edge, Vec3::ZERO, Quat::IDENTITY);
+        graph.insert(
+            BuildSlot::of(&wall),
+            stability_loss(wall_definition, material),
+            true,
+        );
+        for cell in 0..floors {
+            let floor = Vec3::new(cell as f32 * 2.0, 2.0, 0.0);
+            graph.insert(
+                BuildSlot::of(&Transform::from_translation(floor)),
+                stability_loss(floor_definition, material),
+                false,
+            );
+        }
+        graph.stabilities()
+    }
+
+    fn supported(stabilities: &HashMap<BuildSlot, f32>) -> usize {
+        stabilities
+            .values()
+            .filter(|stability| **stability > MIN_STABILITY)
+            .count()
+    }
+
+    #[test]
+    fn test_stability_drops_away_from_ground() {
+        let stabilities = cantilever(3, &material(1.0));
+        let floor = |x: f32| stabilities[&BuildSlot::of(&Transform::from_xyz(x, 2.0, 0.0))];
+        assert!(floor(0.0) < GROUNDED_STABILITY);
+        assert!(floor(2.0) < floor(0.0));
+        assert!(floor(4.0) < floor(2.0));
+    }
+
+    #[test]
+    fn test_stronger_materials_reach_further() {
+        let wood = material(1.0);
+        let reach = |material: &Material| {
+            (1..20)
+                .take_while(|floors| {
+                    supported(&cantilever(*floors, material)) == *floors as usize + 1
+                })
+                .last()
+                .unwrap_or(0)
+        };
+        assert!(reach(&material(2.0)) > reach(&wood));
+    }
+
+    #[test]
+    fn test_neighbours_are_symmetric() {
+        let slots = [
+            BuildSlot(IVec3::new(0, 0, 0)),
+            BuildSlot(IVec3::new(1, 0, 0)),
+            BuildSlot(IVec3::new(0, 1, -1)),
+        ];
+        for slot in slots {
+            for neighbour in support_neighbours(slot) {
+                assert!(
+                    support_neighbours(neighbour).contains(&slot),
+                    "{neighbour:?} doesn't connect back to {slot:?}"
+                );
+            }
+        }
+    }
+}
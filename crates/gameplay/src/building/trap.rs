@@ -0,0 +1,97 @@
+//! Traps: pieces that hurt whatever steps into their trigger volume.
+use super::catalogue::{BuildPieceCatalogue, PieceBox};
+use super::BuildPiece;
+use crate::health::{DamageEvent, DamageSource, Health};
+use bevy::prelude::*;
+use bevy_rapier3d::parry::query::intersection_test;
+use bevy_rapier3d::parry::shape::Cuboid;
+use bevy_rapier3d::prelude::*;
+use bevy_rapier3d::rapier::math::Isometry;
+
+/// The state of a trap piece.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Default)]
+pub struct Trap {
+    /// How long until the trap is armed again, in seconds.
+    pub cooldown: f32,
+}
+
+/// An event sent when a trap goes off on something.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrapTriggered {
+    pub trap: Entity,
+    pub target: Entity,
+}
+
+/// Whether `point`, relative to the piece, is inside `volume`.
+fn contains(volume: &PieceBox, point: Vec3) -> bool {
+    let local = volume.rotation.inverse() * (point - volume.center);
+    local.abs().cmple(volume.half_extents).all()
+}
+
+/// Whether `collider`, placed at `target`, overlaps `volume` on a piece placed at `piece`.
+fn overlaps(volume: &PieceBox, piece: &Transform, collider: &Collider, target: &Transform) -> bool {
+    let volume_position = Isometry::from_parts(
+        piece.transform_point(volume.center).into(),
+        (piece.rotation * volume.rotation).into(),
+    );
+    let target_position = Isometry::from_parts(target.translation.into(), target.rotation.into());
+    intersection_test(
+        &volume_position,
+        &Cuboid::new(volume.half_extents.into()),
+        &target_position,
+        &*collider.raw,
+    )
+    .unwrap_or(false)
+}
+
+/// Damages everything with health inside armed traps' trigger volumes, then re-arms them.
+///
+/// A target with a collider is caught by any part of it, so a character standing on a trap is
+/// hit however far above its feet its origin is. Anything else is caught by its origin.
+pub(super) fn trigger_traps(
+    mut trap_query: Query<(Entity, &BuildPiece, &Transform, &mut Trap)>,
+    target_query: Query<(Entity, &Transform, Option<&Collider>), With<Health>>,
+    catalogue: Res<BuildPieceCatalogue>,
+    time: Res<Time>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut triggered_events: EventWriter<TrapTriggered>,
+) {
+    for (trap_entity, piece, transform, mut trap) in trap_query.iter_mut() {
+        if trap.cooldown > 0.0 {
+            trap.cooldown = (trap.cooldown - time.delta_seconds()).max(0.0);
+            continue;
+        }
+        let Some(definition) = catalogue
+            .get(piece.kind)
+            .and_then(|definition| definition.trap)
+        else {
+            continue;
+        };
+
+        let to_local = transform.compute_matrix().inverse();
+        for (target, target_transform, collider) in target_query.iter() {
+            let inside = match collider {
+                Some(collider) => {
+                    overlaps(&definition.trigger, transform, collider, target_transform)
+                }
+                None => contains(
+                    &definition.trigger,
+                    to_local.transform_point3(target_transform.translation),
+                ),
+            };
+            if !inside {
+                continue;
+            }
+            damage_events.send(DamageEvent {
+                target,
+                amount: definition.damage,
+                source: DamageSource::Trap,
+            });
+            triggered_events.send(TrapTriggered {
+                trap: trap_entity,
+                target,
+            });
+            trap.cooldown = definition.cooldown;
+        }
+    }
+}
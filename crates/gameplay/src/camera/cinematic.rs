@@ -0,0 +1,330 @@
+//! A cinematic camera following authored keyframes, for trailer capture.
+//!
+//! A `CinematicPath` is loaded from a RON file and played back with `start_cinematic`, which
+//! detaches the camera from the player until the path ends or `stop_cinematic` is called.
+use super::{CameraPerspective, CameraRig};
+use bevy::prelude::*;
+use common::config::{self, ConfigError};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// A point the cinematic camera passes through.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CinematicKeyframe {
+    /// When the camera reaches this keyframe, in seconds from the start of the path.
+    pub time: f32,
+    pub position: Vec3,
+    /// The point the camera looks at.
+    pub look_at: Vec3,
+    /// The vertical field of view, in degrees.
+    pub fov: f32,
+}
+
+/// Where the cinematic camera is and what it looks at, at one moment of a path.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CinematicPose {
+    pub position: Vec3,
+    pub look_at: Vec3,
+    /// The vertical field of view, in degrees.
+    pub fov: f32,
+}
+
+/// An authored camera path, smoothly interpolated through its keyframes.
+///
+/// Keyframes are kept in time order however the path was written, so sampling never has to
+/// deal with a path that runs backwards.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(from = "CinematicPathData")]
+pub struct CinematicPath {
+    keyframes: Vec<CinematicKeyframe>,
+    /// Whether playback starts over at the end instead of finishing.
+    pub looping: bool,
+}
+
+/// A cinematic path as written in config files, with its keyframes in any order.
+#[derive(Serialize, Deserialize)]
+struct CinematicPathData {
+    keyframes: Vec<CinematicKeyframe>,
+    #[serde(default)]
+    looping: bool,
+}
+
+impl From<CinematicPathData> for CinematicPath {
+    fn from(data: CinematicPathData) -> Self {
+        Self::new(data.keyframes, data.looping)
+    }
+}
+
+/// A Catmull-Rom spline segment from `p1` to `p2`, which passes through every control point.
+fn catmull_rom(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * (2.0 * p1
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
+
+impl CinematicPath {
+    /// A path through `keyframes`, ordered by time.
+    pub fn new(mut keyframes: Vec<CinematicKeyframe>, looping: bool) -> Self {
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Self { keyframes, looping }
+    }
+
+    /// The keyframes, in time order.
+    pub fn keyframes(&self) -> &[CinematicKeyframe] {
+        &self.keyframes
+    }
+
+    /// Loads a path from a config file, ordering its keyframes by time.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        config::load(path)
+    }
+
+    /// Saves the path to a config file, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> Result<(), ConfigError> {
+        config::save(self, path)
+    }
+
+    /// The time of the last keyframe, in seconds.
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |keyframe| keyframe.time)
+    }
+
+    /// The camera pose `time` seconds into the path, or `None` if it has no keyframes.
+    ///
+    /// Positions and look-at points follow a spline through the keyframes; the FOV is eased
+    /// linearly between them.
+    pub fn sample(&self, time: f32) -> Option<CinematicPose> {
+        let keyframes = &self.keyframes;
+        let first = keyframes.first()?;
+        let time = time.clamp(first.time, self.duration());
+
+        // The segment's start: the last keyframe at or before `time`, short of the final one.
+        let start = keyframes
+            .partition_point(|keyframe| keyframe.time <= time)
+            .saturating_sub(1)
+            .min(keyframes.len().saturating_sub(2));
+        let end = (start + 1).min(keyframes.len() - 1);
+        let (from, to) = (keyframes[start], keyframes[end]);
+        let before = keyframes[start.saturating_sub(1)];
+        let after = keyframes[(end + 1).min(keyframes.len() - 1)];
+
+        let span = to.time - from.time;
+        let t = if span > 0.0 {
+            ((time - from.time) / span).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        Some(CinematicPose {
+            position: catmull_rom(
+                before.position,
+                from.position,
+                to.position,
+                after.position,
+                t,
+            ),
+            look_at: catmull_rom(before.look_at, from.look_at, to.look_at, after.look_at, t),
+            fov: from.fov + (to.fov - from.fov) * t,
+        })
+    }
+}
+
+/// The cinematic being played, with its playback controls.
+#[derive(Resource, Debug, Clone)]
+pub struct CinematicPlayback {
+    path: CinematicPath,
+    time: f32,
+    speed: f32,
+    paused: bool,
+}
+
+impl CinematicPlayback {
+    pub fn new(path: CinematicPath) -> Self {
+        Self {
+            path,
+            time: 0.0,
+            speed: 1.0,
+            paused: false,
+        }
+    }
+
+    /// How far into the path playback is, in seconds.
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// The length of the path, in seconds.
+    pub fn duration(&self) -> f32 {
+        self.path.duration()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    /// Jumps to `time` seconds into the path.
+    pub fn seek(&mut self, time: f32) {
+        self.time = time.clamp(0.0, self.duration());
+    }
+
+    /// The playback rate; `1.0` is real time.
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// Sets the playback rate, e.g. `0.5` for slow motion.
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.max(0.0);
+    }
+}
+
+/// An event sent when a non-looping cinematic reaches its end.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CinematicFinished;
+
+/// Starts playing `path`, detaching the camera from the player.
+pub fn start_cinematic(world: &mut World, path: CinematicPath) {
+    world.insert_resource(CinematicPlayback::new(path));
+    world
+        .resource_mut::<NextState<CameraPerspective>>()
+        .set(CameraPerspective::Cinematic);
+}
+
+/// Stops the running cinematic, returning the camera to the player.
+pub fn stop_cinematic(world: &mut World) {
+    let attached = world
+        .query::<&CameraRig>()
+        .get_single(world)
+        .map_or(CameraPerspective::default(), |rig| rig.attached_perspective);
+    world
+        .resource_mut::<NextState<CameraPerspective>>()
+        .set(attached);
+}
+
+/// Places the rig on the path, then advances playback, finishing at the end of the path.
+pub(super) fn play_cinematic(
+    mut playback: ResMut<CinematicPlayback>,
+    mut rig_query: Query<&mut CameraRig>,
+    perspective: Res<State<CameraPerspective>>,
+    mut next_perspective: ResMut<NextState<CameraPerspective>>,
+    mut finished_events: EventWriter<CinematicFinished>,
+    time: Res<Time>,
+) {
+    if *perspective.get() != CameraPerspective::Cinematic {
+        return;
+    }
+    let Ok(mut rig) = rig_query.get_single_mut() else {
+        return;
+    };
+
+    if let Some(pose) = playback.path.sample(playback.time) {
+        rig.focus = pose.position;
+        rig.look_along(pose.look_at - pose.position);
+        rig.fov = pose.fov;
+    }
+
+    let duration = playback.duration();
+    if !playback.path.looping && playback.time >= duration {
+        finished_events.send(CinematicFinished);
+        next_perspective.set(rig.attached_perspective);
+        return;
+    }
+    if !playback.paused {
+        let advanced = playback.time + time.delta_seconds() * playback.speed;
+        playback.time = if playback.path.looping && duration > 0.0 {
+            advanced % duration
+        } else {
+            advanced.min(duration)
+        };
+    }
+}
+
+/// Discards the playback once the camera leaves the cinematic.
+pub(super) fn stop_cinematic_playback(mut commands: Commands) {
+    commands.remove_resource::<CinematicPlayback>();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyframe(time: f32, x: f32, fov: f32) -> CinematicKeyframe {
+        CinematicKeyframe {
+            time,
+            position: Vec3::new(x, 2.0, 0.0),
+            look_at: Vec3::new(x, 0.0, -5.0),
+            fov,
+        }
+    }
+
+    #[test]
+    fn test_path_passes_through_keyframes() {
+        let path = CinematicPath::new(
+            vec![
+                keyframe(0.0, 0.0, 60.0),
+                keyframe(1.0, 4.0, 50.0),
+                keyframe(3.0, 6.0, 40.0),
+            ],
+            false,
+        );
+        assert_eq!(path.duration(), 3.0);
+        for keyframe in path.keyframes() {
+            let pose = path.sample(keyframe.time).unwrap();
+            assert!(pose.position.distance(keyframe.position) < 1e-5);
+            assert!(pose.look_at.distance(keyframe.look_at) < 1e-5);
+            assert!((pose.fov - keyframe.fov).abs() < 1e-5);
+        }
+
+        let between = path.sample(2.0).unwrap();
+        assert!(between.position.x > 4.0 && between.position.x < 6.0);
+        assert!((between.fov - 45.0).abs() < 1e-5);
+        // Sampling outside the path holds the ends.
+        assert_eq!(path.sample(-1.0), path.sample(0.0));
+        assert_eq!(path.sample(10.0), path.sample(3.0));
+        assert_eq!(CinematicPath::default().sample(0.0), None);
+    }
+
+    #[test]
+    fn test_single_keyframe_path_holds_still() {
+        let path = CinematicPath::new(vec![keyframe(0.0, 1.0, 70.0)], false);
+        let pose = path.sample(0.5).unwrap();
+        assert_eq!(pose.position, path.keyframes()[0].position);
+        assert_eq!(pose.fov, 70.0);
+    }
+
+    #[test]
+    fn test_unsorted_keyframes_are_put_in_order() {
+        let keyframes = vec![
+            keyframe(3.0, 6.0, 40.0),
+            keyframe(0.0, 0.0, 60.0),
+            keyframe(1.0, 4.0, 50.0),
+        ];
+        let path = CinematicPath::new(keyframes.clone(), false);
+        let times: Vec<_> = path
+            .keyframes()
+            .iter()
+            .map(|keyframe| keyframe.time)
+            .collect();
+        assert_eq!(times, [0.0, 1.0, 3.0]);
+        assert_eq!(path.duration(), 3.0);
+        assert_eq!(path.sample(1.0).unwrap().fov, 50.0);
+
+        // Paths read from config files are ordered the same way.
+        let source = config::to_ron(&CinematicPathData {
+            keyframes,
+            looping: false,
+        })
+        .unwrap();
+        assert_eq!(config::from_ron::<CinematicPath>(&source).unwrap(), path);
+    }
+}
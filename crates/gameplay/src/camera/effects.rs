@@ -0,0 +1,343 @@
+//! Additive camera feedback: trauma-based shake, recoil kicks, head bob and FOV punches.
+//!
+//! Effects are accumulated in `CameraEffects` on the main camera and sampled each frame into a
+//! `CameraEffectSample`, which is layered on top of the rig's placement. Sampling is a pure
+//! function of the effect state, so effects can be tested without running an app.
+use super::{CameraPerspective, CameraRig, MainCamera};
+use crate::movement::{CharacterVelocity, Landed, MovementState, LETHAL_IMPACT_SPEED};
+use crate::player::Player;
+use bevy::prelude::*;
+use std::f32::consts::TAU;
+
+/// Trauma lost per second.
+const TRAUMA_DECAY: f32 = 1.0;
+/// How many times per second the shake noise changes direction, roughly.
+const SHAKE_FREQUENCY: f32 = 15.0;
+/// The largest camera displacement at full trauma, in meters.
+const MAX_SHAKE_OFFSET: f32 = 0.15;
+/// The largest pitch and yaw wobble at full trauma, in radians.
+const MAX_SHAKE_ANGLE: f32 = 0.07;
+/// The largest roll wobble at full trauma, in radians.
+const MAX_SHAKE_ROLL: f32 = 0.1;
+/// How quickly recoil kicks settle back; higher is faster.
+const RECOIL_RECOVERY_RATE: f32 = 10.0;
+/// How quickly FOV punches settle back; higher is faster.
+const FOV_PUNCH_RECOVERY_RATE: f32 = 8.0;
+/// The FOV widening while sprinting, in degrees.
+const SPRINT_FOV_KICK: f32 = 8.0;
+/// How quickly the sprint FOV kick and head bob amplitude follow the movement state.
+const MOVEMENT_EFFECT_RATE: f32 = 6.0;
+/// The distance covered per head bob cycle (two steps), in meters.
+const BOB_STRIDE: f32 = 1.8;
+/// Impact speeds below this don't shake the camera on landing.
+const LANDING_SHAKE_MIN_SPEED: f32 = 6.0;
+
+/// An event adding trauma to the camera shake, e.g. from an explosion. Trauma is `0.0..=1.0`.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct CameraShake {
+    pub trauma: f32,
+}
+
+/// An event kicking the view, e.g. from weapon fire. Angles are in radians; positive pitch is up.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct CameraRecoil {
+    pub pitch: f32,
+    pub yaw: f32,
+}
+
+/// An event briefly widening (or, if negative, narrowing) the field of view, in degrees.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct CameraFovPunch {
+    pub degrees: f32,
+}
+
+/// The combined output of all effects for one frame, relative to the rig's camera placement.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CameraEffectSample {
+    /// Translation in camera space, in meters.
+    pub offset: Vec3,
+    /// Pitch (up), yaw (left) and roll, in radians.
+    pub angles: Vec3,
+    /// Added field of view, in degrees.
+    pub fov: f32,
+}
+
+impl CameraEffectSample {
+    /// The sample's rotation in camera space.
+    pub fn rotation(&self) -> Quat {
+        Quat::from_euler(EulerRot::YXZ, self.angles.y, self.angles.x, self.angles.z)
+    }
+}
+
+/// The head bob amplitude for a movement state, in meters.
+fn bob_amplitude(state: MovementState) -> f32 {
+    match state {
+        MovementState::Walking | MovementState::Running => 0.04,
+        MovementState::Sprinting => 0.07,
+        MovementState::Crouching => 0.02,
+        _ => 0.0,
+    }
+}
+
+/// Smooth 1D value noise in `-1.0..=1.0`; each `seed` gives an independent channel.
+pub fn value_noise(seed: u32, x: f32) -> f32 {
+    let hash = |i: i32| {
+        let mut h = seed ^ (i as u32).wrapping_mul(0x27D4_EB2D);
+        h ^= h >> 15;
+        h = h.wrapping_mul(0x85EB_CA6B);
+        h ^= h >> 13;
+        h = h.wrapping_mul(0xC2B2_AE35);
+        h ^= h >> 16;
+        h as f32 / u32::MAX as f32 * 2.0 - 1.0
+    };
+    let cell = x.floor();
+    let t = x - cell;
+    let t = t * t * (3.0 - 2.0 * t);
+    let (a, b) = (hash(cell as i32), hash(cell as i32 + 1));
+    a + (b - a) * t
+}
+
+/// The live state of every camera effect, kept on the main camera.
+#[derive(Component, Debug, Clone, Default, PartialEq)]
+pub struct CameraEffects {
+    /// Shake intensity, `0.0..=1.0`; the shake itself scales with its square.
+    pub trauma: f32,
+    /// The clock driving the shake noise, in seconds.
+    time: f32,
+    /// Outstanding recoil: pitch and yaw, in radians.
+    recoil: Vec2,
+    /// Outstanding FOV punch, in degrees.
+    fov_punch: f32,
+    /// The sprint FOV kick, eased in and out, in degrees.
+    sprint_fov: f32,
+    /// The head bob cycle position, in radians.
+    bob_phase: f32,
+    /// The head bob amplitude, eased towards the movement state's, in meters.
+    bob_amplitude: f32,
+    /// The offset applied to the camera last frame, removed before the rig places it again.
+    applied_offset: Vec3,
+}
+
+impl CameraEffects {
+    /// Adds trauma, saturating at full intensity.
+    pub fn add_trauma(&mut self, trauma: f32) {
+        self.trauma = (self.trauma + trauma.max(0.0)).min(1.0);
+    }
+
+    /// Kicks the view by a recoil impulse.
+    pub fn add_recoil(&mut self, pitch: f32, yaw: f32) {
+        self.recoil += Vec2::new(pitch, yaw);
+    }
+
+    /// Punches the field of view.
+    pub fn punch_fov(&mut self, degrees: f32) {
+        self.fov_punch += degrees;
+    }
+
+    /// Advances the effects by `dt` seconds for a player in `state` moving at `speed` m/s.
+    ///
+    /// Head bob only runs when `head_bob` is set, i.e. in first person.
+    pub fn advance(&mut self, dt: f32, state: MovementState, speed: f32, head_bob: bool) {
+        let settle = |rate: f32| (-rate * dt).exp();
+        let approach = |rate: f32| 1.0 - settle(rate);
+
+        self.time += dt;
+        self.trauma = (self.trauma - TRAUMA_DECAY * dt).max(0.0);
+        self.recoil *= settle(RECOIL_RECOVERY_RATE);
+        self.fov_punch *= settle(FOV_PUNCH_RECOVERY_RATE);
+
+        let sprint_target = if state == MovementState::Sprinting {
+            SPRINT_FOV_KICK
+        } else {
+            0.0
+        };
+        self.sprint_fov += (sprint_target - self.sprint_fov) * approach(MOVEMENT_EFFECT_RATE);
+
+        let bob_target = if head_bob { bob_amplitude(state) } else { 0.0 };
+        self.bob_amplitude += (bob_target - self.bob_amplitude) * approach(MOVEMENT_EFFECT_RATE);
+        self.bob_phase = (self.bob_phase + speed * dt * TAU / BOB_STRIDE) % TAU;
+    }
+
+    /// Combines every effect into this frame's camera offset, rotation and FOV change.
+    pub fn sample(&self) -> CameraEffectSample {
+        let shake = self.trauma * self.trauma;
+        let noise = |channel: u32| value_noise(channel, self.time * SHAKE_FREQUENCY);
+
+        let shake_offset = Vec3::new(noise(0), noise(1), noise(2)) * MAX_SHAKE_OFFSET * shake;
+        let shake_angles = Vec3::new(
+            noise(3) * MAX_SHAKE_ANGLE,
+            noise(4) * MAX_SHAKE_ANGLE,
+            noise(5) * MAX_SHAKE_ROLL,
+        ) * shake;
+
+        // A figure-eight: one sideways sway and two dips per stride.
+        let bob_offset = Vec3::new(
+            self.bob_phase.sin() * 0.5,
+            (self.bob_phase * 2.0).sin().abs() - 0.5,
+            0.0,
+        ) * self.bob_amplitude;
+
+        CameraEffectSample {
+            offset: shake_offset + bob_offset,
+            angles: shake_angles + Vec3::new(self.recoil.x, self.recoil.y, 0.0),
+            fov: self.fov_punch + self.sprint_fov,
+        }
+    }
+}
+
+/// Feeds effect events and hard landings into the camera's effects.
+pub(super) fn receive_camera_events(
+    mut effects_query: Query<&mut CameraEffects, With<MainCamera>>,
+    mut shake_events: EventReader<CameraShake>,
+    mut recoil_events: EventReader<CameraRecoil>,
+    mut fov_events: EventReader<CameraFovPunch>,
+    mut landed_events: EventReader<Landed>,
+) {
+    let Ok(mut effects) = effects_query.get_single_mut() else {
+        return;
+    };
+    for event in shake_events.read() {
+        effects.add_trauma(event.trauma);
+    }
+    for event in recoil_events.read() {
+        effects.add_recoil(event.pitch, event.yaw);
+    }
+    for event in fov_events.read() {
+        effects.punch_fov(event.degrees);
+    }
+    for event in landed_events.read() {
+        let trauma = (event.impact_speed - LANDING_SHAKE_MIN_SPEED)
+            / (LETHAL_IMPACT_SPEED - LANDING_SHAKE_MIN_SPEED);
+        effects.add_trauma(trauma.clamp(0.0, 1.0));
+    }
+}
+
+/// Advances the effects from the player's movement.
+pub(super) fn update_camera_effects(
+    mut effects_query: Query<&mut CameraEffects, With<MainCamera>>,
+    player_query: Query<&CharacterVelocity, With<Player>>,
+    movement_state: Option<Res<State<MovementState>>>,
+    perspective: Res<State<CameraPerspective>>,
+    time: Res<Time>,
+) {
+    let Ok(mut effects) = effects_query.get_single_mut() else {
+        return;
+    };
+    let state = movement_state.map_or(MovementState::Idle, |state| *state.get());
+    let speed = player_query
+        .get_single()
+        .map_or(0.0, |velocity| velocity.horizontal().length());
+    let head_bob = *perspective.get() == CameraPerspective::FirstPerson;
+    effects.advance(time.delta_seconds(), state, speed, head_bob);
+}
+
+/// Takes last frame's effect offset back out, so the rig places the camera from a clean state.
+pub(super) fn remove_camera_effects(
+    mut camera_query: Query<(&mut Transform, &mut CameraEffects), With<MainCamera>>,
+) {
+    for (mut transform, mut effects) in camera_query.iter_mut() {
+        transform.translation -= effects.applied_offset;
+        effects.applied_offset = Vec3::ZERO;
+    }
+}
+
+/// Layers the effects on top of the camera's placement. Runs after `update_camera_position`.
+pub(super) fn apply_camera_effects(
+    mut camera_query: Query<(&mut Transform, &mut CameraEffects), With<MainCamera>>,
+) {
+    for (mut transform, mut effects) in camera_query.iter_mut() {
+        let sample = effects.sample();
+        transform.translation += sample.offset;
+        transform.rotation = sample.rotation();
+        effects.applied_offset = sample.offset;
+    }
+}
+
+/// The field of view after effects, for `apply_camera_fov`.
+pub(super) fn effect_fov(rig: &CameraRig, effects: Option<&CameraEffects>) -> f32 {
+    rig.fov + effects.map_or(0.0, |effects| effects.sample().fov)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: f32 = 1.0 / 60.0;
+
+    #[test]
+    fn test_sampling_is_deterministic() {
+        let mut effects = CameraEffects::default();
+        effects.add_trauma(0.8);
+        effects.add_recoil(0.05, -0.01);
+        effects.advance(0.3, MovementState::Walking, 5.0, true);
+
+        let copy = effects.clone();
+        assert_eq!(effects.sample(), copy.sample());
+        assert_ne!(effects.sample().offset, Vec3::ZERO);
+    }
+
+    #[test]
+    fn test_shake_scales_with_trauma_and_decays() {
+        let mut effects = CameraEffects::default();
+        effects.advance(0.37, MovementState::Idle, 0.0, false);
+        assert_eq!(effects.sample(), CameraEffectSample::default());
+
+        effects.add_trauma(0.5);
+        let light = effects.sample().offset.length();
+        effects.add_trauma(0.5);
+        let heavy = effects.sample().offset.length();
+        // Shake grows with the square of trauma.
+        assert!((heavy - light * 4.0).abs() < 1e-5);
+
+        // Full trauma is gone after a second.
+        for _ in 0..61 {
+            effects.advance(DT, MovementState::Idle, 0.0, false);
+        }
+        assert_eq!(effects.trauma, 0.0);
+        assert_eq!(effects.sample().offset, Vec3::ZERO);
+    }
+
+    #[test]
+    fn test_recoil_and_fov_punch_settle() {
+        let mut effects = CameraEffects::default();
+        effects.add_recoil(0.1, 0.0);
+        effects.punch_fov(10.0);
+        assert_eq!(effects.sample().angles.x, 0.1);
+        assert_eq!(effects.sample().fov, 10.0);
+
+        for _ in 0..60 {
+            effects.advance(DT, MovementState::Idle, 0.0, false);
+        }
+        assert!(effects.sample().angles.x.abs() < 1e-4);
+        assert!(effects.sample().fov.abs() < 1e-2);
+    }
+
+    #[test]
+    fn test_head_bob_follows_state_and_speed() {
+        let bob = |state: MovementState, speed: f32| {
+            let mut effects = CameraEffects::default();
+            let mut peak: f32 = 0.0;
+            for _ in 0..120 {
+                effects.advance(DT, state, speed, true);
+                peak = peak.max(effects.sample().offset.y.abs());
+            }
+            peak
+        };
+
+        assert_eq!(bob(MovementState::Idle, 0.0), 0.0);
+        assert!(bob(MovementState::Walking, 5.0) > 0.0);
+        assert!(bob(MovementState::Sprinting, 10.0) > bob(MovementState::Walking, 5.0));
+        assert!(bob(MovementState::Crouching, 2.5) < bob(MovementState::Walking, 5.0));
+    }
+
+    #[test]
+    fn test_sprinting_kicks_fov() {
+        let mut effects = CameraEffects::default();
+        for _ in 0..120 {
+            effects.advance(DT, MovementState::Sprinting, 10.0, false);
+        }
+        assert!((effects.sample().fov - SPRINT_FOV_KICK).abs() < 0.1);
+        // Third person doesn't bob.
+        assert_eq!(effects.sample().offset, Vec3::ZERO);
+    }
+}
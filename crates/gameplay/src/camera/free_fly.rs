@@ -0,0 +1,52 @@
+//! A spectator camera detached from the player, flying through geometry.
+use super::{CameraPerspective, CameraRig, MainCamera};
+use bevy::prelude::*;
+use common::input::{ActionState, InputAction};
+
+/// The free-fly camera's speed, in meters per second.
+const FREE_FLY_SPEED: f32 = 10.0;
+/// The speed multiplier while holding sprint.
+const FREE_FLY_BOOST: f32 = 3.0;
+
+/// Moves the rig's focus to where the camera was, so entering free fly doesn't jump the view.
+pub(super) fn detach_free_fly_camera(
+    mut rig_query: Query<(&mut CameraRig, &mut Transform), Without<MainCamera>>,
+    camera_query: Query<&Transform, With<MainCamera>>,
+) {
+    let Ok((mut rig, mut rig_transform)) = rig_query.get_single_mut() else {
+        return;
+    };
+    let camera_offset = camera_query
+        .get_single()
+        .map_or(Vec3::ZERO, |transform| transform.translation);
+    rig.focus += rig_transform.rotation * camera_offset;
+    rig_transform.rotation = rig.rotation();
+}
+
+/// Flies the camera along the view with the movement keys, rising on jump and sinking on crouch.
+///
+/// There is no collision: the camera passes through colliders and voxels alike.
+pub(super) fn fly_camera(
+    mut rig_query: Query<&mut CameraRig>,
+    actions: Res<ActionState>,
+    perspective: Res<State<CameraPerspective>>,
+    time: Res<Time>,
+) {
+    if *perspective.get() != CameraPerspective::FreeFly {
+        return;
+    }
+    let Ok(mut rig) = rig_query.get_single_mut() else {
+        return;
+    };
+
+    let input = actions.move_axis();
+    let vertical = actions.value(InputAction::Jump) - actions.value(InputAction::Crouch);
+    let direction = rig.rotation() * Vec3::new(input.x, 0.0, -input.y) + Vec3::Y * vertical;
+    let boost = if actions.pressed(InputAction::Sprint) {
+        FREE_FLY_BOOST
+    } else {
+        1.0
+    };
+    let step = direction.clamp_length_max(1.0) * FREE_FLY_SPEED * boost * time.delta_seconds();
+    rig.focus += step;
+}
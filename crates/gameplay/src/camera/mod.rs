@@ -0,0 +1,666 @@
+//! A camera that follows the player, orbiting in third person and looking around in first person.
+//!
+//! For debugging and trailer capture the camera can also detach from the player, either flying
+//! freely or following an authored cinematic path.
+
+use crate::movement::{CharacterVelocity, Landed, MovementState};
+use crate::player::{Player, PLAYER_FEET_OFFSET};
+use bevy::{
+    input::mouse::{MouseMotion, MouseWheel},
+    prelude::*,
+};
+use bevy_rapier3d::prelude::*;
+use common::config;
+use common::input::{ActionState, InputAction, SuspendDeviceInput};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use world::{MaterialRegistry, WorldData};
+
+pub mod cinematic;
+pub mod effects;
+pub mod free_fly;
+pub mod profile;
+
+use cinematic::{CinematicFinished, CinematicPlayback};
+use effects::{CameraEffects, CameraFovPunch, CameraRecoil, CameraShake};
+use profile::{CameraProfile, CameraProfiles, CameraSmoothing, DEFAULT_CAMERA_PROFILES_PATH};
+
+const CAMERA_MIN_DISTANCE: f32 = 2.0;
+const CAMERA_MAX_DISTANCE: f32 = 10.0;
+const CAMERA_SENSITIVITY: f32 = 0.5;
+const SCROLL_SENSITIVITY: f32 = 0.5;
+/// How far the third-person camera can dip below the focus, looking up.
+const THIRD_PERSON_MIN_PITCH: f32 = -0.35;
+/// How far the view can tilt up or down, short of straight up or down.
+const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.05;
+/// Eye height above the feet while standing, in meters.
+const STANDING_EYE_HEIGHT: f32 = 1.7;
+/// Eye height above the feet while crouching or sliding.
+const CROUCHING_EYE_HEIGHT: f32 = 1.0;
+/// Eye height above the feet while prone or diving.
+const PRONE_EYE_HEIGHT: f32 = 0.4;
+/// How quickly the eye height follows stance changes; higher is faster.
+const EYE_HEIGHT_RATE: f32 = 12.0;
+/// The radius of the sphere cast used to keep the camera out of geometry.
+const CAMERA_COLLISION_RADIUS: f32 = 0.2;
+/// The closest the camera is pulled in towards the focus when blocked.
+const CAMERA_MIN_COLLISION_DISTANCE: f32 = 0.3;
+/// The spacing of the samples taken along the camera boom when checking voxels.
+const VOXEL_PROBE_STEP: f32 = 0.1;
+
+/// The camera's perspective state.
+#[derive(States, Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum CameraPerspective {
+    #[default]
+    ThirdPerson,
+    FirstPerson,
+    /// A camera detached from the player, flying through geometry.
+    FreeFly,
+    /// A camera detached from the player, following a `CinematicPath`.
+    Cinematic,
+}
+
+impl CameraPerspective {
+    /// Whether the camera is attached to the player, who then responds to movement input.
+    pub fn follows_player(self) -> bool {
+        matches!(
+            self,
+            CameraPerspective::ThirdPerson | CameraPerspective::FirstPerson
+        )
+    }
+}
+
+/// Which shoulder the over-the-shoulder camera sits behind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Shoulder {
+    Left,
+    #[default]
+    Right,
+}
+
+impl Shoulder {
+    /// `1.0` for the right shoulder and `-1.0` for the left, mirroring offsets along X.
+    fn sign(self) -> f32 {
+        match self {
+            Shoulder::Left => -1.0,
+            Shoulder::Right => 1.0,
+        }
+    }
+
+    fn swapped(self) -> Self {
+        match self {
+            Shoulder::Left => Shoulder::Right,
+            Shoulder::Right => Shoulder::Left,
+        }
+    }
+}
+
+/// A marker component for the main camera.
+#[derive(Component)]
+pub struct MainCamera;
+
+/// A marker component for the camera's orbital rig.
+#[derive(Component)]
+pub struct CameraRig {
+    /// The focus point of the camera.
+    pub focus: Vec3,
+    /// The distance from the focus point while exploring, adjusted by zooming.
+    distance: f32,
+    /// The length of the third-person boom: pulled in at once when geometry blocks it, and
+    /// otherwise eased towards the profile's distance.
+    collision_distance: f32,
+    /// The yaw angle (horizontal rotation).
+    yaw: f32,
+    /// The pitch angle (vertical rotation); positive looks down.
+    pitch: f32,
+    /// The first-person eye height above the player's feet, eased towards the stance's height.
+    eye_height: f32,
+    /// Whether the player is aiming.
+    aiming: bool,
+    /// The shoulder the aiming camera sits behind.
+    shoulder: Shoulder,
+    /// How far the rig has blended from the explore profile to the aim profile, `0.0..=1.0`.
+    aim_blend: f32,
+    /// The blended shoulder side, from `-1.0` (left) to `1.0` (right).
+    shoulder_blend: f32,
+    /// The pivot's offset from the focus in rig space, pulled in when blocked by geometry.
+    pivot: Vec3,
+    /// The current vertical field of view, in degrees.
+    fov: f32,
+    /// The player-following perspective to return to once the camera is no longer detached.
+    attached_perspective: CameraPerspective,
+}
+
+impl Default for CameraRig {
+    fn default() -> Self {
+        Self {
+            focus: Vec3::ZERO,
+            distance: 5.0,
+            collision_distance: 5.0,
+            yaw: 0.0,
+            pitch: std::f32::consts::FRAC_PI_4,
+            eye_height: STANDING_EYE_HEIGHT,
+            aiming: false,
+            shoulder: Shoulder::default(),
+            aim_blend: 0.0,
+            shoulder_blend: Shoulder::default().sign(),
+            pivot: Vec3::ZERO,
+            attached_perspective: CameraPerspective::default(),
+            fov: 70.0,
+        }
+    }
+}
+
+/// Spawns the camera rig and the main camera.
+fn spawn_camera(mut commands: Commands, profiles: Res<CameraProfiles>) {
+    bevy::log::info!("Spawning camera...");
+    let rig = CameraRig {
+        distance: profiles.explore.distance,
+        collision_distance: profiles.explore.distance,
+        fov: profiles.explore.fov,
+        ..default()
+    };
+    let camera_distance = rig.distance;
+    let transform =
+        Transform::from_translation(rig.focus) * Transform::from_rotation(rig.rotation());
+
+    commands
+        .spawn((
+            rig,
+            transform,
+            GlobalTransform::default(),
+            Name::new("Camera Rig"),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                MainCamera,
+                CameraEffects::default(),
+                // Camera3dBundle is commented out for headless mode
+                // Camera3dBundle {
+                //     transform: Transform::from_translation(Vec3::new(0.0, 0.0, camera_distance)),
+                //     ..default()
+                // },
+                TransformBundle::from_transform(Transform::from_xyz(0.0, 0.0, camera_distance)),
+                Name::new("Main Camera"),
+            ));
+
+            // Add a light to the rig
+            // parent.spawn(PointLightBundle {
+            //     point_light: PointLight {
+            //         intensity: 1_000_000.0,
+            //         shadows_enabled: true,
+            //         ..default()
+            //     },
+            //     transform: Transform::from_xyz(0.0, 0.0, 0.0),
+            //     ..default()
+            // });
+        });
+}
+
+/// The first-person eye height above the feet for a movement state.
+pub fn eye_height(state: MovementState) -> f32 {
+    match state {
+        MovementState::Crouching | MovementState::Sliding => CROUCHING_EYE_HEIGHT,
+        MovementState::Proning | MovementState::Diving => PRONE_EYE_HEIGHT,
+        _ => STANDING_EYE_HEIGHT,
+    }
+}
+
+/// The range the rig's pitch is clamped to in a perspective.
+fn pitch_limits(perspective: CameraPerspective) -> (f32, f32) {
+    match perspective {
+        CameraPerspective::ThirdPerson => (THIRD_PERSON_MIN_PITCH, MAX_PITCH),
+        _ => (-MAX_PITCH, MAX_PITCH),
+    }
+}
+
+/// System to update the camera rig's focus to follow the player.
+///
+/// In third person the focus trails a point slightly ahead of the player, predicted from their
+/// velocity. In first person it sits at eye height, which follows the player's stance.
+fn update_camera_focus(
+    mut rig_query: Query<&mut CameraRig>,
+    player_query: Query<(&Transform, Option<&CharacterVelocity>), With<Player>>,
+    perspective: Res<State<CameraPerspective>>,
+    movement_state: Option<Res<State<MovementState>>>,
+    profiles: Res<CameraProfiles>,
+    time: Res<Time>,
+) {
+    let Ok((player_transform, velocity)) = player_query.get_single() else {
+        return;
+    };
+    let Ok(mut rig) = rig_query.get_single_mut() else {
+        return;
+    };
+
+    let target = movement_state.map_or(STANDING_EYE_HEIGHT, |state| eye_height(*state.get()));
+    let ease = 1.0 - (-EYE_HEIGHT_RATE * time.delta_seconds()).exp();
+    rig.eye_height += (target - rig.eye_height) * ease;
+
+    match perspective.get() {
+        CameraPerspective::ThirdPerson => {
+            let smoothing = profiles.smoothing;
+            let look_ahead = velocity.map_or(Vec3::ZERO, |velocity| velocity.horizontal());
+            let target = player_transform.translation + look_ahead * smoothing.look_ahead;
+            let blend = CameraSmoothing::blend(smoothing.position_half_life, time.delta_seconds());
+            rig.focus = rig.focus.lerp(target, blend);
+        }
+        CameraPerspective::FirstPerson => {
+            rig.focus =
+                player_transform.translation + Vec3::Y * (rig.eye_height - PLAYER_FEET_OFFSET);
+        }
+        // Detached cameras move their focus themselves.
+        CameraPerspective::FreeFly | CameraPerspective::Cinematic => {}
+    }
+}
+
+/// System to handle mouse input for looking around and, in third person, zooming the camera.
+fn handle_camera_input(
+    mut mouse_motion_events: EventReader<MouseMotion>,
+    mut mouse_wheel_events: EventReader<MouseWheel>,
+    mut rig_query: Query<&mut CameraRig>,
+    perspective: Res<State<CameraPerspective>>,
+    profiles: Res<CameraProfiles>,
+) {
+    let Ok(mut rig) = rig_query.get_single_mut() else {
+        return;
+    };
+    let sensitivity = CAMERA_SENSITIVITY * rig.profile(&profiles).sensitivity;
+
+    // Orbit / look
+    let mut delta = Vec2::ZERO;
+    for event in mouse_motion_events.read() {
+        delta += event.delta;
+    }
+    // Cinematics own the view.
+    if delta.length_squared() > 0.0 && *perspective.get() != CameraPerspective::Cinematic {
+        let (min_pitch, max_pitch) = pitch_limits(*perspective.get());
+        rig.yaw -= delta.x.to_radians() * sensitivity;
+        rig.pitch = (rig.pitch + delta.y.to_radians() * sensitivity).clamp(min_pitch, max_pitch);
+    }
+
+    // Zoom
+    let mut scroll = 0.0;
+    for event in mouse_wheel_events.read() {
+        scroll += event.y;
+    }
+    if scroll.abs() > 0.0 && *perspective.get() == CameraPerspective::ThirdPerson {
+        rig.distance = (rig.distance - scroll * SCROLL_SENSITIVITY)
+            .clamp(CAMERA_MIN_DISTANCE, CAMERA_MAX_DISTANCE);
+    }
+}
+
+/// Enters and leaves aiming, swaps shoulders and blends the rig between its profiles.
+fn update_camera_aim(
+    actions: Res<ActionState>,
+    profiles: Res<CameraProfiles>,
+    mut rig_query: Query<&mut CameraRig>,
+    perspective: Res<State<CameraPerspective>>,
+    time: Res<Time>,
+) {
+    let Ok(mut rig) = rig_query.get_single_mut() else {
+        return;
+    };
+
+    rig.aiming = actions.pressed(InputAction::Aim) && perspective.get().follows_player();
+    if actions.just_pressed(InputAction::SwapShoulder) {
+        rig.shoulder = rig.shoulder.swapped();
+    }
+
+    let ease = 1.0 - (-profiles.blend_rate * time.delta_seconds()).exp();
+    let aim_target = if rig.aiming { 1.0 } else { 0.0 };
+    rig.aim_blend += (aim_target - rig.aim_blend) * ease;
+    let shoulder_target = rig.shoulder.sign();
+    rig.shoulder_blend += (shoulder_target - rig.shoulder_blend) * ease;
+    rig.fov = rig.profile(&profiles).fov;
+}
+
+/// Keeps the view direction when returning to third person, as far as its pitch range allows.
+fn clamp_third_person_pitch(mut rig_query: Query<&mut CameraRig>) {
+    let (min_pitch, max_pitch) = pitch_limits(CameraPerspective::ThirdPerson);
+    for mut rig in rig_query.iter_mut() {
+        rig.pitch = rig.pitch.clamp(min_pitch, max_pitch);
+    }
+}
+
+/// Toggles the camera perspective between first and third person, and in and out of free fly.
+fn toggle_camera_perspective(
+    actions: Res<ActionState>,
+    current_perspective: Res<State<CameraPerspective>>,
+    mut next_perspective: ResMut<NextState<CameraPerspective>>,
+    mut rig_query: Query<&mut CameraRig>,
+) {
+    let Ok(mut rig) = rig_query.get_single_mut() else {
+        return;
+    };
+    let current = *current_perspective.get();
+    if current.follows_player() {
+        rig.attached_perspective = current;
+    }
+
+    let next = if actions.just_pressed(InputAction::ToggleFreeFly) {
+        match current {
+            CameraPerspective::FreeFly => Some(rig.attached_perspective),
+            CameraPerspective::Cinematic => None,
+            _ => Some(CameraPerspective::FreeFly),
+        }
+    } else if actions.just_pressed(InputAction::ToggleCamera) {
+        match current {
+            CameraPerspective::ThirdPerson => Some(CameraPerspective::FirstPerson),
+            CameraPerspective::FirstPerson => Some(CameraPerspective::ThirdPerson),
+            CameraPerspective::FreeFly | CameraPerspective::Cinematic => None,
+        }
+    } else {
+        None
+    };
+    if let Some(next) = next {
+        bevy::log::info!("Toggling camera to: {:?}", next);
+        next_perspective.set(next);
+    }
+}
+
+/// System to apply the rig's state to the camera's transform.
+///
+/// The third-person view turns smoothly towards the rig's orientation; other views follow it
+/// directly.
+fn update_camera_transform(
+    mut rig_query: Query<(&CameraRig, &mut Transform)>,
+    perspective: Res<State<CameraPerspective>>,
+    profiles: Res<CameraProfiles>,
+    time: Res<Time>,
+) {
+    let half_life = match perspective.get() {
+        CameraPerspective::ThirdPerson => profiles.smoothing.rotation_half_life,
+        _ => 0.0,
+    };
+    let blend = CameraSmoothing::blend(half_life, time.delta_seconds());
+    for (rig, mut transform) in rig_query.iter_mut() {
+        transform.translation = rig.focus;
+        transform.rotation = transform.rotation.slerp(rig.rotation(), blend);
+    }
+}
+
+/// Cuts the rig to the player once it has spawned, and whenever a detached camera reattaches.
+fn reattach_camera(
+    mut rig_query: Query<(&mut CameraRig, &mut Transform)>,
+    player_query: Query<&Transform, (With<Player>, Without<CameraRig>)>,
+) {
+    let Ok(player_transform) = player_query.get_single() else {
+        return;
+    };
+    for (mut rig, mut transform) in rig_query.iter_mut() {
+        rig.focus = player_transform.translation;
+        transform.rotation = rig.rotation();
+    }
+}
+
+/// How far a camera sphere can travel from `origin` along `direction` before hitting a collider
+/// or a solid voxel, up to `max_distance`.
+fn free_distance(
+    rapier_context: &RapierContext,
+    filter: QueryFilter,
+    voxels: Option<(&WorldData, &MaterialRegistry)>,
+    origin: Vec3,
+    direction: Vec3,
+    max_distance: f32,
+) -> f32 {
+    let collider_hit = rapier_context
+        .cast_shape(
+            origin,
+            Quat::IDENTITY,
+            direction,
+            &Collider::ball(CAMERA_COLLISION_RADIUS),
+            max_distance,
+            false,
+            filter,
+        )
+        .map(|(_, hit)| hit.toi);
+    let voxel_hit = voxels.and_then(|(world, materials)| {
+        world
+            .solid_hit_distance(materials, origin, direction, max_distance, VOXEL_PROBE_STEP)
+            .map(|distance| distance - CAMERA_COLLISION_RADIUS)
+    });
+
+    [collider_hit, voxel_hit]
+        .into_iter()
+        .flatten()
+        .fold(max_distance, f32::min)
+        .max(0.0)
+}
+
+/// Keeps the third-person camera from ending up inside colliders or voxels.
+///
+/// The shoulder pivot is pulled towards the focus if it would be inside a wall, then the boom
+/// behind it is shortened. The camera snaps in to the blocked distance immediately and eases back
+/// out once clear, just as it eases in and out when zooming or aiming.
+fn resolve_camera_collision(
+    mut rig_query: Query<(&mut CameraRig, &Transform)>,
+    player_query: Query<Entity, With<Player>>,
+    rapier_context: Res<RapierContext>,
+    world_data: Option<Res<WorldData>>,
+    materials: Option<Res<MaterialRegistry>>,
+    profiles: Res<CameraProfiles>,
+    time: Res<Time>,
+) {
+    let Ok((mut rig, rig_transform)) = rig_query.get_single_mut() else {
+        return;
+    };
+
+    let mut filter = QueryFilter::new().exclude_sensors();
+    if let Ok(player) = player_query.get_single() {
+        filter = filter.exclude_collider(player);
+    }
+    let voxels = world_data.as_deref().zip(materials.as_deref());
+    // The boom follows the smoothed view, not where it is turning to.
+    let rotation = rig_transform.rotation;
+
+    let offset = rig.profile(&profiles).shoulder_offset;
+    let offset_length = offset.length();
+    rig.pivot = if offset_length > 0.0 {
+        let direction = rotation * offset / offset_length;
+        let free = free_distance(
+            &rapier_context,
+            filter,
+            voxels,
+            rig.focus,
+            direction,
+            offset_length,
+        );
+        offset * (free / offset_length)
+    } else {
+        Vec3::ZERO
+    };
+
+    // Checked against the longest boom of any profile, so blending between them never snaps.
+    let max_distance = rig.distance.max(profiles.aim.distance);
+    let target = free_distance(
+        &rapier_context,
+        filter,
+        voxels,
+        rig.focus + rotation * rig.pivot,
+        rotation * Vec3::Z,
+        max_distance,
+    )
+    .max(CAMERA_MIN_COLLISION_DISTANCE);
+    rig.collision_distance = if target < rig.collision_distance {
+        target
+    } else {
+        let desired = rig.profile(&profiles).distance.min(target);
+        let blend =
+            CameraSmoothing::blend(profiles.smoothing.distance_half_life, time.delta_seconds());
+        rig.collision_distance + (desired - rig.collision_distance) * blend
+    };
+}
+
+/// Pulls the boom in when leaving first person, so the third-person camera eases out of the head.
+fn retract_camera_boom(mut rig_query: Query<&mut CameraRig>) {
+    for mut rig in rig_query.iter_mut() {
+        rig.collision_distance = CAMERA_MIN_COLLISION_DISTANCE;
+    }
+}
+
+/// Updates the camera's local transform to reflect the current perspective (1st/3rd person).
+///
+/// In third person the camera sits on the boom, which does its own easing; leaving third person
+/// eases the camera into the rig's center.
+fn update_camera_position(
+    mut camera_query: Query<&mut Transform, With<MainCamera>>,
+    rig_query: Query<&CameraRig>,
+    perspective: Res<State<CameraPerspective>>,
+    profiles: Res<CameraProfiles>,
+    time: Res<Time>,
+) {
+    let Ok(mut camera_transform) = camera_query.get_single_mut() else {
+        return;
+    };
+    let Ok(rig) = rig_query.get_single() else {
+        return;
+    };
+
+    camera_transform.translation = match perspective.get() {
+        CameraPerspective::ThirdPerson => rig.pivot + Vec3::Z * rig.collision_distance,
+        CameraPerspective::FirstPerson => {
+            let blend =
+                CameraSmoothing::blend(profiles.smoothing.distance_half_life, time.delta_seconds());
+            camera_transform.translation.lerp(Vec3::ZERO, blend)
+        }
+        // Detached cameras are placed exactly.
+        CameraPerspective::FreeFly | CameraPerspective::Cinematic => Vec3::ZERO,
+    };
+}
+
+/// Applies the rig's field of view, plus any effect kicks, to the main camera's projection.
+fn apply_camera_fov(
+    rig_query: Query<&CameraRig>,
+    mut projection_query: Query<(&mut Projection, Option<&CameraEffects>), With<MainCamera>>,
+) {
+    let Ok(rig) = rig_query.get_single() else {
+        return;
+    };
+    for (mut projection, effects) in projection_query.iter_mut() {
+        if let Projection::Perspective(perspective) = projection.as_mut() {
+            perspective.fov = effects::effect_fov(rig, effects).to_radians();
+        }
+    }
+}
+
+impl CameraRig {
+    /// Whether the player is aiming.
+    pub fn is_aiming(&self) -> bool {
+        self.aiming
+    }
+
+    /// Sets whether the player is aiming, as when restoring a recorded view.
+    pub fn set_aiming(&mut self, aiming: bool) {
+        self.aiming = aiming;
+    }
+
+    /// The yaw and pitch the rig is pointed at, in radians.
+    pub fn look_angles(&self) -> (f32, f32) {
+        (self.yaw, self.pitch)
+    }
+
+    /// Points the rig at a yaw and pitch, as when restoring a recorded view.
+    pub fn set_look_angles(&mut self, yaw: f32, pitch: f32) {
+        self.yaw = yaw;
+        self.pitch = pitch;
+    }
+
+    /// The shoulder the aiming camera sits behind.
+    pub fn shoulder(&self) -> Shoulder {
+        self.shoulder
+    }
+
+    /// The current vertical field of view, in degrees.
+    pub fn fov(&self) -> f32 {
+        self.fov
+    }
+
+    /// The current rig parameters, blended between profiles and mirrored to the shoulder.
+    fn profile(&self, profiles: &CameraProfiles) -> CameraProfile {
+        let explore = CameraProfile {
+            distance: self.distance,
+            ..profiles.explore
+        };
+        let mut profile = explore.lerp(&profiles.aim, self.aim_blend);
+        profile.shoulder_offset.x *= self.shoulder_blend;
+        profile
+    }
+
+    /// Points the rig along `direction`, as far as the first-person pitch range allows.
+    fn look_along(&mut self, direction: Vec3) {
+        let direction = direction.normalize_or_zero();
+        if direction == Vec3::ZERO {
+            return;
+        }
+        self.yaw = (-direction.x).atan2(-direction.z);
+        self.pitch = (-direction.y).asin().clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    /// The distance the camera is kept at from the focus after collision is resolved.
+    pub fn collision_distance(&self) -> f32 {
+        self.collision_distance
+    }
+
+    /// The orientation the rig is being pointed in, which the third-person view turns towards.
+    ///
+    /// Positive pitch raises the camera above the focus, looking down.
+    pub fn rotation(&self) -> Quat {
+        Quat::from_rotation_y(self.yaw) * Quat::from_rotation_x(-self.pitch)
+    }
+}
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<CameraProfiles>() {
+            let profiles: CameraProfiles =
+                config::load_or_default(Path::new(DEFAULT_CAMERA_PROFILES_PATH));
+            app.insert_resource(profiles);
+        }
+        app.init_state::<CameraPerspective>()
+            .init_resource::<ActionState>()
+            .add_event::<CameraShake>()
+            .add_event::<CameraRecoil>()
+            .add_event::<CameraFovPunch>()
+            .add_event::<Landed>()
+            .add_event::<CinematicFinished>()
+            .add_systems(Startup, spawn_camera)
+            .add_systems(PostStartup, reattach_camera)
+            .add_systems(
+                OnEnter(CameraPerspective::ThirdPerson),
+                clamp_third_person_pitch,
+            )
+            .add_systems(
+                OnEnter(CameraPerspective::FreeFly),
+                free_fly::detach_free_fly_camera,
+            )
+            .add_systems(OnExit(CameraPerspective::FirstPerson), retract_camera_boom)
+            .add_systems(OnExit(CameraPerspective::FreeFly), reattach_camera)
+            .add_systems(
+                OnExit(CameraPerspective::Cinematic),
+                (cinematic::stop_cinematic_playback, reattach_camera),
+            )
+            .add_systems(
+                Update,
+                (
+                    effects::remove_camera_effects,
+                    handle_camera_input.run_if(not(resource_exists::<SuspendDeviceInput>)),
+                    toggle_camera_perspective,
+                    update_camera_aim,
+                    update_camera_focus,
+                    free_fly::fly_camera,
+                    cinematic::play_cinematic.run_if(resource_exists::<CinematicPlayback>),
+                    update_camera_transform,
+                    resolve_camera_collision,
+                    update_camera_position,
+                    effects::receive_camera_events,
+                    effects::update_camera_effects,
+                    effects::apply_camera_effects,
+                    apply_camera_fov,
+                )
+                    .chain(),
+            );
+    }
+}
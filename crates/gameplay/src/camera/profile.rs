@@ -0,0 +1,136 @@
+//! Data-driven camera rig parameters for exploring and aiming.
+use bevy::prelude::*;
+use common::config::{self, ConfigError};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Where the camera profiles are loaded from by default.
+pub const DEFAULT_CAMERA_PROFILES_PATH: &str = "config/camera.ron";
+
+/// The rig parameters for one camera mode.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CameraProfile {
+    /// The distance from the pivot to the camera in third person, in meters.
+    pub distance: f32,
+    /// The pivot's offset from the focus over the right shoulder; mirrored for the left.
+    pub shoulder_offset: Vec3,
+    /// The vertical field of view, in degrees.
+    pub fov: f32,
+    /// A multiplier on mouse-look sensitivity.
+    pub sensitivity: f32,
+}
+
+impl CameraProfile {
+    /// Blends towards `other`; `t = 0.0` is `self` and `t = 1.0` is `other`.
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        Self {
+            distance: lerp(self.distance, other.distance),
+            shoulder_offset: self.shoulder_offset.lerp(other.shoulder_offset, t),
+            fov: lerp(self.fov, other.fov),
+            sensitivity: lerp(self.sensitivity, other.sensitivity),
+        }
+    }
+}
+
+/// How the third-person camera trails its targets, as half-lives in seconds.
+///
+/// A half-life is the time it takes to close half the remaining gap, whatever the frame rate;
+/// zero snaps straight to the target.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CameraSmoothing {
+    /// How closely the focus follows the player.
+    pub position_half_life: f32,
+    /// How closely the view turns towards where it is being pointed.
+    pub rotation_half_life: f32,
+    /// How closely the camera follows the boom as it zooms, blends and pulls in.
+    pub distance_half_life: f32,
+    /// How far ahead of the player the focus is aimed, in seconds of the player's velocity.
+    pub look_ahead: f32,
+}
+
+impl Default for CameraSmoothing {
+    fn default() -> Self {
+        Self {
+            position_half_life: 0.1,
+            rotation_half_life: 0.03,
+            distance_half_life: 0.05,
+            look_ahead: 0.3,
+        }
+    }
+}
+
+impl CameraSmoothing {
+    /// The fraction of the remaining gap to close over `dt` seconds with `half_life`.
+    pub fn blend(half_life: f32, dt: f32) -> f32 {
+        if half_life <= 0.0 {
+            return 1.0;
+        }
+        1.0 - 0.5f32.powf(dt / half_life)
+    }
+}
+
+/// The camera profiles used by the rig.
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraProfiles {
+    /// The profile while moving around freely.
+    pub explore: CameraProfile,
+    /// The over-the-shoulder profile while aiming.
+    pub aim: CameraProfile,
+    /// How quickly aim and shoulder transitions blend in; higher is faster.
+    pub blend_rate: f32,
+    /// How the camera trails the player; missing from older config files.
+    #[serde(default)]
+    pub smoothing: CameraSmoothing,
+}
+
+impl Default for CameraProfiles {
+    fn default() -> Self {
+        Self {
+            explore: CameraProfile {
+                distance: 5.0,
+                shoulder_offset: Vec3::ZERO,
+                fov: 70.0,
+                sensitivity: 1.0,
+            },
+            aim: CameraProfile {
+                distance: 2.0,
+                shoulder_offset: Vec3::new(0.6, 0.4, 0.0),
+                fov: 50.0,
+                sensitivity: 0.5,
+            },
+            blend_rate: 10.0,
+            smoothing: CameraSmoothing::default(),
+        }
+    }
+}
+
+impl CameraProfiles {
+    /// Loads profiles from a config file.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        config::load(path)
+    }
+
+    /// Saves profiles to a config file, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> Result<(), ConfigError> {
+        config::save(self, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blend_is_independent_of_frame_splits() {
+        let half_life = 0.1;
+        // Closing the gap over one 1/30 s frame...
+        let remaining_one_frame = 1.0 - CameraSmoothing::blend(half_life, 1.0 / 30.0);
+        // ...leaves the same gap as over four 1/120 s frames.
+        let remaining_four_frames = (1.0 - CameraSmoothing::blend(half_life, 1.0 / 120.0)).powi(4);
+        assert!((remaining_one_frame - remaining_four_frames).abs() < 1e-6);
+
+        assert_eq!(CameraSmoothing::blend(half_life, half_life), 0.5);
+        assert_eq!(CameraSmoothing::blend(0.0, 1.0 / 60.0), 1.0);
+    }
+}
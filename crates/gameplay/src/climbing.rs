@@ -0,0 +1,144 @@
+//! Climbable surfaces (ladders, climbable voxels) and the velocities used while climbing.
+use crate::movement::JUMP_SPEED;
+use crate::player::{Player, PLAYER_HALF_HEIGHT, PLAYER_RADIUS};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use world::{MaterialRegistry, WorldData};
+
+/// Vertical speed while climbing, in m/s.
+pub const CLIMB_SPEED: f32 = 3.0;
+/// Horizontal speed when jumping off a climbable surface.
+pub const CLIMB_JUMP_OFF_SPEED: f32 = 5.0;
+/// How far in front of the player's collider climbable voxels are detected.
+const CLIMB_PROBE_DISTANCE: f32 = 0.3;
+/// Forward speed when climbing over the top of a surface.
+const TOP_OUT_FORWARD_SPEED: f32 = 2.0;
+/// Upward speed when climbing over the top of a surface.
+const TOP_OUT_UP_SPEED: f32 = 3.0;
+
+/// A marker component for trigger volumes (sensor colliders) the player can climb, like ladders.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct Climbable;
+
+/// The climbable surface the player is currently touching, refreshed every tick.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq)]
+pub struct ClimbContact {
+    /// The horizontal direction pointing away from the surface, if touching one.
+    pub normal: Option<Vec3>,
+}
+
+/// The velocity while attached to a surface, with forward/back input mapped to up/down.
+pub fn climb_velocity(move_direction: Vec2) -> Vec3 {
+    Vec3::Y * move_direction.y * CLIMB_SPEED
+}
+
+/// The velocity when jumping off a surface, pushing away from it and towards `sideways`.
+pub fn jump_off_velocity(normal: Vec3, sideways: Vec3) -> Vec3 {
+    (normal + sideways).normalize_or_zero() * CLIMB_JUMP_OFF_SPEED + Vec3::Y * JUMP_SPEED * 0.5
+}
+
+/// The velocity when climbing past the top of a surface, carrying the player onto the ledge.
+pub fn top_out_velocity(facing: Vec3) -> Vec3 {
+    facing * TOP_OUT_FORWARD_SPEED + Vec3::Y * TOP_OUT_UP_SPEED
+}
+
+/// Snaps a horizontal direction to the nearest voxel face axis.
+fn snap_to_axis(direction: Vec3) -> Vec3 {
+    if direction.x.abs() > direction.z.abs() {
+        Vec3::X * direction.x.signum()
+    } else {
+        Vec3::Z * direction.z.signum()
+    }
+}
+
+/// Looks for a climbable voxel directly in front of the player.
+fn climbable_voxel_normal(
+    world: Option<&WorldData>,
+    materials: Option<&MaterialRegistry>,
+    position: Vec3,
+    facing: Vec3,
+) -> Option<Vec3> {
+    let (Some(world), Some(materials)) = (world, materials) else {
+        return None;
+    };
+    let reach = facing * (PLAYER_RADIUS + CLIMB_PROBE_DISTANCE);
+    let probes = [position, position - Vec3::Y * PLAYER_HALF_HEIGHT];
+    probes
+        .into_iter()
+        .filter_map(|probe| world.material_at(probe + reach))
+        .filter_map(|id| materials.get(id))
+        .any(|material| material.is_climbable)
+        .then(|| -snap_to_axis(facing))
+}
+
+/// Refreshes the player's `ClimbContact` from `Climbable` volumes and climbable voxels.
+pub(crate) fn detect_climbable(
+    mut player_query: Query<(Entity, &Transform, &Collider, &mut ClimbContact), With<Player>>,
+    climbables: Query<&GlobalTransform, With<Climbable>>,
+    rapier_context: Res<RapierContext>,
+    world_data: Option<Res<WorldData>>,
+    materials: Option<Res<MaterialRegistry>>,
+) {
+    for (entity, transform, collider, mut contact) in player_query.iter_mut() {
+        let mut volume = None;
+        rapier_context.intersections_with_shape(
+            transform.translation,
+            transform.rotation,
+            collider,
+            QueryFilter::new()
+                .exclude_collider(entity)
+                .predicate(&|candidate| climbables.contains(candidate)),
+            |hit| {
+                volume = Some(hit);
+                false
+            },
+        );
+
+        let facing = transform.rotation * Vec3::Z;
+        let normal = match volume.and_then(|volume| climbables.get(volume).ok()) {
+            Some(volume_transform) => {
+                let away = transform.translation - volume_transform.translation();
+                let away = Vec3::new(away.x, 0.0, away.z).normalize_or_zero();
+                Some(if away == Vec3::ZERO { -facing } else { away })
+            }
+            None => climbable_voxel_normal(
+                world_data.as_deref(),
+                materials.as_deref(),
+                transform.translation,
+                facing,
+            ),
+        };
+
+        contact.normal = normal;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_climbable_voxel_in_front_is_detected() {
+        let mut world = WorldData::default();
+        let materials = MaterialRegistry::default();
+        world.set_voxel(IVec3::new(0, 1, 1), world::Voxel(world::MaterialId::LADDER));
+
+        let position = Vec3::new(0.5, 1.5, 0.5);
+        let normal = climbable_voxel_normal(Some(&world), Some(&materials), position, Vec3::Z);
+        assert_eq!(normal, Some(Vec3::NEG_Z));
+
+        let facing_away = climbable_voxel_normal(Some(&world), Some(&materials), position, Vec3::X);
+        assert_eq!(facing_away, None);
+    }
+
+    #[test]
+    fn test_jump_off_pushes_away_and_sideways() {
+        let velocity = jump_off_velocity(Vec3::NEG_Z, Vec3::X);
+        assert!(velocity.z < 0.0);
+        assert!(velocity.x > 0.0);
+        assert!(velocity.y > 0.0);
+        assert!(
+            (Vec3::new(velocity.x, 0.0, velocity.z).length() - CLIMB_JUMP_OFF_SPEED).abs() < 1e-5
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! Health, damage and death for players and other damageable entities.
+use bevy::prelude::*;
+
+const DEFAULT_MAX_HEALTH: f32 = 100.0;
+
+/// Where a piece of damage came from, so UI and audio can react differently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DamageSource {
+    Fall,
+    Drowning,
+    Melee,
+    Bullet,
+    Explosion,
+    Trap,
+    Other,
+}
+
+/// An event requesting that damage be applied to an entity's `Health`.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct DamageEvent {
+    pub target: Entity,
+    pub amount: f32,
+    pub source: DamageSource,
+}
+
+/// An event triggered when an entity's health reaches zero.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Died {
+    pub entity: Entity,
+    pub source: DamageSource,
+}
+
+/// The health of an entity.
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_HEALTH)
+    }
+}
+
+impl Health {
+    /// Creates a component at full health.
+    pub fn new(max: f32) -> Self {
+        Self { current: max, max }
+    }
+
+    /// Whether health has reached zero.
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.0
+    }
+
+    /// Reduces health by `amount`, clamping at zero. Returns the damage actually dealt.
+    pub fn apply_damage(&mut self, amount: f32) -> f32 {
+        let dealt = amount.max(0.0).min(self.current);
+        self.current -= dealt;
+        dealt
+    }
+
+    /// Restores health by `amount`, clamping at max. Dead entities cannot be healed.
+    pub fn heal(&mut self, amount: f32) {
+        if !self.is_dead() {
+            self.current = (self.current + amount.max(0.0)).min(self.max);
+        }
+    }
+}
+
+/// Applies queued `DamageEvent`s and reports deaths.
+fn apply_damage(
+    mut damage_events: EventReader<DamageEvent>,
+    mut health_query: Query<&mut Health>,
+    mut died_events: EventWriter<Died>,
+) {
+    for event in damage_events.read() {
+        let Ok(mut health) = health_query.get_mut(event.target) else {
+            continue;
+        };
+        if health.is_dead() {
+            continue;
+        }
+        health.apply_damage(event.amount);
+        if health.is_dead() {
+            died_events.send(Died {
+                entity: event.target,
+                source: event.source,
+            });
+        }
+    }
+}
+
+pub struct HealthPlugin;
+
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DamageEvent>()
+            .add_event::<Died>()
+            .add_systems(Update, apply_damage);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_damage_clamps_at_zero() {
+        let mut health = Health::new(50.0);
+        assert_eq!(health.apply_damage(30.0), 30.0);
+        assert_eq!(health.apply_damage(30.0), 20.0);
+        assert!(health.is_dead());
+
+        health.heal(10.0);
+        assert_eq!(health.current, 0.0, "dead entities stay dead");
+    }
+
+    #[test]
+    fn test_damage_event_kills_and_reports() {
+        let mut app = App::new();
+        app.add_plugins(HealthPlugin);
+        let entity = app.world.spawn(Health::new(10.0)).id();
+
+        app.world.send_event(DamageEvent {
+            target: entity,
+            amount: 25.0,
+            source: DamageSource::Fall,
+        });
+        app.update();
+
+        assert!(app.world.get::<Health>(entity).unwrap().is_dead());
+        let died = app.world.resource::<Events<Died>>();
+        let mut reader = died.get_reader();
+        assert_eq!(
+            reader.read(died).copied().collect::<Vec<_>>(),
+            vec![Died {
+                entity,
+                source: DamageSource::Fall
+            }]
+        );
+    }
+}
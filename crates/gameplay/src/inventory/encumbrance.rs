@@ -0,0 +1,141 @@
+//! How the weight a character carries slows them down.
+//!
+//! Carried weight falls into tiers by how much of the character's carry capacity it uses. Each
+//! tier past the first lowers movement speed and makes strenuous movement drain stamina faster.
+use super::items::ItemRegistry;
+use super::Inventory;
+use bevy::prelude::*;
+
+/// How much the player can carry before being overloaded, in kilograms.
+pub const DEFAULT_CARRY_CAPACITY: f32 = 30.0;
+/// The fraction of carry capacity up to which a load is light.
+const LIGHT_LOAD: f32 = 0.5;
+/// The fraction of carry capacity up to which a load only burdens.
+const BURDENED_LOAD: f32 = 0.8;
+
+/// How weighed down a character is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum EncumbranceTier {
+    #[default]
+    Light,
+    Burdened,
+    Heavy,
+    /// Carrying more than the carry capacity.
+    Overloaded,
+}
+
+impl EncumbranceTier {
+    /// The tier for carrying `weight` kilograms with a carry capacity of `capacity`.
+    pub fn for_load(weight: f32, capacity: f32) -> Self {
+        let load = if capacity > 0.0 {
+            weight / capacity
+        } else if weight > 0.0 {
+            f32::INFINITY
+        } else {
+            0.0
+        };
+        if load <= LIGHT_LOAD {
+            EncumbranceTier::Light
+        } else if load <= BURDENED_LOAD {
+            EncumbranceTier::Burdened
+        } else if load <= 1.0 {
+            EncumbranceTier::Heavy
+        } else {
+            EncumbranceTier::Overloaded
+        }
+    }
+
+    /// The fraction of normal movement speed left at this tier.
+    pub fn speed_multiplier(self) -> f32 {
+        match self {
+            EncumbranceTier::Light => 1.0,
+            EncumbranceTier::Burdened => 0.9,
+            EncumbranceTier::Heavy => 0.75,
+            EncumbranceTier::Overloaded => 0.5,
+        }
+    }
+
+    /// How many times faster than normal strenuous movement drains stamina at this tier.
+    pub fn stamina_drain_multiplier(self) -> f32 {
+        match self {
+            EncumbranceTier::Light => 1.0,
+            EncumbranceTier::Burdened => 1.25,
+            EncumbranceTier::Heavy => 1.5,
+            EncumbranceTier::Overloaded => 2.0,
+        }
+    }
+}
+
+/// The weight a character's inventory adds up to, and how much it slows them down.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Encumbrance {
+    /// How much can be carried before being overloaded, in kilograms.
+    pub capacity: f32,
+    /// The weight of everything in the inventory, in kilograms.
+    pub weight: f32,
+    pub tier: EncumbranceTier,
+}
+
+impl Default for Encumbrance {
+    fn default() -> Self {
+        Self::new(DEFAULT_CARRY_CAPACITY)
+    }
+}
+
+impl Encumbrance {
+    /// An unladen character with a carry capacity of `capacity` kilograms.
+    pub fn new(capacity: f32) -> Self {
+        Self {
+            capacity,
+            weight: 0.0,
+            tier: EncumbranceTier::Light,
+        }
+    }
+}
+
+/// Weighs every inventory and updates how encumbered its owner is.
+pub(super) fn update_encumbrance(
+    mut query: Query<(&Inventory, &mut Encumbrance)>,
+    registry: Res<ItemRegistry>,
+) {
+    for (inventory, mut encumbrance) in query.iter_mut() {
+        let weight = inventory.weight(&registry);
+        let capacity = encumbrance.capacity;
+        encumbrance.set_if_neq(Encumbrance {
+            capacity,
+            weight,
+            tier: EncumbranceTier::for_load(weight, capacity),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tiers_follow_the_share_of_carry_capacity() {
+        let tier = |weight| EncumbranceTier::for_load(weight, 30.0);
+        assert_eq!(tier(0.0), EncumbranceTier::Light);
+        assert_eq!(tier(15.0), EncumbranceTier::Light);
+        assert_eq!(tier(15.1), EncumbranceTier::Burdened);
+        assert_eq!(tier(30.0), EncumbranceTier::Heavy);
+        assert_eq!(tier(30.1), EncumbranceTier::Overloaded);
+        assert_eq!(
+            EncumbranceTier::for_load(1.0, 0.0),
+            EncumbranceTier::Overloaded
+        );
+
+        // Every tier is slower and more tiring than the last.
+        let tiers = [
+            EncumbranceTier::Light,
+            EncumbranceTier::Burdened,
+            EncumbranceTier::Heavy,
+            EncumbranceTier::Overloaded,
+        ];
+        for pair in tiers.windows(2) {
+            assert!(pair[1].speed_multiplier() < pair[0].speed_multiplier());
+            assert!(pair[1].stamina_drain_multiplier() > pair[0].stamina_drain_multiplier());
+        }
+    }
+}
@@ -0,0 +1,272 @@
+//! Grid layouts, for inventories where items take up space.
+//!
+//! In a grid inventory every stack sits at a cell and covers its item's footprint, which can be
+//! turned a quarter turn. Stacks can't overlap or hang off the edge of the grid. New stacks go in
+//! the first place they fit, scanning rows from the top left and trying each item upright before
+//! turning it.
+use super::stack::ItemStack;
+use serde::{Deserialize, Serialize};
+
+/// How many grid cells an item covers, upright.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ItemSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Default for ItemSize {
+    fn default() -> Self {
+        Self::ONE
+    }
+}
+
+impl ItemSize {
+    /// A single cell.
+    pub const ONE: Self = Self::new(1, 1);
+
+    pub const fn new(width: u32, height: u32) -> Self {
+        Self { width, height }
+    }
+
+    /// The size turned a quarter turn.
+    pub fn rotated(self) -> Self {
+        Self::new(self.height, self.width)
+    }
+
+    /// Whether the item covers the same cells however it's turned.
+    pub fn is_square(self) -> bool {
+        self.width == self.height
+    }
+}
+
+/// Where a stack sits in a grid: the top-left cell it covers, and whether it's turned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct GridPlacement {
+    pub x: u32,
+    pub y: u32,
+    pub rotated: bool,
+}
+
+impl GridPlacement {
+    /// An upright placement at a cell.
+    pub fn at(x: u32, y: u32) -> Self {
+        Self {
+            x,
+            y,
+            rotated: false,
+        }
+    }
+
+    /// The same placement, turned a quarter turn.
+    pub fn turned(self) -> Self {
+        Self {
+            rotated: !self.rotated,
+            ..self
+        }
+    }
+
+    /// The cells an item of `size` covers when placed here.
+    fn area(self, size: ItemSize) -> Area {
+        let size = if self.rotated { size.rotated() } else { size };
+        Area {
+            x: self.x,
+            y: self.y,
+            width: size.width,
+            height: size.height,
+        }
+    }
+}
+
+/// A rectangle of cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Area {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl Area {
+    fn overlaps(&self, other: &Area) -> bool {
+        self.x < other.x + other.width
+            && other.x < self.x + self.width
+            && self.y < other.y + other.height
+            && other.y < self.y + self.height
+    }
+}
+
+/// The layout of a grid inventory: where each of its slots' stacks sits.
+///
+/// A slot's placement only counts while the slot holds a stack, so emptying a slot frees its
+/// cells without touching the layout.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InventoryGrid {
+    pub width: u32,
+    pub height: u32,
+    placements: Vec<Option<(GridPlacement, ItemSize)>>,
+}
+
+impl InventoryGrid {
+    /// An empty grid, with one slot per cell so it can hold a stack in every cell.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            placements: vec![None; (width * height) as usize],
+        }
+    }
+
+    /// How many slots a grid of this size needs.
+    pub fn slot_count(&self) -> usize {
+        self.placements.len()
+    }
+
+    /// Where a slot's stack sits, if the slot holds one.
+    pub fn placement(&self, slots: &[Option<ItemStack>], slot: usize) -> Option<GridPlacement> {
+        slots.get(slot)?.as_ref()?;
+        self.placements.get(slot)?.map(|(placement, _)| placement)
+    }
+
+    /// Whether an item of `size` fits at `placement`, inside the grid and clear of every stack
+    /// except the one in `ignore`.
+    pub fn fits(
+        &self,
+        slots: &[Option<ItemStack>],
+        size: ItemSize,
+        placement: GridPlacement,
+        ignore: Option<usize>,
+    ) -> bool {
+        let occupied: Vec<_> = self
+            .occupied(slots)
+            .filter(|(slot, _)| Some(*slot) != ignore)
+            .map(|(_, area)| area)
+            .collect();
+        self.fits_among(&occupied, placement.area(size))
+    }
+
+    /// The first place an item of `size` fits, if any.
+    pub fn find_space(&self, slots: &[Option<ItemStack>], size: ItemSize) -> Option<GridPlacement> {
+        let occupied: Vec<_> = self.occupied(slots).map(|(_, area)| area).collect();
+        self.find_space_among(&occupied, size)
+    }
+
+    /// How many more stacks of an item of `size` fit, placing each where `find_space` would.
+    pub fn room_for(&self, slots: &[Option<ItemStack>], size: ItemSize) -> usize {
+        let mut occupied: Vec<_> = self.occupied(slots).map(|(_, area)| area).collect();
+        let empty = slots.iter().filter(|slot| slot.is_none()).count();
+        let mut stacks = 0;
+        while stacks < empty {
+            let Some(placement) = self.find_space_among(&occupied, size) else {
+                break;
+            };
+            occupied.push(placement.area(size));
+            stacks += 1;
+        }
+        stacks
+    }
+
+    /// Records where a slot's stack sits. The caller checks that it fits.
+    pub fn set(&mut self, slot: usize, placement: GridPlacement, size: ItemSize) {
+        if let Some(entry) = self.placements.get_mut(slot) {
+            *entry = Some((placement, size));
+        }
+    }
+
+    /// Swaps the placements of two slots, so their stacks stay where they are.
+    pub fn swap(&mut self, a: usize, b: usize) {
+        self.placements.swap(a, b);
+    }
+
+    /// The cells covered by each slot holding a stack.
+    fn occupied<'a>(
+        &'a self,
+        slots: &'a [Option<ItemStack>],
+    ) -> impl Iterator<Item = (usize, Area)> + 'a {
+        self.placements
+            .iter()
+            .zip(slots)
+            .enumerate()
+            .filter_map(|(slot, (placement, stack))| {
+                stack.as_ref()?;
+                placement.map(|(placement, size)| (slot, placement.area(size)))
+            })
+    }
+
+    fn fits_among(&self, occupied: &[Area], area: Area) -> bool {
+        area.width > 0
+            && area.height > 0
+            && area.x + area.width <= self.width
+            && area.y + area.height <= self.height
+            && !occupied.iter().any(|other| other.overlaps(&area))
+    }
+
+    fn find_space_among(&self, occupied: &[Area], size: ItemSize) -> Option<GridPlacement> {
+        (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| GridPlacement::at(x, y)))
+            .flat_map(|placement| {
+                let turned = (!size.is_square()).then(|| placement.turned());
+                std::iter::once(placement).chain(turned)
+            })
+            .find(|placement| self.fits_among(occupied, placement.area(size)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::ItemId;
+
+    fn filled(
+        grid: &mut InventoryGrid,
+        items: &[(GridPlacement, ItemSize)],
+    ) -> Vec<Option<ItemStack>> {
+        let mut slots = vec![None; grid.slot_count()];
+        for (slot, &(placement, size)) in items.iter().enumerate() {
+            grid.set(slot, placement, size);
+            slots[slot] = Some(ItemStack::new(ItemId(1), 1));
+        }
+        slots
+    }
+
+    #[test]
+    fn test_items_fit_inside_the_grid_and_clear_of_others() {
+        let mut grid = InventoryGrid::new(4, 3);
+        let long = ItemSize::new(3, 1);
+        let slots = filled(&mut grid, &[(GridPlacement::at(0, 0), long)]);
+
+        assert!(grid.fits(&slots, long, GridPlacement::at(1, 1), None));
+        assert!(!grid.fits(&slots, long, GridPlacement::at(2, 1), None));
+        assert!(grid.fits(&slots, long, GridPlacement::at(3, 0).turned(), None));
+        assert!(!grid.fits(&slots, long, GridPlacement::at(2, 0).turned(), None));
+        // A stack doesn't get in its own way when it's moved.
+        assert!(grid.fits(&slots, long, GridPlacement::at(2, 0).turned(), Some(0)));
+        assert!(!grid.fits(&slots, long, GridPlacement::at(0, 1).turned(), None));
+        assert!(!grid.fits(&slots, ItemSize::new(0, 1), GridPlacement::at(3, 2), None));
+
+        // An emptied slot's cells are free again.
+        let mut slots = slots;
+        slots[0] = None;
+        assert!(grid.fits(&slots, long, GridPlacement::at(0, 0), None));
+    }
+
+    #[test]
+    fn test_space_is_found_row_by_row_turning_items_when_needed() {
+        let mut grid = InventoryGrid::new(3, 3);
+        let slots = filled(
+            &mut grid,
+            &[
+                (GridPlacement::at(0, 0), ItemSize::new(2, 2)),
+                (GridPlacement::at(0, 2), ItemSize::new(2, 1)),
+            ],
+        );
+        // Only the right-hand column is left, so a 2x1 item has to stand on its end.
+        assert_eq!(
+            grid.find_space(&slots, ItemSize::new(2, 1)),
+            Some(GridPlacement::at(2, 0).turned())
+        );
+        assert_eq!(grid.find_space(&slots, ItemSize::new(2, 2)), None);
+        assert_eq!(grid.room_for(&slots, ItemSize::new(1, 2)), 1);
+        assert_eq!(grid.room_for(&slots, ItemSize::ONE), 3);
+        assert_eq!(grid.find_space(&slots, ItemSize::new(4, 1)), None);
+    }
+}
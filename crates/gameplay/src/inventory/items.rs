@@ -0,0 +1,477 @@
+//! The item database: what every `ItemId` stands for.
+//!
+//! Definitions are loaded from `config/items.ron` and checked as they load, so a file with
+//! clashing IDs or keys, or a weapon firing ammo that doesn't exist, is rejected as a whole.
+//! Items are looked up by their numeric ID at runtime and by their string key from data files.
+use super::grid::ItemSize;
+use super::ItemId;
+use crate::building::MaterialId;
+use bevy::prelude::*;
+use common::config::{self, ConfigError};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+/// Where the item database is loaded from by default.
+pub const DEFAULT_ITEMS_PATH: &str = "config/items.ron";
+
+/// What sort of thing an item is, for sorting and filtering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ItemCategory {
+    Weapon,
+    Ammo,
+    Consumable,
+    Material,
+    Quest,
+    Misc,
+}
+
+/// How rare an item is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Rarity {
+    Common,
+    Uncommon,
+    Rare,
+    Epic,
+    Legendary,
+}
+
+/// Something an item can do. An item can have any number of behaviours.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ItemBehaviour {
+    /// Used up to restore health and stamina.
+    Consumable { health: f32, stamina: f32 },
+    /// Fired or swung for damage, using up the item keyed `ammo` if it has one.
+    Weapon {
+        damage: f32,
+        fire_rate: f32,
+        #[serde(default)]
+        ammo: Option<String>,
+    },
+    /// Loaded into weapons.
+    Ammo { damage_multiplier: f32 },
+    /// Paid to build with a building material.
+    BuildingMaterial { material: MaterialId },
+    /// Needed by a quest, and never dropped or sold.
+    QuestItem { quest: String },
+}
+
+/// Everything about a kind of item.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ItemDefinition {
+    pub id: ItemId,
+    /// The name data files refer to the item by, like `"ammo_9mm"`.
+    pub key: String,
+    /// The name shown to players.
+    pub name: String,
+    pub category: ItemCategory,
+    /// How many of the item fit in one inventory slot.
+    pub stack_size: u32,
+    /// The weight of one item, in kilograms.
+    pub weight: f32,
+    /// The cells a stack of the item covers in a grid inventory.
+    #[serde(default)]
+    pub size: ItemSize,
+    pub rarity: Rarity,
+    /// The item's icon, relative to the assets directory.
+    pub icon: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub behaviours: Vec<ItemBehaviour>,
+}
+
+impl ItemDefinition {
+    /// Whether the item is tagged with `tag`.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// The key of the ammo the item fires, if it's a weapon that takes ammo.
+    pub fn ammo(&self) -> Option<&str> {
+        self.behaviours
+            .iter()
+            .find_map(|behaviour| match behaviour {
+                ItemBehaviour::Weapon {
+                    ammo: Some(ammo), ..
+                } => Some(ammo.as_str()),
+                _ => None,
+            })
+    }
+
+    /// The building material the item pays for, if it's one.
+    pub fn building_material(&self) -> Option<MaterialId> {
+        self.behaviours
+            .iter()
+            .find_map(|behaviour| match behaviour {
+                ItemBehaviour::BuildingMaterial { material } => Some(*material),
+                _ => None,
+            })
+    }
+
+    /// Whether the item can be loaded into weapons.
+    pub fn is_ammo(&self) -> bool {
+        self.behaviours
+            .iter()
+            .any(|behaviour| matches!(behaviour, ItemBehaviour::Ammo { .. }))
+    }
+}
+
+/// Why a set of item definitions was rejected.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ItemRegistryError {
+    /// Two items share an ID.
+    DuplicateId(ItemId),
+    /// Two items share a key.
+    DuplicateKey(String),
+    /// An item can't stack even once.
+    ZeroStackSize(String),
+    /// An item covers no grid cells.
+    ZeroSize(String),
+    /// An item refers to a key no item has.
+    DanglingReference { item: String, reference: String },
+    /// A weapon's ammo refers to an item that isn't ammo.
+    NotAmmo { item: String, reference: String },
+}
+
+impl fmt::Display for ItemRegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ItemRegistryError::DuplicateId(id) => write!(f, "item ID {} is used twice", id.0),
+            ItemRegistryError::DuplicateKey(key) => write!(f, "item key {key:?} is used twice"),
+            ItemRegistryError::ZeroStackSize(key) => {
+                write!(f, "item {key:?} has a stack size of zero")
+            }
+            ItemRegistryError::ZeroSize(key) => write!(f, "item {key:?} covers no grid cells"),
+            ItemRegistryError::DanglingReference { item, reference } => {
+                write!(f, "item {item:?} refers to unknown item {reference:?}")
+            }
+            ItemRegistryError::NotAmmo { item, reference } => {
+                write!(f, "item {item:?} fires {reference:?}, which isn't ammo")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ItemRegistryError {}
+
+/// The definitions of every item, as written in data files.
+#[derive(Serialize, Deserialize)]
+struct ItemList {
+    items: Vec<ItemDefinition>,
+}
+
+/// A resource holding the definition of every item, checked for consistency.
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "ItemList", into = "ItemList")]
+pub struct ItemRegistry {
+    items: Vec<ItemDefinition>,
+    by_id: HashMap<ItemId, usize>,
+    by_key: HashMap<String, usize>,
+}
+
+impl TryFrom<ItemList> for ItemRegistry {
+    type Error = ItemRegistryError;
+
+    fn try_from(list: ItemList) -> Result<Self, Self::Error> {
+        Self::new(list.items)
+    }
+}
+
+impl From<ItemRegistry> for ItemList {
+    fn from(registry: ItemRegistry) -> Self {
+        Self {
+            items: registry.items,
+        }
+    }
+}
+
+impl Default for ItemRegistry {
+    fn default() -> Self {
+        Self::new(built_in_items()).expect("built-in items are consistent")
+    }
+}
+
+impl ItemRegistry {
+    /// Builds a registry from definitions, rejecting them if they're inconsistent.
+    pub fn new(items: Vec<ItemDefinition>) -> Result<Self, ItemRegistryError> {
+        let mut by_id = HashMap::new();
+        let mut by_key = HashMap::new();
+        for (index, item) in items.iter().enumerate() {
+            if by_id.insert(item.id, index).is_some() {
+                return Err(ItemRegistryError::DuplicateId(item.id));
+            }
+            if by_key.insert(item.key.clone(), index).is_some() {
+                return Err(ItemRegistryError::DuplicateKey(item.key.clone()));
+            }
+            if item.stack_size == 0 {
+                return Err(ItemRegistryError::ZeroStackSize(item.key.clone()));
+            }
+            if item.size.width == 0 || item.size.height == 0 {
+                return Err(ItemRegistryError::ZeroSize(item.key.clone()));
+            }
+        }
+
+        for item in &items {
+            let Some(ammo) = item.ammo() else {
+                continue;
+            };
+            match by_key.get(ammo).map(|index| &items[*index]) {
+                None => {
+                    return Err(ItemRegistryError::DanglingReference {
+                        item: item.key.clone(),
+                        reference: ammo.to_string(),
+                    })
+                }
+                Some(reference) if !reference.is_ammo() => {
+                    return Err(ItemRegistryError::NotAmmo {
+                        item: item.key.clone(),
+                        reference: ammo.to_string(),
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(Self {
+            items,
+            by_id,
+            by_key,
+        })
+    }
+
+    /// Loads the registry from a data file, checking it.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        config::load(path)
+    }
+
+    /// Saves the registry to a data file, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> Result<(), ConfigError> {
+        config::save(self, path)
+    }
+
+    /// The definition of the item with an ID.
+    pub fn get(&self, id: ItemId) -> Option<&ItemDefinition> {
+        self.by_id.get(&id).map(|index| &self.items[*index])
+    }
+
+    /// The definition of the item with a key.
+    pub fn get_by_key(&self, key: &str) -> Option<&ItemDefinition> {
+        self.by_key.get(key).map(|index| &self.items[*index])
+    }
+
+    /// The ID of the item with a key.
+    pub fn id(&self, key: &str) -> Option<ItemId> {
+        self.get_by_key(key).map(|item| item.id)
+    }
+
+    /// Every item, in the order they were defined.
+    pub fn iter(&self) -> impl Iterator<Item = &ItemDefinition> {
+        self.items.iter()
+    }
+}
+
+fn item(
+    id: u32,
+    key: &str,
+    name: &str,
+    category: ItemCategory,
+    stack_size: u32,
+    weight: f32,
+    rarity: Rarity,
+) -> ItemDefinition {
+    ItemDefinition {
+        id: ItemId(id),
+        key: key.to_string(),
+        name: name.to_string(),
+        category,
+        stack_size,
+        weight,
+        size: ItemSize::ONE,
+        rarity,
+        icon: format!("icons/items/{key}.png"),
+        tags: Vec::new(),
+        behaviours: Vec::new(),
+    }
+}
+
+/// The items the game ships with. Building materials keep the IDs the material registry uses.
+fn built_in_items() -> Vec<ItemDefinition> {
+    use ItemCategory::*;
+    let material = |id, key, name, weight, rarity, material| ItemDefinition {
+        tags: vec!["building".to_string()],
+        behaviours: vec![ItemBehaviour::BuildingMaterial { material }],
+        ..item(id, key, name, Material, 50, weight, rarity)
+    };
+    vec![
+        material(1, "wood", "Wood", 0.5, Rarity::Common, MaterialId::WOOD),
+        material(
+            2,
+            "metal",
+            "Scrap Metal",
+            1.0,
+            Rarity::Uncommon,
+            MaterialId::METAL,
+        ),
+        material(
+            3,
+            "reinforced_plate",
+            "Reinforced Plate",
+            2.0,
+            Rarity::Rare,
+            MaterialId::REINFORCED,
+        ),
+        ItemDefinition {
+            behaviours: vec![ItemBehaviour::Ammo {
+                damage_multiplier: 1.0,
+            }],
+            ..item(10, "ammo_9mm", "9mm Rounds", Ammo, 60, 0.01, Rarity::Common)
+        },
+        ItemDefinition {
+            tags: vec!["sidearm".to_string()],
+            behaviours: vec![ItemBehaviour::Weapon {
+                damage: 25.0,
+                fire_rate: 4.0,
+                ammo: Some("ammo_9mm".to_string()),
+            }],
+            size: ItemSize::new(2, 1),
+            ..item(20, "pistol", "Pistol", Weapon, 1, 1.2, Rarity::Common)
+        },
+        ItemDefinition {
+            tags: vec!["melee".to_string()],
+            behaviours: vec![ItemBehaviour::Weapon {
+                damage: 35.0,
+                fire_rate: 1.5,
+                ammo: None,
+            }],
+            size: ItemSize::new(3, 1),
+            ..item(21, "machete", "Machete", Weapon, 1, 0.8, Rarity::Common)
+        },
+        ItemDefinition {
+            tags: vec!["medical".to_string()],
+            behaviours: vec![ItemBehaviour::Consumable {
+                health: 25.0,
+                stamina: 0.0,
+            }],
+            ..item(30, "bandage", "Bandage", Consumable, 5, 0.1, Rarity::Common)
+        },
+        ItemDefinition {
+            behaviours: vec![ItemBehaviour::Consumable {
+                health: 0.0,
+                stamina: 50.0,
+            }],
+            ..item(
+                31,
+                "energy_drink",
+                "Energy Drink",
+                Consumable,
+                3,
+                0.3,
+                Rarity::Uncommon,
+            )
+        },
+        ItemDefinition {
+            behaviours: vec![ItemBehaviour::QuestItem {
+                quest: "first_extraction".to_string(),
+            }],
+            ..item(
+                40,
+                "data_drive",
+                "Encrypted Data Drive",
+                Quest,
+                1,
+                0.1,
+                Rarity::Epic,
+            )
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookups_by_id_and_key_agree() {
+        let registry = ItemRegistry::default();
+        let pistol = registry.get_by_key("pistol").unwrap();
+        assert_eq!(registry.get(pistol.id), Some(pistol));
+        assert_eq!(registry.id("wood"), Some(ItemId(1)));
+        assert_eq!(
+            registry.get(ItemId(1)).unwrap().building_material(),
+            Some(MaterialId::WOOD)
+        );
+        assert!(registry
+            .get_by_key(pistol.ammo().unwrap())
+            .unwrap()
+            .is_ammo());
+        assert!(registry.get(ItemId(999)).is_none());
+    }
+
+    #[test]
+    fn test_inconsistent_items_are_rejected() {
+        let items = built_in_items();
+        let mut duplicate_id = items.clone();
+        duplicate_id[1].id = duplicate_id[0].id;
+        assert_eq!(
+            ItemRegistry::new(duplicate_id),
+            Err(ItemRegistryError::DuplicateId(ItemId(1)))
+        );
+
+        let mut duplicate_key = items.clone();
+        duplicate_key[1].key = "wood".to_string();
+        assert_eq!(
+            ItemRegistry::new(duplicate_key),
+            Err(ItemRegistryError::DuplicateKey("wood".to_string()))
+        );
+
+        let mut flat = items.clone();
+        flat[0].size = ItemSize::new(2, 0);
+        assert_eq!(
+            ItemRegistry::new(flat),
+            Err(ItemRegistryError::ZeroSize("wood".to_string()))
+        );
+
+        let without_ammo: Vec<_> = items
+            .iter()
+            .filter(|item| item.key != "ammo_9mm")
+            .cloned()
+            .collect();
+        assert_eq!(
+            ItemRegistry::new(without_ammo),
+            Err(ItemRegistryError::DanglingReference {
+                item: "pistol".to_string(),
+                reference: "ammo_9mm".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_items_load_from_ron() {
+        let registry = ItemRegistry::default();
+        let source = config::to_ron(&registry).unwrap();
+        let loaded: ItemRegistry = config::from_ron(&source).unwrap();
+        assert_eq!(loaded, registry);
+
+        // Checks run while loading, and defaults fill in what's left out.
+        let source = r#"(
+            items: [(
+                id: (7),
+                key: "rifle",
+                name: "Rifle",
+                category: Weapon,
+                stack_size: 1,
+                weight: 3.5,
+                rarity: Rare,
+                icon: "icons/items/rifle.png",
+                behaviours: [Weapon(damage: 40.0, fire_rate: 2.0, ammo: Some("ammo_762"))],
+            )],
+        )"#;
+        assert!(config::from_ron::<ItemRegistry>(source).is_err());
+        let source = source.replace(", ammo: Some(\"ammo_762\")", "");
+        let loaded: ItemRegistry = config::from_ron(&source).unwrap();
+        let rifle = loaded.get_by_key("rifle").unwrap();
+        assert!(rifle.tags.is_empty());
+        assert_eq!(rifle.size, ItemSize::ONE);
+    }
+}
@@ -0,0 +1,338 @@
+//! Moving items between the stash and the mission inventory.
+//!
+//! The stash keeps items between missions and the inventory holds what's carried on one, so the
+//! stash can only be reached between missions. A mission's loadout moves out of the stash when
+//! it starts. When it ends, everything carried goes back if the player extracted; if they died,
+//! only insured items and the secure container's contents do, and the rest is lost.
+use super::items::ItemRegistry;
+use super::stack::{InventoryError, ItemContainer, ItemStack};
+use super::{Inventory, ItemId, Stash};
+use crate::health::Died;
+use crate::player::Player;
+use bevy::prelude::*;
+use std::fmt;
+
+/// Where items are kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ItemLocation {
+    Inventory,
+    Stash,
+}
+
+impl ItemLocation {
+    /// The other place items can be moved to.
+    pub fn other(self) -> Self {
+        match self {
+            ItemLocation::Inventory => ItemLocation::Stash,
+            ItemLocation::Stash => ItemLocation::Inventory,
+        }
+    }
+}
+
+/// How a mission ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissionOutcome {
+    /// The player made it out with everything they carried.
+    Extracted,
+    /// The player died, keeping only insured and secured items.
+    Died,
+}
+
+/// Whether a mission is under way.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct Mission {
+    pub active: bool,
+}
+
+/// An event requesting that `count` items in a slot move between the inventory and the stash.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StashTransfer {
+    pub from: ItemLocation,
+    pub slot: usize,
+    pub count: u32,
+}
+
+/// An event requesting that a mission start, taking `loadout` from the stash.
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct StartMission {
+    pub loadout: Vec<(ItemId, u32)>,
+}
+
+/// An event requesting that the mission end.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EndMission {
+    pub outcome: MissionOutcome,
+}
+
+/// An event sent for every stack moved between the inventory and the stash.
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct ItemsTransferred {
+    pub from: ItemLocation,
+    pub to: ItemLocation,
+    pub stack: ItemStack,
+}
+
+/// An event sent for every stack lost when the player dies on a mission.
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct ItemsLost {
+    pub stack: ItemStack,
+}
+
+/// Why items couldn't be moved.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransferError {
+    /// The stash can't be reached during a mission.
+    MissionUnderway,
+    /// There's no mission to end.
+    NoMission,
+    /// There's no player inventory to move items to or from.
+    NoInventory,
+    Inventory(InventoryError),
+}
+
+impl fmt::Display for TransferError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransferError::MissionUnderway => write!(f, "the stash is out of reach on a mission"),
+            TransferError::NoMission => write!(f, "no mission is under way"),
+            TransferError::NoInventory => write!(f, "there is no inventory"),
+            TransferError::Inventory(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for TransferError {}
+
+impl From<InventoryError> for TransferError {
+    fn from(err: InventoryError) -> Self {
+        TransferError::Inventory(err)
+    }
+}
+
+/// An event sent when requested items couldn't be moved.
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct TransferFailed {
+    pub error: TransferError,
+}
+
+/// Moves `count` items in `slot` from one container to another, returning what was moved.
+fn transfer(
+    from: &mut impl ItemContainer,
+    slot: usize,
+    count: u32,
+    to: &mut impl ItemContainer,
+    registry: &ItemRegistry,
+) -> Result<ItemStack, InventoryError> {
+    let moved = from
+        .slot(slot)?
+        .ok_or(InventoryError::EmptySlot(slot))?
+        .clone();
+    from.transfer(slot, count, to, registry)?;
+    Ok(ItemStack { count, ..moved })
+}
+
+/// Takes `loadout` out of the stash into the inventory, returning the stacks moved. Either all
+/// of it moves or none does.
+fn take_loadout(
+    stash: &mut Stash,
+    inventory: &mut Inventory,
+    loadout: &[(ItemId, u32)],
+    registry: &ItemRegistry,
+) -> Result<Vec<ItemStack>, InventoryError> {
+    let mut stash_after = stash.clone();
+    let mut inventory_after = inventory.clone();
+    let mut moved = Vec::new();
+    for &(id, count) in loadout {
+        let available = stash_after.count(id);
+        if available < count as usize {
+            return Err(InventoryError::NotEnough {
+                id,
+                requested: count as usize,
+                available,
+            });
+        }
+        let mut left = count;
+        for slot in 0..stash_after.items.len() {
+            let Some(held) = stash_after.items[slot]
+                .as_ref()
+                .filter(|held| held.id == id)
+            else {
+                continue;
+            };
+            if left == 0 {
+                break;
+            }
+            let taken = left.min(held.count);
+            moved.push(transfer(
+                &mut stash_after,
+                slot,
+                taken,
+                &mut inventory_after,
+                registry,
+            )?);
+            left -= taken;
+        }
+    }
+    *stash = stash_after;
+    *inventory = inventory_after;
+    Ok(moved)
+}
+
+/// Moves items between the inventory and the stash on request, between missions.
+pub(super) fn transfer_stash_items(
+    mut transfer_events: EventReader<StashTransfer>,
+    mut inventory_query: Query<&mut Inventory, With<Player>>,
+    mut stash: ResMut<Stash>,
+    registry: Res<ItemRegistry>,
+    mission: Res<Mission>,
+    mut transferred_events: EventWriter<ItemsTransferred>,
+    mut failed_events: EventWriter<TransferFailed>,
+) {
+    for event in transfer_events.read() {
+        let result = if mission.active {
+            Err(TransferError::MissionUnderway)
+        } else if let Ok(mut inventory) = inventory_query.get_single_mut() {
+            let stash = &mut *stash;
+            let inventory = &mut *inventory;
+            match event.from {
+                ItemLocation::Inventory => {
+                    transfer(inventory, event.slot, event.count, stash, &registry)
+                }
+                ItemLocation::Stash => {
+                    transfer(stash, event.slot, event.count, inventory, &registry)
+                }
+            }
+            .map_err(TransferError::from)
+        } else {
+            Err(TransferError::NoInventory)
+        };
+        match result {
+            Ok(stack) => {
+                transferred_events.send(ItemsTransferred {
+                    from: event.from,
+                    to: event.from.other(),
+                    stack,
+                });
+            }
+            Err(error) => {
+                failed_events.send(TransferFailed { error });
+            }
+        }
+    }
+}
+
+/// Starts missions, moving their loadout from the stash into the inventory.
+pub(super) fn start_missions(
+    mut start_events: EventReader<StartMission>,
+    mut inventory_query: Query<&mut Inventory, With<Player>>,
+    mut stash: ResMut<Stash>,
+    registry: Res<ItemRegistry>,
+    mut mission: ResMut<Mission>,
+    mut transferred_events: EventWriter<ItemsTransferred>,
+    mut failed_events: EventWriter<TransferFailed>,
+) {
+    for event in start_events.read() {
+        if mission.active {
+            failed_events.send(TransferFailed {
+                error: TransferError::MissionUnderway,
+            });
+            continue;
+        }
+        let Ok(mut inventory) = inventory_query.get_single_mut() else {
+            failed_events.send(TransferFailed {
+                error: TransferError::NoInventory,
+            });
+            continue;
+        };
+        match take_loadout(&mut stash, &mut inventory, &event.loadout, &registry) {
+            Ok(moved) => {
+                mission.active = true;
+                transferred_events.send_batch(moved.into_iter().map(|stack| ItemsTransferred {
+                    from: ItemLocation::Stash,
+                    to: ItemLocation::Inventory,
+                    stack,
+                }));
+            }
+            Err(error) => {
+                failed_events.send(TransferFailed {
+                    error: error.into(),
+                });
+            }
+        }
+    }
+}
+
+/// Ends the mission when the player dies on one.
+pub(super) fn end_mission_on_death(
+    mut died_events: EventReader<Died>,
+    player_query: Query<(), With<Player>>,
+    mission: Res<Mission>,
+    mut end_events: EventWriter<EndMission>,
+) {
+    for event in died_events.read() {
+        if mission.active && player_query.contains(event.entity) {
+            end_events.send(EndMission {
+                outcome: MissionOutcome::Died,
+            });
+        }
+    }
+}
+
+/// Ends missions, returning what the player keeps to the stash and losing the rest.
+///
+/// Items the stash has no room for stay in the inventory.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn end_missions(
+    mut end_events: EventReader<EndMission>,
+    mut inventory_query: Query<&mut Inventory, With<Player>>,
+    mut stash: ResMut<Stash>,
+    registry: Res<ItemRegistry>,
+    mut mission: ResMut<Mission>,
+    mut transferred_events: EventWriter<ItemsTransferred>,
+    mut lost_events: EventWriter<ItemsLost>,
+    mut failed_events: EventWriter<TransferFailed>,
+) {
+    for event in end_events.read() {
+        if !mission.active {
+            failed_events.send(TransferFailed {
+                error: TransferError::NoMission,
+            });
+            continue;
+        }
+        mission.active = false;
+        let Ok(mut inventory) = inventory_query.get_single_mut() else {
+            continue;
+        };
+
+        for slot in 0..inventory.items.len() {
+            let Some(held) = &inventory.items[slot] else {
+                continue;
+            };
+            let kept = match event.outcome {
+                MissionOutcome::Extracted => true,
+                MissionOutcome::Died => held.insured || inventory.is_secure(slot),
+            };
+            if !kept {
+                if let Some(stack) = inventory.items[slot].take() {
+                    lost_events.send(ItemsLost { stack });
+                }
+                continue;
+            }
+            let count = held.count;
+            match transfer(&mut *inventory, slot, count, &mut *stash, &registry) {
+                Ok(stack) => {
+                    transferred_events.send(ItemsTransferred {
+                        from: ItemLocation::Inventory,
+                        to: ItemLocation::Stash,
+                        stack,
+                    });
+                }
+                Err(error) => {
+                    failed_events.send(TransferFailed {
+                        error: error.into(),
+                    });
+                }
+            }
+        }
+    }
+}
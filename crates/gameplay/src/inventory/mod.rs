@@ -0,0 +1,233 @@
+//! Player inventory and stash interfaces.
+use crate::health::Died;
+use bevy::prelude::*;
+use common::config;
+use grid::{GridPlacement, InventoryGrid, ItemSize};
+use items::{ItemRegistry, DEFAULT_ITEMS_PATH};
+use serde::{Deserialize, Serialize};
+use stack::{InventoryError, ItemContainer, ItemStack};
+use std::path::Path;
+
+pub mod encumbrance;
+pub mod grid;
+pub mod items;
+pub mod mission;
+pub mod stack;
+
+const DEFAULT_INVENTORY_SLOTS: usize = 10;
+const DEFAULT_SECURE_SLOTS: usize = 2;
+const DEFAULT_STASH_SLOTS: usize = 100;
+
+/// A component representing a player's mission-specific inventory.
+#[derive(Component, Debug, Clone)]
+pub struct Inventory {
+    pub items: Vec<Option<ItemStack>>,
+    pub capacity: usize,
+    /// How many of the first slots make up the secure container, whose items survive death.
+    pub secure_slots: usize,
+    /// Where each stack sits, if items take up space in this inventory.
+    pub grid: Option<InventoryGrid>,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self {
+            items: vec![None; DEFAULT_INVENTORY_SLOTS],
+            capacity: DEFAULT_INVENTORY_SLOTS,
+            secure_slots: DEFAULT_SECURE_SLOTS,
+            grid: None,
+        }
+    }
+}
+
+impl Inventory {
+    /// An empty inventory where items cover cells of a `width` by `height` grid.
+    pub fn with_grid(width: u32, height: u32) -> Self {
+        let grid = InventoryGrid::new(width, height);
+        let slots = grid.slot_count();
+        Self {
+            items: vec![None; slots],
+            capacity: slots,
+            grid: Some(grid),
+            ..default()
+        }
+    }
+
+    /// Whether a slot is part of the secure container.
+    pub fn is_secure(&self, slot: usize) -> bool {
+        slot < self.secure_slots
+    }
+
+    /// The total weight of everything in the inventory, in kilograms.
+    pub fn weight(&self, registry: &ItemRegistry) -> f32 {
+        self.items
+            .iter()
+            .flatten()
+            .filter_map(|stack| {
+                let item = registry.get(stack.id)?;
+                Some(item.weight * stack.count as f32)
+            })
+            .sum()
+    }
+
+    /// Where a slot's stack sits in a grid inventory.
+    pub fn placement(&self, slot: usize) -> Option<GridPlacement> {
+        self.grid.as_ref()?.placement(&self.items, slot)
+    }
+
+    /// Whether an item of `size` would fit at `placement` in a grid inventory.
+    pub fn fits(&self, size: ItemSize, placement: GridPlacement) -> bool {
+        self.grid
+            .as_ref()
+            .is_some_and(|grid| grid.fits(&self.items, size, placement, None))
+    }
+
+    /// The first place in a grid inventory an item of `size` would go when added.
+    pub fn find_space(&self, size: ItemSize) -> Option<GridPlacement> {
+        self.grid.as_ref()?.find_space(&self.items, size)
+    }
+
+    /// Moves or turns a slot's stack within a grid inventory. Fails with `NoRoom` if it doesn't
+    /// fit there, or if the inventory has no grid.
+    pub fn place(
+        &mut self,
+        slot: usize,
+        placement: GridPlacement,
+        registry: &ItemRegistry,
+    ) -> Result<(), InventoryError> {
+        let held = self.slot(slot)?.ok_or(InventoryError::EmptySlot(slot))?;
+        let size = item_size(registry, held)?;
+        let grid = self.grid.as_mut().ok_or(InventoryError::NoRoom)?;
+        if !grid.fits(&self.items, size, placement, Some(slot)) {
+            return Err(InventoryError::NoRoom);
+        }
+        grid.set(slot, placement, size);
+        Ok(())
+    }
+}
+
+/// The grid cells a stack's item covers.
+fn item_size(registry: &ItemRegistry, stack: &ItemStack) -> Result<ItemSize, InventoryError> {
+    registry
+        .get(stack.id)
+        .map(|item| item.size)
+        .ok_or(InventoryError::UnknownItem(stack.id))
+}
+
+impl ItemContainer for Inventory {
+    fn slots(&self) -> &[Option<ItemStack>] {
+        &self.items
+    }
+
+    fn slots_mut(&mut self) -> &mut [Option<ItemStack>] {
+        &mut self.items
+    }
+
+    fn room_for(
+        &self,
+        stack: &ItemStack,
+        registry: &ItemRegistry,
+    ) -> Result<usize, InventoryError> {
+        let max = stack::stack_size(registry, stack.id)?;
+        let top_up: usize = self
+            .items
+            .iter()
+            .flatten()
+            .filter(|held| held.can_stack_with(stack))
+            .map(|held| max.saturating_sub(held.count) as usize)
+            .sum();
+        let new_stacks = match &self.grid {
+            Some(grid) => grid.room_for(&self.items, item_size(registry, stack)?),
+            None => self.items.iter().filter(|slot| slot.is_none()).count(),
+        };
+        Ok(top_up + new_stacks * max as usize)
+    }
+
+    fn claim_slot(
+        &mut self,
+        stack: &ItemStack,
+        registry: &ItemRegistry,
+    ) -> Result<Option<usize>, InventoryError> {
+        let empty = self.items.iter().position(Option::is_none);
+        let Some(grid) = &mut self.grid else {
+            return Ok(empty);
+        };
+        let size = item_size(registry, stack)?;
+        let (Some(slot), Some(placement)) = (empty, grid.find_space(&self.items, size)) else {
+            return Ok(None);
+        };
+        grid.set(slot, placement, size);
+        Ok(Some(slot))
+    }
+
+    fn swap(&mut self, a: usize, b: usize) -> Result<(), InventoryError> {
+        self.slot(a)?;
+        self.slot(b)?;
+        self.items.swap(a, b);
+        if let Some(grid) = &mut self.grid {
+            grid.swap(a, b);
+        }
+        Ok(())
+    }
+}
+
+/// A resource representing the global stash, accessible across missions.
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Stash {
+    pub items: Vec<Option<ItemStack>>,
+    pub capacity: usize,
+}
+
+impl Default for Stash {
+    fn default() -> Self {
+        Self {
+            items: vec![None; DEFAULT_STASH_SLOTS],
+            capacity: DEFAULT_STASH_SLOTS,
+        }
+    }
+}
+
+impl ItemContainer for Stash {
+    fn slots(&self) -> &[Option<ItemStack>] {
+        &self.items
+    }
+
+    fn slots_mut(&mut self) -> &mut [Option<ItemStack>] {
+        &mut self.items
+    }
+}
+
+/// A unique identifier for an item, defined by its entry in the `ItemRegistry`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ItemId(pub u32);
+
+pub struct InventoryPlugin;
+
+impl Plugin for InventoryPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<ItemRegistry>() {
+            let items: ItemRegistry = config::load_or_default(Path::new(DEFAULT_ITEMS_PATH));
+            app.insert_resource(items);
+        }
+        app.add_event::<mission::StashTransfer>()
+            .add_event::<mission::StartMission>()
+            .add_event::<mission::EndMission>()
+            .add_event::<mission::ItemsTransferred>()
+            .add_event::<mission::ItemsLost>()
+            .add_event::<mission::TransferFailed>()
+            .add_event::<Died>()
+            .init_resource::<Stash>()
+            .init_resource::<mission::Mission>()
+            .add_systems(
+                Update,
+                (
+                    mission::transfer_stash_items,
+                    mission::start_missions,
+                    mission::end_mission_on_death,
+                    mission::end_missions,
+                    encumbrance::update_encumbrance,
+                )
+                    .chain(),
+            );
+    }
+}
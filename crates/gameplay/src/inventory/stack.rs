@@ -0,0 +1,495 @@
+//! Item stacks and the slot operations shared by every item container.
+//!
+//! A slot holds one stack: some number of the same item, plus per-instance data like durability.
+//! Stacks only merge when their instance data matches, and never grow past the stack size in the
+//! item's definition. Every operation either succeeds completely or leaves the container as it
+//! was, so items are never created or lost.
+use super::items::ItemRegistry;
+use super::ItemId;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Some number of one item in a single slot.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ItemStack {
+    pub id: ItemId,
+    pub count: u32,
+    /// How worn the items are, from `1.0` new to `0.0` broken, for items that wear out.
+    #[serde(default)]
+    pub durability: Option<f32>,
+    /// Anything else particular to these items, like weapon attachments.
+    #[serde(default)]
+    pub data: BTreeMap<String, String>,
+    /// Whether the items are insured, so they go back to the stash if the player dies.
+    #[serde(default)]
+    pub insured: bool,
+}
+
+impl ItemStack {
+    /// A stack of `count` plain items.
+    pub fn new(id: ItemId, count: u32) -> Self {
+        Self {
+            id,
+            count,
+            durability: None,
+            data: BTreeMap::new(),
+            insured: false,
+        }
+    }
+
+    /// Whether `other` holds the same items, so the two can merge.
+    pub fn can_stack_with(&self, other: &ItemStack) -> bool {
+        self.id == other.id
+            && self.durability == other.durability
+            && self.data == other.data
+            && self.insured == other.insured
+    }
+
+    /// A stack of `count` of the same items.
+    fn with_count(&self, count: u32) -> Self {
+        Self {
+            count,
+            ..self.clone()
+        }
+    }
+}
+
+/// Why an operation on an item container failed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InventoryError {
+    /// The container has no slot with this index.
+    NoSuchSlot(usize),
+    /// The slot holds nothing.
+    EmptySlot(usize),
+    /// The item has no definition, so how far it stacks isn't known.
+    UnknownItem(ItemId),
+    /// Zero items, or more than the slot holds, were asked for.
+    InvalidCount(u32),
+    /// The container holds fewer of the item than were asked for.
+    NotEnough {
+        id: ItemId,
+        requested: usize,
+        available: usize,
+    },
+    /// There isn't room for the items.
+    NoRoom,
+    /// The target slot holds different items.
+    SlotOccupied(usize),
+}
+
+impl fmt::Display for InventoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InventoryError::NoSuchSlot(slot) => write!(f, "there is no slot {slot}"),
+            InventoryError::EmptySlot(slot) => write!(f, "slot {slot} is empty"),
+            InventoryError::UnknownItem(id) => write!(f, "item {} has no definition", id.0),
+            InventoryError::InvalidCount(count) => write!(f, "can't move {count} items"),
+            InventoryError::NotEnough {
+                id,
+                requested,
+                available,
+            } => write!(
+                f,
+                "needed {requested} of item {} but only {available} are held",
+                id.0
+            ),
+            InventoryError::NoRoom => write!(f, "there is no room for the items"),
+            InventoryError::SlotOccupied(slot) => write!(f, "slot {slot} holds other items"),
+        }
+    }
+}
+
+impl std::error::Error for InventoryError {}
+
+/// How many of an item fit in one slot.
+pub(super) fn stack_size(registry: &ItemRegistry, id: ItemId) -> Result<u32, InventoryError> {
+    registry
+        .get(id)
+        .map(|item| item.stack_size)
+        .ok_or(InventoryError::UnknownItem(id))
+}
+
+/// Something that holds items in slots, like an inventory or the stash.
+pub trait ItemContainer {
+    fn slots(&self) -> &[Option<ItemStack>];
+    fn slots_mut(&mut self) -> &mut [Option<ItemStack>];
+
+    /// The stack in a slot, if it holds one.
+    fn slot(&self, slot: usize) -> Result<Option<&ItemStack>, InventoryError> {
+        self.slots()
+            .get(slot)
+            .map(Option::as_ref)
+            .ok_or(InventoryError::NoSuchSlot(slot))
+    }
+
+    /// How many of an item the container holds, across all its slots.
+    fn count(&self, id: ItemId) -> usize {
+        self.slots()
+            .iter()
+            .flatten()
+            .filter(|stack| stack.id == id)
+            .map(|stack| stack.count as usize)
+            .sum()
+    }
+
+    /// How many more of `stack`'s items fit, topping up matching stacks and filling empty slots.
+    fn room_for(
+        &self,
+        stack: &ItemStack,
+        registry: &ItemRegistry,
+    ) -> Result<usize, InventoryError> {
+        let max = stack_size(registry, stack.id)?;
+        Ok(self
+            .slots()
+            .iter()
+            .map(|slot| match slot {
+                None => max as usize,
+                Some(held) if held.can_stack_with(stack) => max.saturating_sub(held.count) as usize,
+                Some(_) => 0,
+            })
+            .sum())
+    }
+
+    /// Picks an empty slot for a new stack of `stack`'s items and reserves any space it covers.
+    /// The caller fills the slot straight away.
+    fn claim_slot(
+        &mut self,
+        _stack: &ItemStack,
+        _registry: &ItemRegistry,
+    ) -> Result<Option<usize>, InventoryError> {
+        Ok(self.slots().iter().position(Option::is_none))
+    }
+
+    /// Adds a stack, topping up matching stacks before filling empty slots. Adds nothing if it
+    /// doesn't all fit.
+    fn add(&mut self, stack: ItemStack, registry: &ItemRegistry) -> Result<(), InventoryError> {
+        if stack.count == 0 {
+            return Err(InventoryError::InvalidCount(0));
+        }
+        if self.room_for(&stack, registry)? < stack.count as usize {
+            return Err(InventoryError::NoRoom);
+        }
+        let max = stack_size(registry, stack.id)?;
+        let mut left = stack.count;
+        for held in self.slots_mut().iter_mut().flatten() {
+            if left > 0 && held.can_stack_with(&stack) {
+                let moved = left.min(max.saturating_sub(held.count));
+                held.count += moved;
+                left -= moved;
+            }
+        }
+        while left > 0 {
+            let slot = self
+                .claim_slot(&stack, registry)?
+                .ok_or(InventoryError::NoRoom)?;
+            let moved = left.min(max);
+            self.slots_mut()[slot] = Some(stack.with_count(moved));
+            left -= moved;
+        }
+        Ok(())
+    }
+
+    /// Removes `count` of an item, taking from the last stacks first. Removes nothing if the
+    /// container holds fewer than that.
+    fn remove(&mut self, id: ItemId, count: usize) -> Result<(), InventoryError> {
+        let available = self.count(id);
+        if available < count {
+            return Err(InventoryError::NotEnough {
+                id,
+                requested: count,
+                available,
+            });
+        }
+        let mut left = count;
+        for slot in self.slots_mut().iter_mut().rev() {
+            let Some(held) = slot.as_mut().filter(|held| held.id == id) else {
+                continue;
+            };
+            if left == 0 {
+                break;
+            }
+            let taken = left.min(held.count as usize);
+            held.count -= taken as u32;
+            left -= taken;
+            if held.count == 0 {
+                *slot = None;
+            }
+        }
+        Ok(())
+    }
+
+    /// Takes `count` items out of a slot.
+    fn take(&mut self, slot: usize, count: u32) -> Result<ItemStack, InventoryError> {
+        let entry = self
+            .slots_mut()
+            .get_mut(slot)
+            .ok_or(InventoryError::NoSuchSlot(slot))?;
+        let held = entry.as_mut().ok_or(InventoryError::EmptySlot(slot))?;
+        if count == 0 || count > held.count {
+            return Err(InventoryError::InvalidCount(count));
+        }
+        let taken = held.with_count(count);
+        held.count -= count;
+        if held.count == 0 {
+            *entry = None;
+        }
+        Ok(taken)
+    }
+
+    /// Splits `count` items off a slot into an empty slot, returning that slot.
+    fn split(
+        &mut self,
+        slot: usize,
+        count: u32,
+        registry: &ItemRegistry,
+    ) -> Result<usize, InventoryError> {
+        let held = self
+            .slot(slot)?
+            .ok_or(InventoryError::EmptySlot(slot))?
+            .clone();
+        if count == 0 || count >= held.count {
+            return Err(InventoryError::InvalidCount(count));
+        }
+        let empty = self
+            .claim_slot(&held, registry)?
+            .ok_or(InventoryError::NoRoom)?;
+        let taken = self.take(slot, count)?;
+        self.slots_mut()[empty] = Some(taken);
+        Ok(empty)
+    }
+
+    /// Moves a slot's stack into another slot: into it if it's empty, or onto it if it holds the
+    /// same items, leaving behind whatever doesn't fit.
+    fn move_stack(
+        &mut self,
+        from: usize,
+        to: usize,
+        registry: &ItemRegistry,
+    ) -> Result<(), InventoryError> {
+        let held = self.slot(from)?.ok_or(InventoryError::EmptySlot(from))?;
+        let target = self.slot(to)?;
+        if from == to {
+            return Ok(());
+        }
+        let moved = match target {
+            // Moving a whole stack into an empty slot swaps the two, so the stack keeps any space
+            // it covers.
+            None => return self.swap(from, to),
+            Some(target) if target.can_stack_with(held) => {
+                let max = stack_size(registry, held.id)?;
+                held.count.min(max.saturating_sub(target.count))
+            }
+            Some(_) => return Err(InventoryError::SlotOccupied(to)),
+        };
+        if moved == 0 {
+            return Err(InventoryError::NoRoom);
+        }
+        let taken = self.take(from, moved)?;
+        match &mut self.slots_mut()[to] {
+            Some(target) => target.count += taken.count,
+            empty => *empty = Some(taken),
+        }
+        Ok(())
+    }
+
+    /// Swaps the contents of two slots.
+    fn swap(&mut self, a: usize, b: usize) -> Result<(), InventoryError> {
+        self.slot(a)?;
+        self.slot(b)?;
+        self.slots_mut().swap(a, b);
+        Ok(())
+    }
+
+    /// Moves `count` items from a slot into another container. Moves nothing if they don't all
+    /// fit.
+    fn transfer<C: ItemContainer + ?Sized>(
+        &mut self,
+        slot: usize,
+        count: u32,
+        to: &mut C,
+        registry: &ItemRegistry,
+    ) -> Result<(), InventoryError> {
+        let held = self.slot(slot)?.ok_or(InventoryError::EmptySlot(slot))?;
+        if count == 0 || count > held.count {
+            return Err(InventoryError::InvalidCount(count));
+        }
+        if to.room_for(held, registry)? < count as usize {
+            return Err(InventoryError::NoRoom);
+        }
+        let taken = self.take(slot, count)?;
+        to.add(taken, registry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// A bare container for testing.
+    struct Slots(Vec<Option<ItemStack>>);
+
+    impl ItemContainer for Slots {
+        fn slots(&self) -> &[Option<ItemStack>] {
+            &self.0
+        }
+
+        fn slots_mut(&mut self) -> &mut [Option<ItemStack>] {
+            &mut self.0
+        }
+    }
+
+    const WOOD: ItemId = ItemId(1);
+    const BANDAGE: ItemId = ItemId(30);
+    const PISTOL: ItemId = ItemId(20);
+
+    fn worn(id: ItemId, durability: f32) -> ItemStack {
+        ItemStack {
+            durability: Some(durability),
+            ..ItemStack::new(id, 1)
+        }
+    }
+
+    #[test]
+    fn test_adding_tops_up_stacks_before_using_slots() {
+        let registry = ItemRegistry::default();
+        let mut slots = Slots(vec![None; 3]);
+        slots.add(ItemStack::new(BANDAGE, 3), &registry).unwrap();
+        slots.add(ItemStack::new(BANDAGE, 4), &registry).unwrap();
+        let counts: Vec<_> = slots
+            .0
+            .iter()
+            .map(|slot| slot.as_ref().map(|s| s.count))
+            .collect();
+        assert_eq!(counts, [Some(5), Some(2), None]);
+
+        // Too much for the room left adds nothing.
+        assert_eq!(
+            slots.add(ItemStack::new(BANDAGE, 9), &registry),
+            Err(InventoryError::NoRoom)
+        );
+        assert_eq!(slots.count(BANDAGE), 7);
+        assert_eq!(
+            slots.add(ItemStack::new(ItemId(999), 1), &registry),
+            Err(InventoryError::UnknownItem(ItemId(999)))
+        );
+    }
+
+    #[test]
+    fn test_instance_data_keeps_stacks_apart() {
+        let registry = ItemRegistry::default();
+        let mut slots = Slots(vec![None; 2]);
+        slots.add(worn(BANDAGE, 1.0), &registry).unwrap();
+        slots.add(worn(BANDAGE, 0.5), &registry).unwrap();
+        assert!(slots.0.iter().all(Option::is_some));
+        assert_eq!(
+            slots.move_stack(1, 0, &registry),
+            Err(InventoryError::SlotOccupied(0))
+        );
+        slots.swap(0, 1).unwrap();
+        assert_eq!(slots.0[0].as_ref().unwrap().durability, Some(0.5));
+    }
+
+    #[test]
+    fn test_split_move_and_transfer() {
+        let registry = ItemRegistry::default();
+        let mut slots = Slots(vec![None; 3]);
+        slots.add(ItemStack::new(WOOD, 30), &registry).unwrap();
+        assert_eq!(slots.split(0, 10, &registry), Ok(1));
+        assert_eq!(
+            slots.split(0, 20, &registry),
+            Err(InventoryError::InvalidCount(20))
+        );
+        slots.move_stack(1, 0, &registry).unwrap();
+        assert_eq!(slots.0[0].as_ref().unwrap().count, 30);
+        assert!(slots.0[1].is_none());
+
+        let mut other = Slots(vec![Some(ItemStack::new(PISTOL, 1))]);
+        assert_eq!(
+            slots.transfer(0, 5, &mut other, &registry),
+            Err(InventoryError::NoRoom)
+        );
+        let mut other = Slots(vec![None]);
+        slots.transfer(0, 5, &mut other, &registry).unwrap();
+        assert_eq!((slots.count(WOOD), other.count(WOOD)), (25, 5));
+        assert_eq!(
+            slots.remove(WOOD, 26),
+            Err(InventoryError::NotEnough {
+                id: WOOD,
+                requested: 26,
+                available: 25,
+            })
+        );
+    }
+
+    #[derive(Debug, Clone)]
+    enum Op {
+        Add(usize, u32),
+        Remove(usize, usize),
+        Split(usize, u32),
+        Move(usize, usize),
+        Swap(usize, usize),
+        Transfer(usize, u32),
+    }
+
+    const ITEMS: [ItemId; 3] = [WOOD, BANDAGE, PISTOL];
+    const SLOTS: usize = 6;
+
+    fn op() -> impl Strategy<Value = Op> {
+        prop_oneof![
+            (0..ITEMS.len(), 1..80u32).prop_map(|(item, count)| Op::Add(item, count)),
+            (0..ITEMS.len(), 0..80usize).prop_map(|(item, count)| Op::Remove(item, count)),
+            (0..SLOTS, 0..60u32).prop_map(|(slot, count)| Op::Split(slot, count)),
+            (0..SLOTS, 0..SLOTS).prop_map(|(from, to)| Op::Move(from, to)),
+            (0..SLOTS, 0..SLOTS).prop_map(|(a, b)| Op::Swap(a, b)),
+            (0..SLOTS, 0..60u32).prop_map(|(slot, count)| Op::Transfer(slot, count)),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn prop_item_counts_are_conserved(ops in prop::collection::vec(op(), 1..60)) {
+            let registry = ItemRegistry::default();
+            let mut slots = Slots(vec![None; SLOTS]);
+            let mut other = Slots(vec![None; SLOTS / 2]);
+            let total = |slots: &Slots, other: &Slots, id| slots.count(id) + other.count(id);
+            for op in ops {
+                let before: Vec<_> = ITEMS.iter().map(|id| total(&slots, &other, *id)).collect();
+                // What the operation should have changed each item's total by.
+                let mut expected = before.clone();
+                match op {
+                    Op::Add(item, count) => {
+                        if slots.add(ItemStack::new(ITEMS[item], count), &registry).is_ok() {
+                            expected[item] += count as usize;
+                        }
+                    }
+                    Op::Remove(item, count) => {
+                        if slots.remove(ITEMS[item], count).is_ok() {
+                            expected[item] -= count;
+                        }
+                    }
+                    Op::Split(slot, count) => {
+                        let _ = slots.split(slot, count, &registry);
+                    }
+                    Op::Move(from, to) => {
+                        let _ = slots.move_stack(from, to, &registry);
+                    }
+                    Op::Swap(a, b) => {
+                        let _ = slots.swap(a, b);
+                    }
+                    Op::Transfer(slot, count) => {
+                        let _ = slots.transfer(slot, count, &mut other, &registry);
+                    }
+                }
+                let after: Vec<_> = ITEMS.iter().map(|id| total(&slots, &other, *id)).collect();
+                prop_assert_eq!(after, expected);
+
+                for stack in slots.0.iter().chain(&other.0).flatten() {
+                    prop_assert!(stack.count > 0);
+                    prop_assert!(stack.count <= registry.get(stack.id).unwrap().stack_size);
+                }
+            }
+        }
+    }
+}
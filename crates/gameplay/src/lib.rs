@@ -1,17 +1,27 @@
-//! Gameplay logic, including movement, stats, inventory, building, and missions.
+//! Gameplay logic, including movement, stats, inventory, building, missions, and saves.
 
 use bevy::prelude::*;
 
 pub mod building;
+pub mod climbing;
+pub mod health;
 pub mod inventory;
 pub mod movement;
+pub mod profile;
+pub mod replay;
+pub mod stamina;
+pub mod swimming;
 
 pub mod camera;
 use building::BuildingPlugin;
 use camera::CameraPlugin;
+use health::HealthPlugin;
 use inventory::InventoryPlugin;
 use movement::MovementPlugin;
 use player::PlayerPlugin;
+use replay::ReplayPlugin;
+use stamina::StaminaPlugin;
+use swimming::SwimmingPlugin;
 
 pub mod player;
 
@@ -25,6 +35,10 @@ impl Plugin for GameplayPlugin {
             InventoryPlugin,
             PlayerPlugin,
             CameraPlugin,
+            StaminaPlugin,
+            HealthPlugin,
+            SwimmingPlugin,
+            ReplayPlugin,
         ));
     }
 }
@@ -1,11 +1,51 @@
 //! Player movement state and logic.
-use crate::camera::CameraRig;
-use crate::player::Player;
+use crate::camera::{CameraPerspective, CameraRig};
+use crate::climbing::{
+    climb_velocity, detect_climbable, jump_off_velocity, top_out_velocity, ClimbContact,
+};
+use crate::health::{DamageEvent, DamageSource};
+use crate::inventory::encumbrance::Encumbrance;
+use crate::player::{Player, PLAYER_FEET_OFFSET};
+use crate::stamina::Stamina;
+use crate::swimming::{
+    detect_submersion, step_swim_velocity, Submersion, SWIM_ENTER_SUBMERSION, SWIM_EXIT_SUBMERSION,
+};
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
+use common::input::{ActionState, InputAction};
+use world::{MaterialRegistry, WorldData};
 
 const BASE_MOVE_SPEED: f32 = 5.0;
 const SPRINT_SPEED: f32 = 10.0;
+const CROUCH_SPEED: f32 = 2.5;
+
+/// Horizontal acceleration towards the target speed on regular footing, in m/s².
+pub const GROUND_ACCELERATION: f32 = 40.0;
+/// Horizontal deceleration when there is no input on regular footing, in m/s².
+pub const GROUND_DECELERATION: f32 = 50.0;
+/// The fraction of `GROUND_ACCELERATION` available while airborne.
+pub const AIR_CONTROL: f32 = 0.2;
+/// Deceleration while sliding on regular footing, in m/s².
+pub const SLIDE_DECELERATION: f32 = 6.0;
+/// Speed added in the direction of travel when a slide starts.
+pub const SLIDE_BOOST: f32 = 2.0;
+/// Initial upwards speed of a jump.
+pub const JUMP_SPEED: f32 = 6.0;
+/// Initial upwards speed of a dive, which stays lower than a jump.
+pub const DIVE_SPEED: f32 = 3.5;
+/// Speed added in the direction of travel when a dive starts.
+pub const DIVE_BOOST: f32 = 3.0;
+
+/// Landings slower than this are not reported, so walking over bumps doesn't spam `Landed`.
+const MIN_LANDING_EVENT_SPEED: f32 = 2.0;
+/// The highest impact speed that causes no fall damage (roughly a 5m drop).
+pub const SAFE_IMPACT_SPEED: f32 = 10.0;
+/// The impact speed at which fall damage reaches `LETHAL_FALL_DAMAGE` (roughly a 32m drop).
+pub const LETHAL_IMPACT_SPEED: f32 = 25.0;
+/// Fall damage dealt at `LETHAL_IMPACT_SPEED`, matching the default max health.
+pub const LETHAL_FALL_DAMAGE: f32 = 100.0;
+/// The fraction of fall damage absorbed by landing in a slide or a crouch roll.
+pub const ROLL_MITIGATION: f32 = 0.5;
 
 /// A state machine for player movement, implemented as a Bevy State.
 #[derive(States, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
@@ -23,81 +63,243 @@ pub enum MovementState {
     Mantling,
     Vaulting,
     Leaning,
+    Swimming,
 }
 
 /// A resource to store the player's processed input.
 #[derive(Resource, Default)]
 pub struct PlayerInput {
     pub move_direction: Vec2,
+    pub sprint: bool,
+    pub crouch: bool,
+    pub jump: bool,
 }
 
-/// Gathers keyboard input and stores it in the `PlayerInput` resource.
-fn gather_player_input(
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut player_input: ResMut<PlayerInput>,
-) {
-    let mut direction = Vec2::ZERO;
-    if keyboard_input.pressed(KeyCode::KeyW) {
-        direction.y += 1.0;
+/// The velocity of a kinematic character, integrated by the movement systems.
+///
+/// Named to avoid clashing with Rapier's `Velocity`, which only applies to dynamic bodies.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq)]
+pub struct CharacterVelocity(pub Vec3);
+
+impl CharacterVelocity {
+    /// The velocity projected onto the XZ plane.
+    pub fn horizontal(&self) -> Vec3 {
+        Vec3::new(self.0.x, 0.0, self.0.z)
+    }
+}
+
+/// Tracks whether the character was on the ground last tick, to detect landings.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct GroundContact {
+    pub grounded: bool,
+}
+
+/// An event triggered when the player touches down after being airborne, for camera shake and audio.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct Landed {
+    /// The downwards speed at touchdown, in m/s.
+    pub impact_speed: f32,
+}
+
+/// What the character is standing on for a tick, which selects the velocity curve.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Footing {
+    /// On the ground, with the friction of the surface underfoot.
+    Ground { friction: f32 },
+    /// Sliding along the ground, with the friction of the surface underfoot.
+    Sliding { friction: f32 },
+    /// In the air.
+    Airborne,
+}
+
+/// Moves `current` towards `target` by at most `max_delta`.
+pub(crate) fn approach(current: Vec3, target: Vec3, max_delta: f32) -> Vec3 {
+    let delta = target - current;
+    let distance = delta.length();
+    if distance <= max_delta || distance == 0.0 {
+        target
+    } else {
+        current + delta / distance * max_delta
+    }
+}
+
+/// Advances a horizontal velocity by one tick of length `dt`.
+///
+/// `wish_velocity` is the input direction scaled by the target speed for the current state.
+/// Ground movement accelerates and brakes proportionally to the surface friction, slides only
+/// bleed speed, and airborne movement keeps its momentum with limited air control.
+pub fn step_horizontal_velocity(
+    current: Vec3,
+    wish_velocity: Vec3,
+    footing: Footing,
+    dt: f32,
+) -> Vec3 {
+    match footing {
+        Footing::Ground { friction } => {
+            let rate = if wish_velocity == Vec3::ZERO {
+                GROUND_DECELERATION
+            } else {
+                GROUND_ACCELERATION
+            };
+            approach(current, wish_velocity, rate * friction * dt)
+        }
+        Footing::Sliding { friction } => {
+            approach(current, Vec3::ZERO, SLIDE_DECELERATION * friction * dt)
+        }
+        Footing::Airborne => {
+            if wish_velocity == Vec3::ZERO {
+                current
+            } else {
+                approach(
+                    current,
+                    wish_velocity,
+                    GROUND_ACCELERATION * AIR_CONTROL * dt,
+                )
+            }
+        }
     }
-    if keyboard_input.pressed(KeyCode::KeyS) {
-        direction.y -= 1.0;
+}
+
+/// The target ground speed for a movement state.
+fn target_speed(state: MovementState) -> f32 {
+    match state {
+        MovementState::Sprinting => SPRINT_SPEED,
+        MovementState::Crouching | MovementState::Proning => CROUCH_SPEED,
+        _ => BASE_MOVE_SPEED,
     }
-    if keyboard_input.pressed(KeyCode::KeyA) {
-        direction.x -= 1.0;
+}
+
+/// Computes fall damage for a landing.
+///
+/// Damage ramps linearly from zero at `SAFE_IMPACT_SPEED` to `LETHAL_FALL_DAMAGE` at
+/// `LETHAL_IMPACT_SPEED`, then is reduced by `mitigation` (the fraction absorbed, `0.0..=1.0`).
+pub fn fall_damage(impact_speed: f32, mitigation: f32) -> f32 {
+    if impact_speed <= SAFE_IMPACT_SPEED {
+        return 0.0;
     }
-    if keyboard_input.pressed(KeyCode::KeyD) {
-        direction.x += 1.0;
+    let severity = (impact_speed - SAFE_IMPACT_SPEED) / (LETHAL_IMPACT_SPEED - SAFE_IMPACT_SPEED);
+    severity * LETHAL_FALL_DAMAGE * (1.0 - mitigation.clamp(0.0, 1.0))
+}
+
+/// Looks up how much fall damage the voxels at and directly below `feet` absorb.
+fn landing_absorption(
+    world: Option<&WorldData>,
+    materials: Option<&MaterialRegistry>,
+    feet: Vec3,
+) -> f32 {
+    let (Some(world), Some(materials)) = (world, materials) else {
+        return 0.0;
+    };
+    [feet + Vec3::Y * 0.1, feet - Vec3::Y * 0.1]
+        .into_iter()
+        .filter_map(|pos| world.material_at(pos))
+        .filter_map(|id| materials.get(id))
+        .map(|material| material.impact_absorption)
+        .fold(0.0, f32::max)
+}
+
+/// Looks up the friction of the voxel directly below `feet`.
+/// Falls back to regular footing when there is no voxel data, e.g. on plain colliders.
+fn surface_friction(
+    world: Option<&WorldData>,
+    materials: Option<&MaterialRegistry>,
+    feet: Vec3,
+) -> f32 {
+    let (Some(world), Some(materials)) = (world, materials) else {
+        return 1.0;
+    };
+    world
+        .material_at(feet - Vec3::Y * 0.1)
+        .and_then(|id| materials.get(id))
+        .filter(|material| material.is_solid)
+        .map_or(1.0, |material| material.friction)
+}
+
+/// The rotation the player should face, if it changes this tick.
+///
+/// In first person or while aiming the player faces where the camera looks and strafes freely;
+/// otherwise they turn towards their direction of travel.
+fn facing_rotation(face_view: bool, look: Vec3, desired_move: Vec3) -> Option<Quat> {
+    let facing = if face_view { look } else { desired_move };
+    (facing.length_squared() > 0.0).then(|| Quat::from_rotation_y(facing.x.atan2(facing.z)))
+}
+
+/// Gathers the player's actions and stores them in the `PlayerInput` resource.
+///
+/// While the camera is detached from the player, the actions steer the camera instead.
+pub(crate) fn gather_player_input(
+    actions: Res<ActionState>,
+    mut player_input: ResMut<PlayerInput>,
+    perspective: Option<Res<State<CameraPerspective>>>,
+) {
+    if perspective.is_some_and(|perspective| !perspective.get().follows_player()) {
+        *player_input = PlayerInput::default();
+        return;
     }
-    player_input.move_direction = direction.normalize_or_zero();
+    player_input.move_direction = actions.move_axis();
+    player_input.sprint = actions.pressed(InputAction::Sprint);
+    player_input.crouch = actions.pressed(InputAction::Crouch);
+    player_input.jump = actions.pressed(InputAction::Jump);
 }
 
-/// Applies the player's input to the `KinematicCharacterController`.
+/// The components `apply_player_movement` reads and writes on the player.
+type PlayerMovementData<'a> = (
+    &'a mut KinematicCharacterController,
+    &'a mut Transform,
+    &'a mut CharacterVelocity,
+    Option<&'a KinematicCharacterControllerOutput>,
+    Option<&'a GravityScale>,
+    Option<&'a ClimbContact>,
+    Option<&'a Submersion>,
+    Option<&'a Encumbrance>,
+);
+
+/// Integrates the player's velocity from input and feeds it to the `KinematicCharacterController`.
+#[allow(clippy::too_many_arguments)]
 fn apply_player_movement(
-    mut player_query: Query<(&mut KinematicCharacterController, &mut Transform), With<Player>>,
-    rig_query: Query<&Transform, (With<CameraRig>, Without<Player>)>,
+    mut player_query: Query<PlayerMovementData, With<Player>>,
+    rig_query: Query<&CameraRig>,
     player_input: Res<PlayerInput>,
     movement_state: Res<State<MovementState>>,
     time: Res<Time>,
     rapier_config: Res<RapierConfiguration>,
+    world_data: Option<Res<WorldData>>,
+    materials: Option<Res<MaterialRegistry>>,
+    perspective: Option<Res<State<CameraPerspective>>>,
 ) {
-    let Ok((mut controller, mut player_transform)) = player_query.get_single_mut() else {
-        return;
-    };
-    let Ok(rig_transform) = rig_query.get_single() else {
+    let Ok((
+        mut controller,
+        mut player_transform,
+        mut velocity,
+        output,
+        gravity_scale,
+        climb,
+        submersion,
+        encumbrance,
+    )) = player_query.get_single_mut()
+    else {
         return;
     };
-
-    if player_input.move_direction == Vec2::ZERO {
-        controller.translation = Some(Vec3::ZERO);
-        return;
-    }
+    // Without a single camera rig to steer by, move as if looking straight ahead.
+    let default_rig = CameraRig::default();
+    let rig = rig_query.get_single().unwrap_or(&default_rig);
 
     // --- Camera-relative movement ---
-    // Get the camera's forward direction on the XZ plane.
-    let mut forward: Vec3 = rig_transform.forward().into();
+    // Get the camera's forward direction on the XZ plane. This is where the player is pointing
+    // the camera, not where the smoothed view has turned to so far.
+    let look = rig.rotation() * Vec3::NEG_Z;
+    let mut forward = look;
     forward.y = 0.0;
     let forward = forward.normalize();
 
     // Get the camera's right direction based on the new forward vector.
-    let right = Vec3::new(forward.z, 0.0, -forward.x);
+    let right = Vec3::new(-forward.z, 0.0, forward.x);
 
     // Calculate the desired movement direction based on player input.
     let desired_move = (forward * player_input.move_direction.y
         + right * player_input.move_direction.x)
         .normalize_or_zero();
 
-    // --- Player rotation ---
-    if desired_move.length_squared() > 0.0 {
-        player_transform.rotation = Quat::from_rotation_y(desired_move.x.atan2(desired_move.z));
-    }
-
-    // --- Apply movement ---
-    let speed = match movement_state.get() {
-        MovementState::Sprinting => SPRINT_SPEED,
-        _ => BASE_MOVE_SPEED,
-    };
-
     // Use the fixed delta time from Rapier's config if it's set, otherwise use the variable time.
     // This makes the system compatible with both the fixed-timestep test environment
     // and the variable-timestep game environment.
@@ -105,39 +307,260 @@ fn apply_player_movement(
         TimestepMode::Fixed { dt, .. } => dt,
         _ => time.delta_seconds(),
     };
+    let state = *movement_state.get();
+    let face_view = rig.is_aiming()
+        || perspective
+            .is_some_and(|perspective| *perspective.get() == CameraPerspective::FirstPerson);
+    let gravity = rapier_config.gravity.y * gravity_scale.map_or(1.0, |scale| scale.0);
+
+    // --- Climbing ---
+    // While climbing the player keeps facing the surface and gravity is suspended.
+    if state == MovementState::Climbing {
+        velocity.0 = match climb.and_then(|climb| climb.normal) {
+            Some(normal) if player_input.jump => {
+                jump_off_velocity(normal, right * player_input.move_direction.x)
+            }
+            Some(_) => climb_velocity(player_input.move_direction),
+            // Past the top, hold on until `start_mantle` pulls the player over or they let go.
+            None => Vec3::ZERO,
+        };
+        controller.translation = Some(velocity.0 * delta_seconds);
+        return;
+    }
+
+    // --- Swimming ---
+    // Swimming follows the camera in 3D, with jump and crouch to rise and dive.
+    if state == MovementState::Swimming {
+        let rise = (i8::from(player_input.jump) - i8::from(player_input.crouch)) as f32;
+        let wish_direction = look * player_input.move_direction.y
+            + right * player_input.move_direction.x
+            + Vec3::Y * rise;
+        if let Some(facing) = facing_rotation(face_view, forward, desired_move) {
+            player_transform.rotation = facing;
+        }
+        velocity.0 = step_swim_velocity(
+            velocity.0,
+            wish_direction,
+            submersion.map_or(0.0, |submersion| submersion.depth),
+            gravity,
+            delta_seconds,
+        );
+        controller.translation = Some(velocity.0 * delta_seconds);
+        return;
+    }
+
+    // --- Player rotation ---
+    if let Some(facing) = facing_rotation(face_view, forward, desired_move) {
+        player_transform.rotation = facing;
+    }
+
+    // --- Horizontal velocity ---
+    let grounded = output.is_some_and(|output| output.grounded);
+    let friction = surface_friction(
+        world_data.as_deref(),
+        materials.as_deref(),
+        player_transform.translation - Vec3::Y * PLAYER_FEET_OFFSET,
+    );
+    let footing = match (grounded, state) {
+        (false, _) => Footing::Airborne,
+        (true, MovementState::Sliding) => Footing::Sliding { friction },
+        (true, _) => Footing::Ground { friction },
+    };
+    let load_factor = encumbrance.map_or(1.0, |encumbrance| encumbrance.tier.speed_multiplier());
+    let wish_velocity = desired_move * target_speed(state) * load_factor;
+    let horizontal =
+        step_horizontal_velocity(velocity.horizontal(), wish_velocity, footing, delta_seconds);
+
+    // --- Vertical velocity ---
+    // Gravity is always applied so the controller keeps pressing into the ground while grounded.
+    let mut vertical = velocity.0.y;
+    if grounded && vertical < 0.0 {
+        vertical = 0.0;
+    }
+    if grounded && player_input.jump {
+        // Jumping keeps the horizontal momentum built up on the ground.
+        vertical = JUMP_SPEED;
+    } else {
+        vertical += gravity * delta_seconds;
+    }
+
+    velocity.0 = Vec3::new(horizontal.x, vertical, horizontal.z);
+    controller.translation = Some(velocity.0 * delta_seconds);
+}
+
+/// Gives the player a burst of speed in their direction of travel when a slide starts.
+fn start_slide(mut player_query: Query<&mut CharacterVelocity, With<Player>>) {
+    for mut velocity in player_query.iter_mut() {
+        let direction = velocity.horizontal().normalize_or_zero();
+        velocity.0 += direction * SLIDE_BOOST;
+    }
+}
+
+/// Launches the player forwards and up when a dive starts.
+fn start_dive(mut player_query: Query<&mut CharacterVelocity, With<Player>>) {
+    for mut velocity in player_query.iter_mut() {
+        let direction = velocity.horizontal().normalize_or_zero();
+        velocity.0 = velocity.horizontal() + direction * DIVE_BOOST + Vec3::Y * DIVE_SPEED;
+    }
+}
 
-    // Let Rapier's character controller handle gravity and collisions.
-    let movement = desired_move * speed * delta_seconds;
-    controller.translation = Some(movement);
+/// Carries the player over the top of the surface they climbed when a mantle starts.
+fn start_mantle(mut player_query: Query<(&Transform, &mut CharacterVelocity), With<Player>>) {
+    for (transform, mut velocity) in player_query.iter_mut() {
+        velocity.0 = top_out_velocity(transform.rotation * Vec3::Z);
+    }
 }
 
-/// Updates the `MovementState` based on whether the player is moving and sprinting.
-fn update_movement_state(
+/// The components `detect_landing` reads and writes on the player.
+type PlayerLandingData<'a> = (
+    Entity,
+    &'a Transform,
+    &'a CharacterVelocity,
+    &'a mut GroundContact,
+    Option<&'a KinematicCharacterControllerOutput>,
+);
+
+/// Detects the player touching down, emitting `Landed` and applying fall damage.
+///
+/// Runs before `apply_player_movement`, which zeroes the vertical velocity once grounded.
+fn detect_landing(
+    mut player_query: Query<PlayerLandingData, With<Player>>,
     player_input: Res<PlayerInput>,
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut next_state: ResMut<NextState<MovementState>>,
-    current_state: Res<State<MovementState>>,
+    movement_state: Res<State<MovementState>>,
+    world_data: Option<Res<WorldData>>,
+    materials: Option<Res<MaterialRegistry>>,
+    mut landed_events: EventWriter<Landed>,
+    mut damage_events: EventWriter<DamageEvent>,
 ) {
-    let current_state = *current_state.get();
+    for (entity, transform, velocity, mut contact, output) in player_query.iter_mut() {
+        let grounded = output.is_some_and(|output| output.grounded);
+        let was_grounded = std::mem::replace(&mut contact.grounded, grounded);
+        let impact_speed = -velocity.0.y;
+        if !grounded || was_grounded || impact_speed < MIN_LANDING_EVENT_SPEED {
+            continue;
+        }
+
+        landed_events.send(Landed { impact_speed });
 
-    // Check for stopping
-    if player_input.move_direction == Vec2::ZERO {
-        if current_state != MovementState::Idle {
-            next_state.set(MovementState::Idle);
+        let rolling = player_input.crouch || *movement_state.get() == MovementState::Sliding;
+        let material_absorption = landing_absorption(
+            world_data.as_deref(),
+            materials.as_deref(),
+            transform.translation - Vec3::Y * PLAYER_FEET_OFFSET,
+        );
+        let mitigation = if rolling {
+            material_absorption.max(ROLL_MITIGATION)
+        } else {
+            material_absorption
+        };
+        let damage = fall_damage(impact_speed, mitigation);
+        if damage > 0.0 {
+            damage_events.send(DamageEvent {
+                target: entity,
+                amount: damage,
+                source: DamageSource::Fall,
+            });
         }
-        return;
     }
+}
 
-    // Player is moving, check for sprint
-    let is_sprinting = keyboard_input.pressed(KeyCode::ShiftLeft);
+/// The components `update_movement_state` reads on the player.
+type PlayerStateData<'a> = (
+    &'a CharacterVelocity,
+    Option<&'a Stamina>,
+    Option<&'a ClimbContact>,
+    Option<&'a Submersion>,
+    Option<&'a KinematicCharacterControllerOutput>,
+);
 
-    if is_sprinting {
-        if current_state != MovementState::Sprinting {
-            next_state.set(MovementState::Sprinting);
+/// Updates the `MovementState` based on the player's input, current speed, stamina,
+/// climbable surfaces and water.
+pub(crate) fn update_movement_state(
+    player_input: Res<PlayerInput>,
+    player_query: Query<PlayerStateData, With<Player>>,
+    mut next_state: ResMut<NextState<MovementState>>,
+    current_state: Res<State<MovementState>>,
+) {
+    let current_state = *current_state.get();
+    let (velocity, stamina, climb, submersion, output) = match player_query.get_single() {
+        Ok((velocity, stamina, climb, submersion, output)) => {
+            (*velocity, stamina, climb, submersion, output)
         }
-    } else if current_state != MovementState::Walking {
-        // Not sprinting, so should be walking (if not already)
-        next_state.set(MovementState::Walking);
+        Err(_) => (CharacterVelocity::default(), None, None, None, None),
+    };
+    let depth = submersion.map_or(0.0, |submersion| submersion.depth);
+    let speed = velocity.horizontal().length();
+    let grounded = output.is_some_and(|output| output.grounded);
+    let touching_climbable = climb.is_some_and(|climb| climb.normal.is_some());
+    let can_enter = |state: MovementState| {
+        state == current_state || stamina.is_none_or(|stamina| stamina.can_enter(state))
+    };
+    let exhausted = stamina.is_some_and(Stamina::is_exhausted);
+    let landed = grounded && velocity.0.y <= 0.0;
+
+    let next = if current_state == MovementState::Climbing
+        && touching_climbable
+        && !player_input.jump
+        && !exhausted
+        && !(grounded && player_input.move_direction.y < 0.0)
+    {
+        // Stay on the surface until climbing off the top or bottom, jumping off or tiring out.
+        MovementState::Climbing
+    } else if current_state != MovementState::Climbing
+        && touching_climbable
+        && player_input.move_direction.y > 0.0
+        && !player_input.crouch
+        && velocity.0.y <= 0.0
+        && can_enter(MovementState::Climbing)
+    {
+        MovementState::Climbing
+    } else if current_state == MovementState::Climbing
+        && !touching_climbable
+        && player_input.move_direction.y > 0.0
+        && can_enter(MovementState::Mantling)
+    {
+        // Pull up over the top of the surface; without the stamina for it, let go instead.
+        MovementState::Mantling
+    } else if depth >= SWIM_ENTER_SUBMERSION
+        || (current_state == MovementState::Swimming && depth > SWIM_EXIT_SUBMERSION)
+    {
+        MovementState::Swimming
+    } else if matches!(
+        current_state,
+        MovementState::Mantling | MovementState::Diving
+    ) && !landed
+    {
+        // Mantles and dives carry on until the player lands.
+        current_state
+    } else if current_state == MovementState::Sprinting
+        && grounded
+        && player_input.jump
+        && player_input.crouch
+        && can_enter(MovementState::Diving)
+    {
+        MovementState::Diving
+    } else if current_state == MovementState::Sliding && player_input.crouch && speed > CROUCH_SPEED
+    {
+        // Keep sliding until the momentum runs out or crouch is released.
+        MovementState::Sliding
+    } else if current_state == MovementState::Sprinting
+        && player_input.crouch
+        && speed > BASE_MOVE_SPEED
+        && can_enter(MovementState::Sliding)
+    {
+        MovementState::Sliding
+    } else if player_input.crouch {
+        MovementState::Crouching
+    } else if player_input.move_direction == Vec2::ZERO {
+        MovementState::Idle
+    } else if player_input.sprint && can_enter(MovementState::Sprinting) && !exhausted {
+        MovementState::Sprinting
+    } else {
+        MovementState::Walking
+    };
+
+    if next != current_state {
+        next_state.set(next);
     }
 }
 
@@ -147,10 +570,19 @@ impl Plugin for MovementPlugin {
     fn build(&self, app: &mut App) {
         app.init_state::<MovementState>()
             .init_resource::<PlayerInput>()
+            .init_resource::<ActionState>()
+            .add_event::<Landed>()
+            .add_event::<DamageEvent>()
+            .add_systems(OnEnter(MovementState::Sliding), start_slide)
+            .add_systems(OnEnter(MovementState::Diving), start_dive)
+            .add_systems(OnEnter(MovementState::Mantling), start_mantle)
             .add_systems(
                 FixedUpdate,
                 (
                     gather_player_input,
+                    detect_climbable,
+                    detect_submersion,
+                    detect_landing,
                     apply_player_movement,
                     update_movement_state,
                 )
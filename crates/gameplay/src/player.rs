@@ -3,6 +3,21 @@
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
 
+use crate::climbing::ClimbContact;
+use crate::health::Health;
+use crate::inventory::encumbrance::Encumbrance;
+use crate::inventory::Inventory;
+use crate::movement::{CharacterVelocity, GroundContact};
+use crate::stamina::Stamina;
+use crate::swimming::{Breath, Submersion};
+
+/// Half the height of the cylindrical part of the player's capsule collider.
+pub const PLAYER_HALF_HEIGHT: f32 = 1.0;
+/// The radius of the player's capsule collider.
+pub const PLAYER_RADIUS: f32 = 0.5;
+/// The distance from the player's origin down to the bottom of its collider.
+pub const PLAYER_FEET_OFFSET: f32 = PLAYER_HALF_HEIGHT + PLAYER_RADIUS;
+
 /// A marker component for the player entity.
 #[derive(Component)]
 pub struct Player;
@@ -27,9 +42,18 @@ fn spawn_player(
         },
         // --- Rapier components ---
         RigidBody::KinematicPositionBased,
-        Collider::capsule_y(1.0, 0.5), // Physics shape
+        Collider::capsule_y(PLAYER_HALF_HEIGHT, PLAYER_RADIUS), // Physics shape
         KinematicCharacterController::default(),
         GravityScale(1.0),
+        CharacterVelocity::default(),
+        GroundContact::default(),
+        ClimbContact::default(),
+        Submersion::default(),
+        Breath::default(),
+        Health::default(),
+        Stamina::default(),
+        Inventory::default(),
+        Encumbrance::default(),
     ));
 }
 
@@ -0,0 +1,431 @@
+//! The player profile: everything that carries over between sessions.
+//!
+//! A profile holds the stash, progress like currency and unlocked blueprints, and the player's
+//! settings. It's saved as RON under a one-line header giving the format version and a checksum
+//! of the rest, so damaged files are caught rather than half-loaded. Saves go to a temporary file
+//! that then replaces the profile, so a crash mid-save never leaves a partly written profile,
+//! and the last few saves are kept as numbered backups to fall back on.
+use crate::building::blueprint::Blueprint;
+use crate::camera::profile::CameraProfiles;
+use crate::inventory::mission::EndMission;
+use crate::inventory::Stash;
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use common::config::{self, ConfigError};
+use common::input::{InputBindings, InputBindingsPath};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// The profile format version; bumped whenever saved data changes meaning.
+pub const PROFILE_FORMAT_VERSION: u32 = 2;
+/// Where the profile is saved by default.
+pub const DEFAULT_PROFILE_PATH: &str = "saves/profile.ron";
+/// How many earlier saves are kept as backups.
+pub const PROFILE_BACKUPS: usize = 3;
+/// The start of the header line, before the version and checksum.
+const HEADER_PREFIX: &str = "// protocol-zero profile v";
+
+/// An error while loading or saving a profile.
+#[derive(Debug)]
+pub enum ProfileError {
+    Config(ConfigError),
+    /// The file doesn't start with a profile header.
+    InvalidHeader,
+    /// The contents don't match the checksum they were saved with.
+    ChecksumMismatch,
+    /// The file was written by an incompatible version of the game.
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProfileError::Config(err) => write!(f, "{err}"),
+            ProfileError::InvalidHeader => write!(f, "not a profile"),
+            ProfileError::ChecksumMismatch => write!(f, "the profile is damaged"),
+            ProfileError::UnsupportedVersion(version) => {
+                write!(f, "unsupported profile version {version}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProfileError {}
+
+impl From<ConfigError> for ProfileError {
+    fn from(err: ConfigError) -> Self {
+        ProfileError::Config(err)
+    }
+}
+
+/// What the player has earned, beyond what's in the stash.
+#[derive(Resource, Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Progress {
+    pub currency: u64,
+    /// Blueprints the player has unlocked or captured, and can place, by name.
+    pub blueprints: BTreeMap<String, Blueprint>,
+    /// Story flags that have been set.
+    pub story_flags: BTreeSet<String>,
+}
+
+/// The settings saved with a profile.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProfileSettings {
+    pub bindings: InputBindings,
+    pub camera: CameraProfiles,
+}
+
+/// Everything saved for a player.
+///
+/// Anything left out of a file takes its default, so fields can be added without bumping the
+/// format version.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub stash: Stash,
+    pub progress: Progress,
+    pub settings: ProfileSettings,
+}
+
+impl Profile {
+    /// Parses a profile, checking its header and upgrading it from older formats.
+    pub fn from_ron(source: &str) -> Result<Self, ProfileError> {
+        let (header, body) = source.split_once('\n').ok_or(ProfileError::InvalidHeader)?;
+        let (version, saved_checksum) = header
+            .trim_end()
+            .strip_prefix(HEADER_PREFIX)
+            .and_then(|header| header.split_once(' '))
+            .ok_or(ProfileError::InvalidHeader)?;
+        let version: u32 = version.parse().map_err(|_| ProfileError::InvalidHeader)?;
+        let saved_checksum =
+            u64::from_str_radix(saved_checksum, 16).map_err(|_| ProfileError::InvalidHeader)?;
+        if checksum(body) != saved_checksum {
+            return Err(ProfileError::ChecksumMismatch);
+        }
+        migrate(version, body)
+    }
+
+    /// Serializes the profile to RON under its header.
+    pub fn to_ron(&self) -> Result<String, ProfileError> {
+        let body = config::to_ron(self)?;
+        Ok(format!(
+            "{HEADER_PREFIX}{PROFILE_FORMAT_VERSION} {:016x}\n{body}",
+            checksum(&body)
+        ))
+    }
+
+    /// Loads a profile from a file.
+    pub fn load(path: &Path) -> Result<Self, ProfileError> {
+        let source = std::fs::read_to_string(path).map_err(ConfigError::Io)?;
+        Self::from_ron(&source)
+    }
+
+    /// Loads a profile, falling back to the newest backup that loads if the file itself is
+    /// missing or damaged. Fails with the file's own error if no copy loads.
+    pub fn recover(path: &Path) -> Result<Self, ProfileError> {
+        let err = match Self::load(path) {
+            Ok(profile) => return Ok(profile),
+            Err(err) => err,
+        };
+        for n in 1..=PROFILE_BACKUPS {
+            let backup = backup_path(path, n);
+            if let Ok(profile) = Self::load(&backup) {
+                warn!("Loaded profile backup {:?}: {}", backup, err);
+                return Ok(profile);
+            }
+        }
+        Err(err)
+    }
+
+    /// Saves the profile to a file, creating its directory if needed and keeping the file it
+    /// replaces as the newest backup.
+    pub fn save(&self, path: &Path) -> Result<(), ProfileError> {
+        let contents = self.to_ron()?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(ConfigError::Io)?;
+        }
+        let temp = with_suffix(path, "tmp");
+        write_synced(&temp, &contents).map_err(ConfigError::Io)?;
+        if path.exists() {
+            rotate_backups(path).map_err(ConfigError::Io)?;
+        }
+        Ok(std::fs::rename(&temp, path).map_err(ConfigError::Io)?)
+    }
+}
+
+/// The layout of format 1 profiles, which kept blueprints in a list.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ProfileV1 {
+    stash: Stash,
+    progress: ProgressV1,
+    settings: ProfileSettings,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ProgressV1 {
+    currency: u64,
+    blueprints: Vec<Blueprint>,
+    story_flags: BTreeSet<String>,
+}
+
+impl From<ProfileV1> for Profile {
+    /// Keys blueprints by name. Of several with the same name, the last one saved is kept, as if
+    /// they had been unlocked in order.
+    fn from(profile: ProfileV1) -> Self {
+        Self {
+            stash: profile.stash,
+            progress: Progress {
+                currency: profile.progress.currency,
+                blueprints: profile
+                    .progress
+                    .blueprints
+                    .into_iter()
+                    .map(|blueprint| (blueprint.name.clone(), blueprint))
+                    .collect(),
+                story_flags: profile.progress.story_flags,
+            },
+            settings: profile.settings,
+        }
+    }
+}
+
+/// Parses a profile body saved in format `version`, upgrading it to the current format.
+///
+/// Fields added since an older version take their defaults, so only changes that rename or
+/// reshape saved data need a conversion here, from the old layout kept as its own type.
+fn migrate(version: u32, body: &str) -> Result<Profile, ProfileError> {
+    match version {
+        1 => Ok(config::from_ron::<ProfileV1>(body)?.into()),
+        PROFILE_FORMAT_VERSION => Ok(config::from_ron(body)?),
+        _ => Err(ProfileError::UnsupportedVersion(version)),
+    }
+}
+
+/// A 64-bit FNV-1a hash, which is plenty to notice a damaged file.
+fn checksum(body: &str) -> u64 {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+    body.bytes().fold(OFFSET, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    })
+}
+
+/// `path` with `.suffix` added to its file name.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Where the `n`th newest backup of the profile at `path` is kept, counting from 1.
+pub fn backup_path(path: &Path, n: usize) -> PathBuf {
+    with_suffix(path, &format!("bak{n}"))
+}
+
+/// Writes a file and waits for it to reach the disk.
+fn write_synced(path: &Path, contents: &str) -> std::io::Result<()> {
+    let mut file = std::fs::File::create(path)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()
+}
+
+/// Shifts every backup one older, dropping the oldest, and copies the profile in as the newest.
+fn rotate_backups(path: &Path) -> std::io::Result<()> {
+    for n in (1..PROFILE_BACKUPS).rev() {
+        let backup = backup_path(path, n);
+        if backup.exists() {
+            std::fs::rename(&backup, backup_path(path, n + 1))?;
+        }
+    }
+    std::fs::copy(path, backup_path(path, 1)).map(|_| ())
+}
+
+/// The path the profile is loaded from and saved to.
+#[derive(Resource, Debug, Clone, PartialEq, Eq)]
+pub struct ProfilePath(pub PathBuf);
+
+/// An event requesting that the profile be saved.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SaveProfile;
+
+/// Saves the profile when asked, when a mission ends, when the settings change and when the app
+/// exits.
+#[allow(clippy::too_many_arguments)]
+fn save_profile(
+    mut save_events: EventReader<SaveProfile>,
+    mut end_events: EventReader<EndMission>,
+    mut exit_events: EventReader<AppExit>,
+    stash: Res<Stash>,
+    progress: Res<Progress>,
+    bindings: Option<Res<InputBindings>>,
+    camera: Option<Res<CameraProfiles>>,
+    path: Res<ProfilePath>,
+) {
+    let requests = save_events.read().count() + end_events.read().count();
+    let settings_changed = bindings
+        .as_ref()
+        .is_some_and(|bindings| bindings.is_changed() && !bindings.is_added())
+        || camera
+            .as_ref()
+            .is_some_and(|camera| camera.is_changed() && !camera.is_added());
+    if requests + exit_events.read().count() == 0 && !settings_changed {
+        return;
+    }
+    let profile = Profile {
+        stash: stash.clone(),
+        progress: progress.clone(),
+        settings: ProfileSettings {
+            bindings: bindings.map_or_else(InputBindings::default, |bindings| bindings.clone()),
+            camera: camera.map_or_else(CameraProfiles::default, |camera| camera.clone()),
+        },
+    };
+    if let Err(err) = profile.save(&path.0) {
+        warn!("Failed to save profile to {:?}: {}", path.0, err);
+    }
+}
+
+/// Loads the player profile on startup, restoring the stash, progress and settings, and saves
+/// it as the game goes on.
+///
+/// A profile that can't be loaded is replaced with a new one, but survives as a backup. A new
+/// profile starts from the input bindings and camera settings loaded from their own config files.
+/// From then on the profile owns them, and the bindings are no longer saved to their config file.
+///
+/// It isn't part of `GameplayPlugin`, so that the app decides where the profile lives. Add it
+/// after the plugins whose settings it takes over.
+pub struct ProfilePlugin {
+    /// The file the profile is loaded from and saved to.
+    pub path: PathBuf,
+}
+
+impl Default for ProfilePlugin {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from(DEFAULT_PROFILE_PATH),
+        }
+    }
+}
+
+impl Plugin for ProfilePlugin {
+    fn build(&self, app: &mut App) {
+        let profile = match Profile::recover(&self.path) {
+            Ok(profile) => Some(profile),
+            Err(ProfileError::Config(ConfigError::Io(err)))
+                if err.kind() == std::io::ErrorKind::NotFound =>
+            {
+                None
+            }
+            Err(err) => {
+                warn!("Starting a new profile instead of {:?}: {}", self.path, err);
+                None
+            }
+        };
+        let profile = profile.unwrap_or_else(|| Profile {
+            settings: ProfileSettings {
+                bindings: app
+                    .world
+                    .get_resource::<InputBindings>()
+                    .cloned()
+                    .unwrap_or_default(),
+                camera: app
+                    .world
+                    .get_resource::<CameraProfiles>()
+                    .cloned()
+                    .unwrap_or_default(),
+            },
+            ..default()
+        });
+        app.world.remove_resource::<InputBindingsPath>();
+        app.insert_resource(profile.settings.bindings)
+            .insert_resource(profile.settings.camera)
+            .insert_resource(profile.stash)
+            .insert_resource(profile.progress)
+            .insert_resource(ProfilePath(self.path.clone()))
+            .add_event::<SaveProfile>()
+            .add_event::<EndMission>()
+            .add_event::<AppExit>()
+            .add_systems(Last, save_profile);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_damaged_profiles_are_rejected() {
+        let mut profile = Profile::default();
+        profile.progress.currency = 250;
+        let source = profile.to_ron().unwrap();
+        assert_eq!(Profile::from_ron(&source).unwrap().progress.currency, 250);
+
+        let tampered = source.replace("250", "9250");
+        assert!(matches!(
+            Profile::from_ron(&tampered),
+            Err(ProfileError::ChecksumMismatch)
+        ));
+        let truncated = &source[..source.len() / 2];
+        assert!(matches!(
+            Profile::from_ron(truncated),
+            Err(ProfileError::ChecksumMismatch)
+        ));
+        let (_, body) = source.split_once('\n').unwrap();
+        assert!(matches!(
+            Profile::from_ron(body),
+            Err(ProfileError::InvalidHeader)
+        ));
+    }
+
+    #[test]
+    fn test_versions_are_checked_and_missing_fields_default() {
+        let body = "(progress: (currency: 40))";
+        let with_header =
+            |version: u32| format!("{HEADER_PREFIX}{version} {:016x}\n{body}", checksum(body));
+
+        let profile = Profile::from_ron(&with_header(PROFILE_FORMAT_VERSION)).unwrap();
+        assert_eq!(profile.progress.currency, 40);
+        assert!(profile.progress.story_flags.is_empty());
+        assert_eq!(profile.settings, ProfileSettings::default());
+
+        assert!(matches!(
+            Profile::from_ron(&with_header(PROFILE_FORMAT_VERSION + 1)),
+            Err(ProfileError::UnsupportedVersion(_))
+        ));
+    }
+
+    #[test]
+    fn test_version_1_profiles_are_upgraded() {
+        let body = r#"(
+    progress: (
+        currency: 75,
+        blueprints: [
+            (name: "Hut", pieces: []),
+            (name: "Tower", pieces: []),
+            (name: "Hut", pieces: [(kind: (0), material: (0), translation: (0.0, 0.0, 1.0))]),
+        ],
+        story_flags: ["met_the_broker"],
+    ),
+)"#;
+        let source = format!("{HEADER_PREFIX}1 {:016x}\n{body}", checksum(body));
+        let profile = Profile::from_ron(&source).unwrap();
+
+        assert_eq!(profile.progress.currency, 75);
+        assert!(profile.progress.story_flags.contains("met_the_broker"));
+        let names: Vec<_> = profile.progress.blueprints.keys().collect();
+        assert_eq!(names, ["Hut", "Tower"]);
+        assert_eq!(profile.progress.blueprints["Hut"].pieces.len(), 1);
+
+        // Saving writes the current format, which reads back the same.
+        let saved = profile.to_ron().unwrap();
+        assert!(saved.starts_with(&format!("{HEADER_PREFIX}{PROFILE_FORMAT_VERSION} ")));
+        assert_eq!(Profile::from_ron(&saved).unwrap(), profile);
+    }
+}
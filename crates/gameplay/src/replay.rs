@@ -0,0 +1,372 @@
+//! Recording of per-tick player input and deterministic replay of it.
+//!
+//! A `Replay` stores the world seed a session started from and the input of every `FixedUpdate`
+//! tick, along with the camera view movement is steered by. Playing it back into a fresh app with
+//! the same scene reproduces the same simulation; the player's own devices are ignored meanwhile.
+use crate::camera::{CameraPerspective, CameraRig};
+use crate::climbing::detect_climbable;
+use crate::movement::{gather_player_input, PlayerInput};
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use common::config::{self, ConfigError};
+use common::input::{ActionState, InputAction, SuspendDeviceInput};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use world::WorldSeed;
+
+/// The replay file format version; bumped whenever recorded data changes meaning.
+pub const REPLAY_FORMAT_VERSION: u32 = 1;
+
+/// An error while loading or saving a replay.
+#[derive(Debug)]
+pub enum ReplayError {
+    Config(ConfigError),
+    /// The file was written by an incompatible version of the game.
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Config(err) => write!(f, "{err}"),
+            ReplayError::UnsupportedVersion(version) => {
+                write!(f, "unsupported replay version {version}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<ConfigError> for ReplayError {
+    fn from(err: ConfigError) -> Self {
+        ReplayError::Config(err)
+    }
+}
+
+/// The camera view during a tick, which movement is steered by.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ReplayView {
+    pub yaw: f32,
+    pub pitch: f32,
+    pub aiming: bool,
+    pub perspective: CameraPerspective,
+}
+
+impl ReplayView {
+    /// Captures the view of a camera rig in a perspective.
+    pub fn capture(rig: &CameraRig, perspective: CameraPerspective) -> Self {
+        let (yaw, pitch) = rig.look_angles();
+        Self {
+            yaw,
+            pitch,
+            aiming: rig.is_aiming(),
+            perspective,
+        }
+    }
+
+    /// Points a camera rig the way it was pointed. The perspective is left to the caller, as
+    /// changing it is a state transition.
+    pub fn apply(&self, rig: &mut CameraRig) {
+        rig.set_look_angles(self.yaw, self.pitch);
+        rig.set_aiming(self.aiming);
+    }
+}
+
+/// The input of a single `FixedUpdate` tick.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayFrame {
+    pub move_direction: Vec2,
+    pub sprint: bool,
+    pub crouch: bool,
+    pub jump: bool,
+    /// The values of all actions that were active; absent actions were released.
+    pub actions: BTreeMap<InputAction, f32>,
+    /// The camera view, if there was a camera.
+    pub view: Option<ReplayView>,
+}
+
+impl ReplayFrame {
+    /// Captures the current tick's input and camera view.
+    pub fn capture(input: &PlayerInput, actions: &ActionState, view: Option<ReplayView>) -> Self {
+        Self {
+            move_direction: input.move_direction,
+            sprint: input.sprint,
+            crouch: input.crouch,
+            jump: input.jump,
+            actions: InputAction::ALL
+                .into_iter()
+                .map(|action| (action, actions.value(action)))
+                .filter(|(_, value)| *value > 0.0)
+                .collect(),
+            view,
+        }
+    }
+
+    /// Restores this frame's input. Its view is restored separately.
+    pub fn apply(&self, input: &mut PlayerInput, actions: &mut ActionState) {
+        input.move_direction = self.move_direction;
+        input.sprint = self.sprint;
+        input.crouch = self.crouch;
+        input.jump = self.jump;
+        for action in InputAction::ALL {
+            actions.set_value(action, self.actions.get(&action).copied().unwrap_or(0.0));
+        }
+    }
+}
+
+/// A recorded session: the world seed it started from and the input of every tick.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    pub world_seed: u64,
+    /// The fixed timestep the session ran at, in seconds.
+    pub timestep: f64,
+    pub frames: Vec<ReplayFrame>,
+}
+
+impl Replay {
+    /// Parses a replay from RON.
+    pub fn from_ron(source: &str) -> Result<Self, ReplayError> {
+        config::from_ron::<Self>(source)?.checked()
+    }
+
+    /// Serializes the replay to RON.
+    pub fn to_ron(&self) -> Result<String, ReplayError> {
+        Ok(config::to_ron(self)?)
+    }
+
+    /// Loads a replay file.
+    pub fn load(path: &Path) -> Result<Self, ReplayError> {
+        config::load::<Self>(path)?.checked()
+    }
+
+    /// Saves the replay to a file, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> Result<(), ReplayError> {
+        Ok(config::save(self, path)?)
+    }
+
+    /// Rejects replays written in another format version.
+    fn checked(self) -> Result<Self, ReplayError> {
+        if self.version != REPLAY_FORMAT_VERSION {
+            return Err(ReplayError::UnsupportedVersion(self.version));
+        }
+        Ok(self)
+    }
+}
+
+/// Records every tick's input while present.
+#[derive(Resource, Debug, Clone)]
+pub struct InputRecorder {
+    replay: Replay,
+    /// Where the replay is saved when the app exits, if anywhere.
+    pub save_path: Option<PathBuf>,
+}
+
+impl InputRecorder {
+    /// The number of ticks recorded so far.
+    pub fn frame_count(&self) -> usize {
+        self.replay.frames.len()
+    }
+}
+
+/// Feeds a replay's input into the simulation, one frame per tick, while present.
+#[derive(Resource, Debug, Clone)]
+pub struct InputPlayback {
+    replay: Replay,
+    cursor: usize,
+}
+
+impl InputPlayback {
+    /// Whether every recorded frame has been played.
+    pub fn is_finished(&self) -> bool {
+        self.cursor >= self.replay.frames.len()
+    }
+}
+
+/// An event sent on the tick after the last recorded frame was played.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReplayFinished;
+
+/// Starts recording input, capturing the current world seed and timestep.
+pub fn start_recording(world: &mut World, save_path: Option<PathBuf>) {
+    let replay = Replay {
+        version: REPLAY_FORMAT_VERSION,
+        world_seed: world.get_resource::<WorldSeed>().map_or(0, |seed| seed.0),
+        timestep: world
+            .get_resource::<Time<Fixed>>()
+            .map_or(0.0, |time| time.timestep().as_secs_f64()),
+        frames: Vec::new(),
+    };
+    world.insert_resource(InputRecorder { replay, save_path });
+}
+
+/// Stops recording and returns what was recorded, if a recording was running.
+pub fn stop_recording(world: &mut World) -> Option<Replay> {
+    world
+        .remove_resource::<InputRecorder>()
+        .map(|recorder| recorder.replay)
+}
+
+/// Reseeds the app from `replay` and starts feeding it its recorded input, in place of the
+/// player's devices.
+///
+/// Call this before the first update so that startup systems see the recorded world seed.
+pub fn start_playback(world: &mut World, replay: Replay) {
+    world.insert_resource(WorldSeed(replay.world_seed));
+    if replay.timestep > 0.0 {
+        if let Some(mut time) = world.get_resource_mut::<Time<Fixed>>() {
+            time.set_timestep_seconds(replay.timestep);
+        }
+    }
+    world.insert_resource(InputPlayback { replay, cursor: 0 });
+    world.insert_resource(SuspendDeviceInput);
+}
+
+/// Stops playing back a replay and hands input back to the player's devices.
+pub fn stop_playback(world: &mut World) {
+    world.remove_resource::<InputPlayback>();
+    world.remove_resource::<SuspendDeviceInput>();
+}
+
+/// Overwrites the gathered input and camera view with the next recorded frame.
+///
+/// The replayed actions toggle the perspective as they did while recording; a perspective that
+/// still differs from the recorded one is queued to be corrected.
+fn play_back_input(
+    mut playback: ResMut<InputPlayback>,
+    mut player_input: ResMut<PlayerInput>,
+    mut actions: ResMut<ActionState>,
+    mut rig_query: Query<&mut CameraRig>,
+    perspective: Option<Res<State<CameraPerspective>>>,
+    next_perspective: Option<ResMut<NextState<CameraPerspective>>>,
+    mut finished_events: EventWriter<ReplayFinished>,
+) {
+    let cursor = playback.cursor;
+    match playback.replay.frames.get(cursor) {
+        Some(frame) => {
+            frame.apply(&mut player_input, &mut actions);
+            if let (Some(view), Ok(mut rig)) = (frame.view, rig_query.get_single_mut()) {
+                view.apply(&mut rig);
+            }
+            let recorded = frame.view.map(|view| view.perspective);
+            if let (Some(recorded), Some(perspective), Some(mut next_perspective)) =
+                (recorded, perspective, next_perspective)
+            {
+                if recorded != *perspective.get() {
+                    next_perspective.set(recorded);
+                }
+            }
+        }
+        None => {
+            *player_input = PlayerInput::default();
+            *actions = ActionState::default();
+            if cursor == playback.replay.frames.len() {
+                finished_events.send(ReplayFinished);
+            }
+        }
+    }
+    playback.cursor += 1;
+}
+
+/// Appends this tick's input and camera view to the recording.
+fn record_input(
+    mut recorder: ResMut<InputRecorder>,
+    player_input: Res<PlayerInput>,
+    actions: Res<ActionState>,
+    rig_query: Query<&CameraRig>,
+    perspective: Option<Res<State<CameraPerspective>>>,
+) {
+    let view = rig_query.get_single().ok().map(|rig| {
+        let perspective = perspective.map_or(CameraPerspective::default(), |state| *state.get());
+        ReplayView::capture(rig, perspective)
+    });
+    recorder
+        .replay
+        .frames
+        .push(ReplayFrame::capture(&player_input, &actions, view));
+}
+
+/// Saves the running recording when the app exits.
+fn save_recording_on_exit(recorder: Res<InputRecorder>, mut exit_events: EventReader<AppExit>) {
+    if exit_events.read().next().is_none() {
+        return;
+    }
+    if let Some(path) = &recorder.save_path {
+        match recorder.replay.save(path) {
+            Ok(()) => info!("Saved replay to {:?}", path),
+            Err(err) => error!("Failed to save replay to {:?}: {}", path, err),
+        }
+    }
+}
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ReplayFinished>()
+            .add_event::<AppExit>()
+            .add_systems(
+                FixedUpdate,
+                (
+                    play_back_input.run_if(resource_exists::<InputPlayback>),
+                    record_input.run_if(resource_exists::<InputRecorder>),
+                )
+                    .chain()
+                    .after(gather_player_input)
+                    .before(detect_climbable),
+            )
+            .add_systems(
+                Last,
+                save_recording_on_exit.run_if(resource_exists::<InputRecorder>),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_round_trips_input() {
+        let input = PlayerInput {
+            move_direction: Vec2::new(0.6, 0.8),
+            sprint: true,
+            crouch: false,
+            jump: true,
+        };
+        let mut actions = ActionState::default();
+        actions.press(InputAction::Sprint);
+        actions.set_value(InputAction::MoveForward, 0.8);
+
+        let frame = ReplayFrame::capture(&input, &actions, None);
+        assert_eq!(frame.actions.len(), 2);
+
+        let mut restored_input = PlayerInput::default();
+        let mut restored_actions = ActionState::default();
+        restored_actions.press(InputAction::Fire);
+        frame.apply(&mut restored_input, &mut restored_actions);
+
+        assert_eq!(restored_input.move_direction, input.move_direction);
+        assert!(restored_input.sprint && restored_input.jump && !restored_input.crouch);
+        assert_eq!(restored_actions.value(InputAction::MoveForward), 0.8);
+        assert!(!restored_actions.pressed(InputAction::Fire));
+    }
+
+    #[test]
+    fn test_unsupported_version_is_rejected() {
+        let replay = Replay {
+            version: REPLAY_FORMAT_VERSION + 1,
+            world_seed: 2,
+            timestep: 1.0 / 60.0,
+            frames: Vec::new(),
+        };
+        let source = replay.to_ron().unwrap();
+        assert!(matches!(
+            Replay::from_ron(&source),
+            Err(ReplayError::UnsupportedVersion(_))
+        ));
+    }
+}
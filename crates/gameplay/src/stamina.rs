@@ -0,0 +1,267 @@
+//! Player stamina, which gates and is consumed by strenuous movement.
+use crate::inventory::encumbrance::Encumbrance;
+use crate::movement::{update_movement_state, MovementState};
+use crate::player::Player;
+use bevy::prelude::*;
+
+const DEFAULT_MAX_STAMINA: f32 = 100.0;
+/// Stamina regenerated per second once the regeneration delay has passed.
+const REGEN_RATE: f32 = 20.0;
+/// Seconds after the last stamina use before regeneration starts.
+const REGEN_DELAY: f32 = 1.0;
+/// The fraction of max stamina that must be regained before an exhausted player can exert again.
+const EXHAUSTION_RECOVERY_FRACTION: f32 = 0.3;
+
+/// Stamina drained per second while in a movement state.
+pub fn drain_rate(state: MovementState) -> f32 {
+    match state {
+        MovementState::Sprinting => 12.0,
+        MovementState::Climbing => 4.0,
+        _ => 0.0,
+    }
+}
+
+/// Stamina consumed once when entering a movement state.
+pub fn entry_cost(state: MovementState) -> f32 {
+    match state {
+        MovementState::Sliding => 15.0,
+        MovementState::Mantling => 20.0,
+        MovementState::Diving => 20.0,
+        _ => 0.0,
+    }
+}
+
+/// Events emitted when the player's stamina changes, for the UI.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub enum StaminaEvent {
+    /// The stamina value changed.
+    Changed { current: f32, max: f32 },
+    /// Stamina ran out, locking out strenuous movement.
+    Exhausted,
+    /// Enough stamina was regained to lift the exhaustion lockout.
+    Recovered,
+}
+
+/// A pool of stamina that strenuous movement states drain.
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct Stamina {
+    pub current: f32,
+    pub max: f32,
+    /// Seconds left before regeneration resumes.
+    regen_delay: f32,
+    exhausted: bool,
+}
+
+impl Default for Stamina {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_STAMINA)
+    }
+}
+
+impl Stamina {
+    /// Creates a full stamina pool.
+    pub fn new(max: f32) -> Self {
+        Self {
+            current: max,
+            max,
+            regen_delay: 0.0,
+            exhausted: false,
+        }
+    }
+
+    /// Whether stamina ran out and has not yet recovered.
+    pub fn is_exhausted(&self) -> bool {
+        self.exhausted
+    }
+
+    /// Whether there is enough stamina to enter a movement state.
+    pub fn can_enter(&self, state: MovementState) -> bool {
+        let cost = entry_cost(state);
+        if cost == 0.0 && drain_rate(state) == 0.0 {
+            return true;
+        }
+        !self.exhausted && self.current > 0.0 && self.current >= cost
+    }
+
+    /// Consumes stamina and resets the regeneration delay.
+    /// Returns `true` if this use exhausted the pool.
+    pub fn consume(&mut self, amount: f32) -> bool {
+        if amount <= 0.0 {
+            return false;
+        }
+        self.current = (self.current - amount).max(0.0);
+        self.regen_delay = REGEN_DELAY;
+        if self.current == 0.0 && !self.exhausted {
+            self.exhausted = true;
+            return true;
+        }
+        false
+    }
+
+    /// Advances stamina by `dt` seconds spent in `state`, draining or regenerating it.
+    pub fn tick(&mut self, state: MovementState, dt: f32) -> Option<StaminaEvent> {
+        self.tick_scaled(state, 1.0, dt)
+    }
+
+    /// Like `tick`, with the drain scaled by `drain_multiplier`, as when carrying a heavy load.
+    pub fn tick_scaled(
+        &mut self,
+        state: MovementState,
+        drain_multiplier: f32,
+        dt: f32,
+    ) -> Option<StaminaEvent> {
+        let drain = drain_rate(state) * drain_multiplier;
+        if drain > 0.0 {
+            return self.consume(drain * dt).then_some(StaminaEvent::Exhausted);
+        }
+
+        if self.regen_delay > 0.0 {
+            self.regen_delay = (self.regen_delay - dt).max(0.0);
+            return None;
+        }
+        self.current = (self.current + REGEN_RATE * dt).min(self.max);
+        if self.exhausted && self.current >= self.max * EXHAUSTION_RECOVERY_FRACTION {
+            self.exhausted = false;
+            return Some(StaminaEvent::Recovered);
+        }
+        None
+    }
+}
+
+/// Drains or regenerates the player's stamina according to the current movement state.
+fn update_stamina(
+    mut player_query: Query<(&mut Stamina, Option<&Encumbrance>), With<Player>>,
+    movement_state: Res<State<MovementState>>,
+    time: Res<Time>,
+    mut events: EventWriter<StaminaEvent>,
+) {
+    for (mut stamina, encumbrance) in player_query.iter_mut() {
+        let before = stamina.current;
+        let drain_multiplier = encumbrance.map_or(1.0, |encumbrance| {
+            encumbrance.tier.stamina_drain_multiplier()
+        });
+        let event = stamina.tick_scaled(
+            *movement_state.get(),
+            drain_multiplier,
+            time.delta_seconds(),
+        );
+        if stamina.current != before {
+            events.send(StaminaEvent::Changed {
+                current: stamina.current,
+                max: stamina.max,
+            });
+        }
+        if let Some(event) = event {
+            events.send(event);
+        }
+    }
+}
+
+/// Charges the one-off stamina cost of the movement state that was just entered.
+fn charge_entry_cost(
+    mut player_query: Query<&mut Stamina, With<Player>>,
+    movement_state: Res<State<MovementState>>,
+    mut events: EventWriter<StaminaEvent>,
+) {
+    let cost = entry_cost(*movement_state.get());
+    for mut stamina in player_query.iter_mut() {
+        let exhausted = stamina.consume(cost);
+        events.send(StaminaEvent::Changed {
+            current: stamina.current,
+            max: stamina.max,
+        });
+        if exhausted {
+            events.send(StaminaEvent::Exhausted);
+        }
+    }
+}
+
+pub struct StaminaPlugin;
+
+impl Plugin for StaminaPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<StaminaEvent>()
+            .add_systems(OnEnter(MovementState::Sliding), charge_entry_cost)
+            .add_systems(OnEnter(MovementState::Mantling), charge_entry_cost)
+            .add_systems(OnEnter(MovementState::Diving), charge_entry_cost)
+            .add_systems(FixedUpdate, update_stamina.after(update_movement_state));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sprinting_drains_until_exhausted() {
+        let mut stamina = Stamina::new(12.0);
+        assert!(stamina.can_enter(MovementState::Sprinting));
+
+        // Sprinting drains 12 per second, so the pool lasts about one second.
+        let events: Vec<_> = (0..65)
+            .filter_map(|_| stamina.tick(MovementState::Sprinting, 1.0 / 60.0))
+            .collect();
+
+        assert_eq!(events, vec![StaminaEvent::Exhausted]);
+        assert!(stamina.is_exhausted());
+        assert!(!stamina.can_enter(MovementState::Sprinting));
+        assert!(stamina.can_enter(MovementState::Walking));
+    }
+
+    #[test]
+    fn test_regeneration_waits_for_delay() {
+        let mut stamina = Stamina::new(100.0);
+        stamina.consume(50.0);
+
+        stamina.tick(MovementState::Idle, REGEN_DELAY * 0.5);
+        assert_eq!(stamina.current, 50.0);
+
+        stamina.tick(MovementState::Idle, REGEN_DELAY * 0.5);
+        stamina.tick(MovementState::Idle, 1.0);
+        assert_eq!(stamina.current, 50.0 + REGEN_RATE);
+    }
+
+    #[test]
+    fn test_exhaustion_lockout_lifts_after_recovery() {
+        let mut stamina = Stamina::new(100.0);
+        assert!(stamina.consume(100.0));
+
+        stamina.tick(MovementState::Idle, REGEN_DELAY);
+        // 20/s for one second only reaches 20%, which is below the recovery threshold.
+        assert_eq!(stamina.tick(MovementState::Idle, 1.0), None);
+        assert!(!stamina.can_enter(MovementState::Sprinting));
+
+        assert_eq!(
+            stamina.tick(MovementState::Idle, 0.6),
+            Some(StaminaEvent::Recovered)
+        );
+        assert!(stamina.can_enter(MovementState::Sprinting));
+    }
+
+    #[test]
+    fn test_entry_cost_gates_states() {
+        let mut stamina = Stamina::new(100.0);
+        stamina.consume(100.0 - entry_cost(MovementState::Sliding) + 1.0);
+        assert!(!stamina.can_enter(MovementState::Sliding));
+        assert!(stamina.can_enter(MovementState::Sprinting));
+        assert!(!stamina.can_enter(MovementState::Mantling));
+        assert!(!stamina.can_enter(MovementState::Diving));
+    }
+
+    #[test]
+    fn test_heavy_loads_drain_faster() {
+        let mut light = Stamina::new(100.0);
+        let mut heavy = Stamina::new(100.0);
+        light.tick(MovementState::Sprinting, 1.0);
+        heavy.tick_scaled(MovementState::Sprinting, 2.0, 1.0);
+        assert_eq!(100.0 - heavy.current, 2.0 * (100.0 - light.current));
+
+        // Regeneration isn't affected.
+        heavy.tick_scaled(MovementState::Idle, 2.0, REGEN_DELAY);
+        heavy.tick_scaled(MovementState::Idle, 2.0, 1.0);
+        assert_eq!(
+            heavy.current,
+            100.0 - 2.0 * drain_rate(MovementState::Sprinting) + REGEN_RATE
+        );
+    }
+}
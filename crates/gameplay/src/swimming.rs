@@ -0,0 +1,285 @@
+//! Water detection, buoyant swimming movement and breath/drowning.
+use crate::health::{DamageEvent, DamageSource};
+use crate::movement::approach;
+use crate::player::{Player, PLAYER_FEET_OFFSET};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use bevy_rapier3d::rapier::math::Isometry;
+use world::{MaterialRegistry, WorldData};
+
+/// Swimming speed when fully submerged, in m/s.
+pub const SWIM_SPEED: f32 = 3.5;
+/// The fraction of `SWIM_SPEED` available when barely submerged.
+const SHALLOW_SWIM_SPEED_FRACTION: f32 = 0.6;
+/// How quickly the swimming velocity approaches the input direction, in m/s².
+const SWIM_ACCELERATION: f32 = 10.0;
+/// The fraction of vertical velocity lost per second to water resistance.
+const WATER_DRAG: f32 = 2.0;
+/// The submersion at which buoyancy cancels out gravity, leaving the head above water.
+pub const TREAD_SUBMERSION: f32 = 0.75;
+/// The submersion at which the player starts swimming.
+pub const SWIM_ENTER_SUBMERSION: f32 = 0.5;
+/// Below this submersion the player stops swimming and walks again.
+pub const SWIM_EXIT_SUBMERSION: f32 = 0.3;
+/// Above this submersion the player's head is underwater and breath runs down.
+pub const HEAD_SUBMERSION: f32 = 0.9;
+
+const DEFAULT_MAX_BREATH: f32 = 15.0;
+/// Breath regained per second with the head above water.
+const BREATH_REGEN_RATE: f32 = 5.0;
+/// Damage per second while out of breath.
+pub const DROWNING_DAMAGE_PER_SECOND: f32 = 10.0;
+
+/// A marker component for water volumes (sensor colliders) the player can swim in.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct WaterVolume;
+
+/// How deep the player is in water, refreshed every tick.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq)]
+pub struct Submersion {
+    /// The fraction of the player's height that is underwater, `0.0..=1.0`.
+    pub depth: f32,
+}
+
+impl Submersion {
+    /// Whether the player's head is underwater.
+    pub fn head_underwater(&self) -> bool {
+        self.depth >= HEAD_SUBMERSION
+    }
+}
+
+/// How long the player can stay underwater before drowning.
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct Breath {
+    /// Remaining breath, in seconds.
+    pub current: f32,
+    pub max: f32,
+    /// Drowning damage accumulated but not yet sent as a whole `DamageEvent`.
+    pending_damage: f32,
+}
+
+impl Default for Breath {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_BREATH)
+    }
+}
+
+impl Breath {
+    /// Creates a full breath meter lasting `max` seconds.
+    pub fn new(max: f32) -> Self {
+        Self {
+            current: max,
+            max,
+            pending_damage: 0.0,
+        }
+    }
+
+    /// Advances the meter by `dt` seconds and returns drowning damage due this tick, if any.
+    ///
+    /// Drowning damage is delivered in whole points so `DamageEvent`s aren't sent every tick.
+    pub fn tick(&mut self, head_underwater: bool, dt: f32) -> Option<f32> {
+        if !head_underwater {
+            self.current = (self.current + BREATH_REGEN_RATE * dt).min(self.max);
+            self.pending_damage = 0.0;
+            return None;
+        }
+        if self.current > 0.0 {
+            self.current = (self.current - dt).max(0.0);
+            return None;
+        }
+        self.pending_damage += DROWNING_DAMAGE_PER_SECOND * dt;
+        if self.pending_damage >= 1.0 {
+            let damage = self.pending_damage.floor();
+            self.pending_damage -= damage;
+            Some(damage)
+        } else {
+            None
+        }
+    }
+}
+
+/// The length of the overlap between `[a_min, a_max]` and `[b_min, b_max]`.
+fn overlap(a_min: f32, a_max: f32, b_min: f32, b_max: f32) -> f32 {
+    (a_max.min(b_max) - a_min.max(b_min)).max(0.0)
+}
+
+/// The maximum swimming speed at a given submersion; deeper water allows full strokes.
+pub fn swim_speed(submersion: f32) -> f32 {
+    SWIM_SPEED
+        * (SHALLOW_SWIM_SPEED_FRACTION
+            + (1.0 - SHALLOW_SWIM_SPEED_FRACTION) * submersion.clamp(0.0, 1.0))
+}
+
+/// Advances a swimming velocity by one tick.
+///
+/// `wish_direction` is the 3D, camera-directed input direction. Buoyancy scales with
+/// `submersion` and balances `gravity` at `TREAD_SUBMERSION`, so an idle swimmer treads water
+/// at the surface.
+pub fn step_swim_velocity(
+    current: Vec3,
+    wish_direction: Vec3,
+    submersion: f32,
+    gravity: f32,
+    dt: f32,
+) -> Vec3 {
+    let max_delta = SWIM_ACCELERATION * dt;
+    let mut velocity = if wish_direction == Vec3::ZERO {
+        // Without input, horizontal momentum bleeds off and buoyancy alone moves the swimmer.
+        let horizontal = Vec3::new(current.x, 0.0, current.z);
+        approach(horizontal, Vec3::ZERO, max_delta) + Vec3::Y * current.y
+    } else {
+        let target = wish_direction.clamp_length_max(1.0) * swim_speed(submersion);
+        approach(current, target, max_delta)
+    };
+
+    let buoyancy = -gravity * submersion.clamp(0.0, 1.0) / TREAD_SUBMERSION;
+    velocity.y += (gravity + buoyancy) * dt;
+    velocity.y *= (1.0 - WATER_DRAG * dt).max(0.0);
+    velocity
+}
+
+/// The height of the column `[feet_y, head_y]` at `position` that is inside fluid voxels.
+fn voxel_submerged_height(
+    world: &WorldData,
+    materials: &MaterialRegistry,
+    position: Vec3,
+    feet_y: f32,
+    head_y: f32,
+) -> f32 {
+    let x = position.x.floor() as i32;
+    let z = position.z.floor() as i32;
+    (feet_y.floor() as i32..=head_y.floor() as i32)
+        .filter(|&y| {
+            world
+                .get_voxel(IVec3::new(x, y, z))
+                .and_then(|voxel| materials.get(voxel.0))
+                .is_some_and(|material| material.is_fluid)
+        })
+        .map(|y| overlap(feet_y, head_y, y as f32, y as f32 + 1.0))
+        .sum()
+}
+
+/// Refreshes the player's `Submersion` from fluid voxels and `WaterVolume` sensors.
+///
+/// Volumes are tested along the vertical line through the player's center, which is exact for
+/// the upright boxes and cylinders used for pools and rivers.
+pub(crate) fn detect_submersion(
+    mut player_query: Query<(&Transform, &mut Submersion), With<Player>>,
+    water_volumes: Query<(&GlobalTransform, &Collider), With<WaterVolume>>,
+    world_data: Option<Res<WorldData>>,
+    materials: Option<Res<MaterialRegistry>>,
+) {
+    for (transform, mut submersion) in player_query.iter_mut() {
+        let feet_y = transform.translation.y - PLAYER_FEET_OFFSET;
+        let head_y = transform.translation.y + PLAYER_FEET_OFFSET;
+        let height = head_y - feet_y;
+
+        let mut submerged: f32 = 0.0;
+        for (volume_transform, volume_collider) in water_volumes.iter() {
+            let (_, rotation, translation) = volume_transform.to_scale_rotation_translation();
+            let iso: Isometry<f32> = (translation, rotation).into();
+            let aabb = volume_collider.raw.compute_aabb(&iso);
+            let depth = overlap(feet_y, head_y, aabb.mins.y, aabb.maxs.y);
+            if depth <= 0.0 {
+                continue;
+            }
+            let probe_y = feet_y.max(aabb.mins.y) + depth * 0.5;
+            let probe = Vec3::new(transform.translation.x, probe_y, transform.translation.z);
+            if volume_collider.raw.contains_point(&iso, &probe.into()) {
+                submerged = submerged.max(depth);
+            }
+        }
+
+        if let (Some(world), Some(materials)) = (world_data.as_deref(), materials.as_deref()) {
+            submerged = submerged.max(voxel_submerged_height(
+                world,
+                materials,
+                transform.translation,
+                feet_y,
+                head_y,
+            ));
+        }
+
+        submersion.depth = (submerged / height).clamp(0.0, 1.0);
+    }
+}
+
+/// Runs down the player's breath while their head is underwater and applies drowning damage.
+fn update_breath(
+    mut player_query: Query<(Entity, &Submersion, &mut Breath), With<Player>>,
+    time: Res<Time>,
+    mut damage_events: EventWriter<DamageEvent>,
+) {
+    for (entity, submersion, mut breath) in player_query.iter_mut() {
+        if let Some(amount) = breath.tick(submersion.head_underwater(), time.delta_seconds()) {
+            damage_events.send(DamageEvent {
+                target: entity,
+                amount,
+                source: DamageSource::Drowning,
+            });
+        }
+    }
+}
+
+pub struct SwimmingPlugin;
+
+impl Plugin for SwimmingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DamageEvent>()
+            .add_systems(FixedUpdate, update_breath.after(detect_submersion));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRAVITY: f32 = -9.81;
+
+    #[test]
+    fn test_idle_swimmer_treads_at_the_surface() {
+        // Buoyancy exactly cancels gravity at the treading depth.
+        let velocity = step_swim_velocity(Vec3::ZERO, Vec3::ZERO, TREAD_SUBMERSION, GRAVITY, 0.1);
+        assert!(velocity.length() < 1e-5);
+
+        // Deeper than that, the swimmer floats up; shallower, they sink.
+        assert!(step_swim_velocity(Vec3::ZERO, Vec3::ZERO, 1.0, GRAVITY, 0.1).y > 0.0);
+        assert!(step_swim_velocity(Vec3::ZERO, Vec3::ZERO, 0.5, GRAVITY, 0.1).y < 0.0);
+    }
+
+    #[test]
+    fn test_swim_speed_scales_with_depth() {
+        assert!(swim_speed(0.3) < swim_speed(0.8));
+        assert_eq!(swim_speed(1.0), SWIM_SPEED);
+    }
+
+    #[test]
+    fn test_voxel_submersion_is_continuous() {
+        let mut world = WorldData::default();
+        let materials = MaterialRegistry::default();
+        for y in 0..2 {
+            world.set_voxel(IVec3::new(0, y, 0), world::Voxel(world::MaterialId::WATER));
+        }
+
+        let position = Vec3::new(0.5, 1.5, 0.5);
+        let submerged = voxel_submerged_height(&world, &materials, position, 0.5, 3.5);
+        assert!((submerged - 1.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_breath_runs_out_then_drowns() {
+        let mut breath = Breath::new(1.0);
+        let dt = 0.1;
+
+        let damage: f32 = (0..10).filter_map(|_| breath.tick(true, dt)).sum();
+        assert_eq!(damage, 0.0);
+        assert!(breath.current < 1e-5);
+
+        // Out of breath: 10 damage per second, delivered in whole points.
+        let damage: f32 = (0..10).filter_map(|_| breath.tick(true, dt)).sum();
+        assert!((damage - DROWNING_DAMAGE_PER_SECOND).abs() <= 1.0);
+
+        breath.tick(false, 1.0);
+        assert!(breath.current > 0.0);
+    }
+}
@@ -0,0 +1,1054 @@
+use bevy::input::InputPlugin;
+use bevy::prelude::*;
+use bevy::scene::ScenePlugin;
+use bevy::time::TimeUpdateStrategy;
+use bevy_rapier3d::prelude::*;
+use common::input::{ActionState, InputAction};
+use gameplay::building::blueprint::{
+    Blueprint, BlueprintCaptured, BlueprintCompleted, BlueprintPiece, BlueprintPolicy,
+    BlueprintStaging, BlueprintStarted, CaptureBlueprint, PlaceBlueprint,
+};
+use gameplay::building::catalogue::BuildPieceCatalogue;
+use gameplay::building::damage::{
+    BuildDamageState, BuildDamageStateChanged, BuildPieceDamage, BuildPieceDestroyed,
+    BuildPieceRepair, BuildPieceRepaired, BuildPieceUpgrade, BuildPieceUpgraded,
+};
+use gameplay::building::door::{Door, DoorChanged, DoorLeaf, LockDoor, ToggleDoor};
+use gameplay::building::placement::BuildSlot;
+use gameplay::building::preview::{BuildGhost, BuildMode};
+use gameplay::building::support::BuildPieceCollapsed;
+use gameplay::building::trap::TrapTriggered;
+use gameplay::building::{
+    BuildEvent, BuildMaterialRegistry, BuildPiece, BuildPieceKind, BuildRejectReason,
+    BuildRejected, BuildingPlugin, MaterialId,
+};
+use gameplay::camera::{CameraPlugin, MainCamera};
+use gameplay::health::{DamageEvent, DamageSource, Health, HealthPlugin};
+use gameplay::inventory::items::ItemRegistry;
+use gameplay::inventory::stack::{ItemContainer, ItemStack};
+use gameplay::inventory::{Inventory, ItemId};
+use gameplay::movement::MovementPlugin;
+use gameplay::player::{Player, PlayerPlugin};
+use std::f32::consts::FRAC_PI_2;
+use std::time::Duration;
+use world::{MaterialRegistry, Voxel, WorldData};
+
+/// A minimal app with building and a player carrying `wood` pieces of wood.
+fn setup_build_app(wood: usize) -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, BuildingPlugin));
+    // Normally inserted by `InventoryPlugin`.
+    app.init_resource::<ItemRegistry>();
+    let wood_item = wood_item(&app);
+    let mut inventory = Inventory::default();
+    if wood > 0 {
+        let registry = ItemRegistry::default();
+        inventory
+            .add(ItemStack::new(wood_item, wood as u32), &registry)
+            .unwrap();
+    }
+    app.world.spawn((Player, inventory));
+    app
+}
+
+/// How many items a piece of `kind` takes to build.
+fn cost(kind: BuildPieceKind) -> usize {
+    BuildPieceCatalogue::default().get(kind).unwrap().cost
+}
+
+fn wood_item(app: &App) -> ItemId {
+    app.world
+        .resource::<BuildMaterialRegistry>()
+        .get(MaterialId::WOOD)
+        .unwrap()
+        .item
+}
+
+/// Puts `count` of `item` into the player's inventory.
+fn give(app: &mut App, item: ItemId, count: usize) {
+    let registry = ItemRegistry::default();
+    let mut query = app.world.query_filtered::<&mut Inventory, With<Player>>();
+    query
+        .single_mut(&mut app.world)
+        .add(ItemStack::new(item, count as u32), &registry)
+        .unwrap();
+}
+
+fn item_count(app: &mut App, item: ItemId) -> usize {
+    let mut query = app.world.query_filtered::<&Inventory, With<Player>>();
+    query.single(&app.world).count(item)
+}
+
+/// Sends `event` and runs a frame.
+fn send<E: Event>(app: &mut App, event: E) {
+    app.world.send_event(event);
+    app.update();
+}
+
+fn drain<E: Event + Clone>(app: &mut App) -> Vec<E> {
+    app.world.resource_mut::<Events<E>>().drain().collect()
+}
+
+/// Builds a single grounded floor and returns it.
+fn build_floor(app: &mut App) -> Entity {
+    build(app, BuildPieceKind::FLOOR, Vec3::ZERO, Quat::IDENTITY);
+    let mut query = app.world.query_filtered::<Entity, With<BuildPiece>>();
+    query.single(&app.world)
+}
+
+fn damage(app: &mut App, target: Entity, amount: f32, source: DamageSource) {
+    app.world.send_event(BuildPieceDamage {
+        target,
+        amount,
+        source,
+    });
+    app.update();
+}
+
+fn wood_left(app: &mut App) -> usize {
+    let wood = wood_item(app);
+    let mut query = app.world.query_filtered::<&Inventory, With<Player>>();
+    query.single(&app.world).count(wood)
+}
+
+/// Fills the voxels between `min` and `max`, inclusive, with stone.
+fn fill_stone(app: &mut App, min: IVec3, max: IVec3) {
+    let mut world_data = WorldData::default();
+    for x in min.x..=max.x {
+        for y in min.y..=max.y {
+            for z in min.z..=max.z {
+                world_data.set_voxel(IVec3::new(x, y, z), Voxel(world::MaterialId::STONE));
+            }
+        }
+    }
+    app.insert_resource(world_data);
+    app.init_resource::<MaterialRegistry>();
+}
+
+fn build(app: &mut App, piece: BuildPieceKind, position: Vec3, rotation: Quat) {
+    app.world.send_event(BuildEvent {
+        piece,
+        material: MaterialId::WOOD,
+        position,
+        rotation,
+    });
+    app.update();
+}
+
+/// Presses and releases `action` over one frame.
+fn tap(app: &mut App, action: InputAction) {
+    app.world.resource_mut::<ActionState>().press(action);
+    app.update();
+    app.world.resource_mut::<ActionState>().release(action);
+}
+
+/// Flat stone ground with its surface at y = 0, and a camera above it aiming at `target`.
+fn setup_aim(app: &mut App, target: Vec3) {
+    fill_stone(app, IVec3::new(-16, -2, -16), IVec3::new(16, -1, 16));
+    let transform = Transform::from_xyz(0.0, 4.0, 4.0).looking_at(target, Vec3::Y);
+    app.world
+        .spawn((MainCamera, transform, GlobalTransform::from(transform)));
+}
+
+fn ghost(app: &mut App) -> Option<(BuildPieceKind, Option<BuildRejectReason>, Transform)> {
+    let mut query = app.world.query::<(&BuildGhost, &Transform)>();
+    query
+        .get_single(&app.world)
+        .ok()
+        .map(|(ghost, transform)| (ghost.piece, ghost.rejection, *transform))
+}
+
+fn rejections(app: &mut App) -> Vec<BuildRejectReason> {
+    app.world
+        .resource_mut::<Events<BuildRejected>>()
+        .drain()
+        .map(|event| event.reason)
+        .collect()
+}
+
+fn pieces(app: &mut App) -> Vec<(BuildPiece, Transform)> {
+    let mut query = app.world.query::<(&BuildPiece, &Transform)>();
+    query
+        .iter(&app.world)
+        .map(|(piece, transform)| (piece.clone(), *transform))
+        .collect()
+}
+
+#[test]
+fn test_build_event_spawns_snapped_piece_and_pays() {
+    let mut app = setup_build_app(5);
+    build(
+        &mut app,
+        BuildPieceKind::FLOOR,
+        Vec3::new(0.6, 0.0, -0.3),
+        Quat::from_rotation_y(0.2),
+    );
+
+    assert_eq!(rejections(&mut app), []);
+    let pieces = pieces(&mut app);
+    assert_eq!(pieces.len(), 1);
+    let (piece, transform) = &pieces[0];
+    assert_eq!(piece.kind, BuildPieceKind::FLOOR);
+    assert_eq!(piece.material_id, MaterialId::WOOD);
+    assert_eq!(piece.health, 100.0);
+    assert_eq!(transform.translation, Vec3::ZERO);
+    assert_eq!(transform.rotation, Quat::IDENTITY);
+    assert_eq!(wood_left(&mut app), 5 - cost(BuildPieceKind::FLOOR));
+
+    let mut query = app
+        .world
+        .query_filtered::<(), (With<BuildPiece>, With<Collider>, With<RigidBody>)>();
+    assert_eq!(query.iter(&app.world).count(), 1);
+}
+
+#[test]
+fn test_build_event_builds_in_requested_material() {
+    let mut app = setup_build_app(5);
+    let metal = app
+        .world
+        .resource::<BuildMaterialRegistry>()
+        .get(MaterialId::METAL)
+        .unwrap()
+        .clone();
+    give(&mut app, metal.item, cost(BuildPieceKind::FLOOR));
+
+    send(
+        &mut app,
+        BuildEvent {
+            piece: BuildPieceKind::FLOOR,
+            material: MaterialId::METAL,
+            position: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+        },
+    );
+    assert_eq!(rejections(&mut app), []);
+    let pieces = pieces(&mut app);
+    assert_eq!(pieces.len(), 1);
+    assert_eq!(pieces[0].0.material_id, MaterialId::METAL);
+    assert_eq!(pieces[0].0.health, metal.base_hp);
+    assert_eq!(item_count(&mut app, metal.item), 0);
+    assert_eq!(wood_left(&mut app), 5);
+
+    send(
+        &mut app,
+        BuildEvent {
+            piece: BuildPieceKind::FLOOR,
+            material: MaterialId(999),
+            position: Vec3::new(2.0, 0.0, 0.0),
+            rotation: Quat::IDENTITY,
+        },
+    );
+    assert_eq!(rejections(&mut app), [BuildRejectReason::UnknownPiece]);
+}
+
+#[test]
+fn test_occupied_slot_is_rejected() {
+    let mut app = setup_build_app(10);
+    build(&mut app, BuildPieceKind::FLOOR, Vec3::ZERO, Quat::IDENTITY);
+    // A ramp would take up the same cell as the floor.
+    build(
+        &mut app,
+        BuildPieceKind::RAMP,
+        Vec3::new(0.4, 0.5, 0.4),
+        Quat::IDENTITY,
+    );
+    assert_eq!(rejections(&mut app), [BuildRejectReason::Occupied]);
+
+    // Walls built from either side of the same edge collide, even within a single frame.
+    app.world.send_event(BuildEvent {
+        piece: BuildPieceKind::WALL,
+        material: MaterialId::WOOD,
+        position: Vec3::ZERO,
+        rotation: Quat::from_rotation_y(FRAC_PI_2),
+    });
+    app.world.send_event(BuildEvent {
+        piece: BuildPieceKind::WALL,
+        material: MaterialId::WOOD,
+        position: Vec3::new(2.0, 0.0, 0.0),
+        rotation: Quat::from_rotation_y(-FRAC_PI_2),
+    });
+    app.update();
+    assert_eq!(rejections(&mut app), [BuildRejectReason::Occupied]);
+
+    assert_eq!(pieces(&mut app).len(), 2);
+    let mut query = app.world.query::<&BuildSlot>();
+    assert_eq!(query.iter(&app.world).count(), 2);
+    // Rejected pieces aren't paid for.
+    let spent = cost(BuildPieceKind::FLOOR) + cost(BuildPieceKind::WALL);
+    assert_eq!(wood_left(&mut app), 10 - spent);
+}
+
+#[test]
+fn test_terrain_blocks_placement() {
+    let mut app = setup_build_app(10);
+    // Flat ground with its surface at y = 0, and a boulder in the cell at x = 4.
+    fill_stone(&mut app, IVec3::new(-8, -2, -8), IVec3::new(8, -1, 8));
+    let mut world_data = app.world.resource_mut::<WorldData>();
+    world_data.set_voxel(IVec3::new(4, 0, 0), Voxel(world::MaterialId::STONE));
+
+    // Resting on the ground is fine.
+    build(&mut app, BuildPieceKind::FLOOR, Vec3::ZERO, Quat::IDENTITY);
+    assert_eq!(rejections(&mut app), []);
+
+    build(
+        &mut app,
+        BuildPieceKind::FLOOR,
+        Vec3::new(4.0, 0.0, 0.0),
+        Quat::IDENTITY,
+    );
+    assert_eq!(rejections(&mut app), [BuildRejectReason::BlockedByTerrain]);
+
+    // A level lower, the floor would be buried in the ground.
+    build(
+        &mut app,
+        BuildPieceKind::FLOOR,
+        Vec3::new(-4.0, -1.0, 0.0),
+        Quat::IDENTITY,
+    );
+    assert_eq!(rejections(&mut app), [BuildRejectReason::BlockedByTerrain]);
+    assert_eq!(pieces(&mut app).len(), 1);
+}
+
+#[test]
+fn test_insufficient_resources_is_rejected() {
+    let mut app = setup_build_app(cost(BuildPieceKind::WALL) - 1);
+    build(&mut app, BuildPieceKind::WALL, Vec3::ZERO, Quat::IDENTITY);
+
+    assert_eq!(
+        rejections(&mut app),
+        [BuildRejectReason::InsufficientResources]
+    );
+    assert!(pieces(&mut app).is_empty());
+    // Nothing is taken for a piece that isn't built.
+    assert_eq!(wood_left(&mut app), cost(BuildPieceKind::WALL) - 1);
+}
+
+#[test]
+fn test_build_ghost_previews_aimed_placement() {
+    let mut app = setup_build_app(cost(BuildPieceKind::FLOOR));
+    setup_aim(&mut app, Vec3::new(0.4, 0.0, -0.4));
+    app.update();
+    assert!(ghost(&mut app).is_none());
+
+    tap(&mut app, InputAction::Build);
+    let (piece, rejection, transform) = ghost(&mut app).unwrap();
+    assert_eq!(piece, BuildPieceKind::FLOOR);
+    assert_eq!(rejection, None);
+    assert_eq!(transform.translation, Vec3::ZERO);
+
+    // Taking the player's wood turns the preview invalid.
+    let wood = wood_item(&app);
+    let mut query = app.world.query_filtered::<&mut Inventory, With<Player>>();
+    query.single_mut(&mut app.world).remove(wood, 1).unwrap();
+    app.update();
+    let (_, rejection, _) = ghost(&mut app).unwrap();
+    assert_eq!(rejection, Some(BuildRejectReason::InsufficientResources));
+    // Previews don't build anything.
+    assert!(pieces(&mut app).is_empty());
+}
+
+#[test]
+fn test_build_ghost_rotates_and_cycles_pieces() {
+    let mut app = setup_build_app(10);
+    setup_aim(&mut app, Vec3::new(0.4, 0.0, -0.4));
+    tap(&mut app, InputAction::Build);
+
+    tap(&mut app, InputAction::RotateBuildPiece);
+    let (_, _, transform) = ghost(&mut app).unwrap();
+    assert!(transform
+        .rotation
+        .abs_diff_eq(Quat::from_rotation_y(FRAC_PI_2), 1e-5));
+
+    tap(&mut app, InputAction::CycleBuildPiece);
+    let (piece, _, transform) = ghost(&mut app).unwrap();
+    assert_eq!(
+        piece,
+        app.world
+            .resource::<BuildPieceCatalogue>()
+            .next(BuildPieceKind::FLOOR)
+    );
+    assert!(transform
+        .rotation
+        .abs_diff_eq(Quat::from_rotation_y(FRAC_PI_2), 1e-5));
+    assert_eq!(app.world.resource::<BuildMode>().piece, piece);
+}
+
+#[test]
+fn test_confirming_build_ghost_places_piece() {
+    let mut app = setup_build_app(10);
+    setup_aim(&mut app, Vec3::new(0.4, 0.0, -0.4));
+    // Firing outside build mode builds nothing.
+    tap(&mut app, InputAction::Fire);
+    assert!(pieces(&mut app).is_empty());
+
+    tap(&mut app, InputAction::Build);
+    let (_, _, preview) = ghost(&mut app).unwrap();
+    tap(&mut app, InputAction::Fire);
+    assert_eq!(rejections(&mut app), []);
+    let pieces = pieces(&mut app);
+    assert_eq!(pieces.len(), 1);
+    assert_eq!(pieces[0].1, preview);
+
+    // The spot is now taken, which the preview shows.
+    app.update();
+    let (_, rejection, _) = ghost(&mut app).unwrap();
+    assert_eq!(rejection, Some(BuildRejectReason::Occupied));
+
+    tap(&mut app, InputAction::Build);
+    assert!(ghost(&mut app).is_none());
+    assert!(!app.world.resource::<BuildMode>().active);
+}
+
+/// Builds a grounded wall with floors on top of it reaching out along +X, one per frame as the
+/// player would, returning the reasons any floor was rejected.
+fn build_cantilever(app: &mut App, floors: usize) -> Vec<BuildRejectReason> {
+    build(app, BuildPieceKind::WALL, Vec3::ZERO, Quat::IDENTITY);
+    let mut rejected = rejections(app);
+    for cell in 0..floors {
+        let position = Vec3::new(cell as f32 * 2.0, 2.0, 0.0);
+        build(app, BuildPieceKind::FLOOR, position, Quat::IDENTITY);
+        rejected.extend(rejections(app));
+    }
+    rejected
+}
+
+fn collapsed(app: &mut App) -> Vec<BuildPieceCollapsed> {
+    app.world
+        .resource_mut::<Events<BuildPieceCollapsed>>()
+        .drain()
+        .collect()
+}
+
+#[test]
+fn test_floors_cantilever_a_limited_distance() {
+    let mut app = setup_build_app(40);
+    // The floor on the wall and two more reaching out are held up; the fourth isn't.
+    assert_eq!(
+        build_cantilever(&mut app, 4),
+        [BuildRejectReason::Unsupported]
+    );
+    let mut floors: Vec<_> = pieces(&mut app)
+        .into_iter()
+        .filter(|(piece, _)| piece.kind == BuildPieceKind::FLOOR)
+        .map(|(piece, transform)| (transform.translation.x, piece.stability))
+        .collect();
+    floors.sort_by(|a, b| a.0.total_cmp(&b.0));
+    assert_eq!(floors.len(), 3);
+    assert!(floors
+        .windows(2)
+        .all(|pair| pair[1].1 < pair[0].1 && pair[1].1 > 0.0));
+
+    // A floor hanging in the air isn't held up by anything.
+    build(
+        &mut app,
+        BuildPieceKind::FLOOR,
+        Vec3::new(-8.0, 4.0, 0.0),
+        Quat::IDENTITY,
+    );
+    assert_eq!(rejections(&mut app), [BuildRejectReason::Unsupported]);
+    assert!(collapsed(&mut app).is_empty());
+}
+
+#[test]
+fn test_destroying_support_collapses_dependent_pieces() {
+    let mut app = setup_build_app(40);
+    assert_eq!(build_cantilever(&mut app, 3), []);
+    // A floor on the ground nearby doesn't depend on the wall.
+    build(
+        &mut app,
+        BuildPieceKind::FLOOR,
+        Vec3::new(-6.0, 0.0, 0.0),
+        Quat::IDENTITY,
+    );
+    assert_eq!(pieces(&mut app).len(), 5);
+
+    let mut query = app.world.query::<(Entity, &BuildPiece)>();
+    let wall = query
+        .iter(&app.world)
+        .find(|(_, piece)| piece.kind == BuildPieceKind::WALL)
+        .map(|(entity, _)| entity)
+        .unwrap();
+    app.world.despawn(wall);
+    app.update();
+
+    let fallen = collapsed(&mut app);
+    assert_eq!(fallen.len(), 3);
+    assert!(fallen
+        .iter()
+        .all(|event| event.kind == BuildPieceKind::FLOOR));
+    let remaining = pieces(&mut app);
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(remaining[0].1.translation, Vec3::new(-6.0, 0.0, 0.0));
+
+    // Nothing else comes down afterwards.
+    app.update();
+    assert!(collapsed(&mut app).is_empty());
+}
+
+#[test]
+fn test_damage_respects_resistance_and_destroys() {
+    let mut app = setup_build_app(10);
+    let floor = build_floor(&mut app);
+    let wood_hp = app.world.get::<BuildPiece>(floor).unwrap().health;
+
+    // Wood shrugs off half of bullet damage.
+    damage(&mut app, floor, 40.0, DamageSource::Bullet);
+    assert_eq!(
+        app.world.get::<BuildPiece>(floor).unwrap().health,
+        wood_hp - 20.0
+    );
+    assert!(drain::<BuildDamageStateChanged>(&mut app).is_empty());
+
+    // ...and takes extra from explosions, wearing it down.
+    damage(&mut app, floor, 20.0, DamageSource::Explosion);
+    assert_eq!(
+        app.world.get::<BuildPiece>(floor).unwrap().health,
+        wood_hp - 50.0
+    );
+    assert_eq!(
+        *app.world.get::<BuildDamageState>(floor).unwrap(),
+        BuildDamageState::Damaged
+    );
+    assert_eq!(
+        drain::<BuildDamageStateChanged>(&mut app),
+        [BuildDamageStateChanged {
+            entity: floor,
+            state: BuildDamageState::Damaged
+        }]
+    );
+
+    damage(&mut app, floor, 1000.0, DamageSource::Melee);
+    let destroyed = drain::<BuildPieceDestroyed>(&mut app);
+    assert_eq!(destroyed.len(), 1);
+    assert_eq!(destroyed[0].entity, floor);
+    assert_eq!(destroyed[0].source, DamageSource::Melee);
+    assert!(app.world.get_entity(floor).is_none());
+}
+
+#[test]
+fn test_destroyed_support_collapses_pieces_above() {
+    let mut app = setup_build_app(10);
+    assert_eq!(build_cantilever(&mut app, 2), []);
+    let mut query = app.world.query::<(Entity, &BuildPiece)>();
+    let wall = query
+        .iter(&app.world)
+        .find(|(_, piece)| piece.kind == BuildPieceKind::WALL)
+        .map(|(entity, _)| entity)
+        .unwrap();
+
+    damage(&mut app, wall, 1000.0, DamageSource::Explosion);
+    assert_eq!(drain::<BuildPieceDestroyed>(&mut app).len(), 1);
+    assert_eq!(collapsed(&mut app).len(), 2);
+    assert!(pieces(&mut app).is_empty());
+}
+
+#[test]
+fn test_repair_consumes_items() {
+    let mut app = setup_build_app(cost(BuildPieceKind::FLOOR));
+    let floor = build_floor(&mut app);
+    assert_eq!(wood_left(&mut app), 0);
+    damage(&mut app, floor, 90.0, DamageSource::Melee);
+
+    // Nothing to pay with.
+    app.world.send_event(BuildPieceRepair { target: floor });
+    app.update();
+    assert_eq!(
+        rejections(&mut app),
+        [BuildRejectReason::InsufficientResources]
+    );
+    assert!(app.world.get::<BuildPiece>(floor).unwrap().health < 100.0);
+
+    let wood = wood_item(&app);
+    give(&mut app, wood, 5);
+    app.world.send_event(BuildPieceRepair { target: floor });
+    app.update();
+    assert_eq!(rejections(&mut app), []);
+    let piece = app.world.get::<BuildPiece>(floor).unwrap();
+    assert_eq!(piece.health, 100.0);
+    assert_eq!(
+        *app.world.get::<BuildDamageState>(floor).unwrap(),
+        BuildDamageState::Intact
+    );
+    assert_eq!(
+        drain::<BuildPieceRepaired>(&mut app),
+        [BuildPieceRepaired {
+            entity: floor,
+            health: 100.0
+        }]
+    );
+    assert_eq!(wood_left(&mut app), 5 - cost(BuildPieceKind::FLOOR));
+
+    // Repairing an undamaged piece is free and does nothing.
+    app.world.send_event(BuildPieceRepair { target: floor });
+    app.update();
+    assert!(drain::<BuildPieceRepaired>(&mut app).is_empty());
+    assert_eq!(wood_left(&mut app), 5 - cost(BuildPieceKind::FLOOR));
+}
+
+#[test]
+fn test_upgrades_keep_the_piece_and_its_health_share() {
+    let mut app = setup_build_app(cost(BuildPieceKind::FLOOR));
+    let floor = build_floor(&mut app);
+    damage(&mut app, floor, 50.0, DamageSource::Melee);
+
+    let registry = app.world.resource::<BuildMaterialRegistry>().clone();
+    let metal = registry.get(MaterialId::METAL).unwrap().clone();
+    let reinforced = registry.get(MaterialId::REINFORCED).unwrap().clone();
+    give(&mut app, metal.item, cost(BuildPieceKind::FLOOR));
+    give(&mut app, reinforced.item, cost(BuildPieceKind::FLOOR));
+
+    let upgrade = |app: &mut App| {
+        app.world.send_event(BuildPieceUpgrade { target: floor });
+        app.update();
+    };
+    upgrade(&mut app);
+    let piece = app.world.get::<BuildPiece>(floor).unwrap();
+    assert_eq!(piece.material_id, MaterialId::METAL);
+    assert_eq!(piece.health, metal.base_hp / 2.0);
+    assert_eq!(item_count(&mut app, metal.item), 0);
+    assert_eq!(
+        drain::<BuildPieceUpgraded>(&mut app),
+        [BuildPieceUpgraded {
+            entity: floor,
+            from: MaterialId::WOOD,
+            to: MaterialId::METAL
+        }]
+    );
+
+    upgrade(&mut app);
+    assert_eq!(
+        app.world.get::<BuildPiece>(floor).unwrap().material_id,
+        MaterialId::REINFORCED
+    );
+    assert_eq!(item_count(&mut app, reinforced.item), 0);
+    assert_eq!(rejections(&mut app), []);
+
+    upgrade(&mut app);
+    assert_eq!(rejections(&mut app), [BuildRejectReason::FullyUpgraded]);
+    assert_eq!(pieces(&mut app).len(), 1);
+}
+
+#[test]
+fn test_catalogue_pieces_place_on_the_grid() {
+    let mut app = setup_build_app(10);
+    build(
+        &mut app,
+        BuildPieceKind::STAIRS,
+        Vec3::new(0.3, 0.0, 0.2),
+        Quat::IDENTITY,
+    );
+    build(
+        &mut app,
+        BuildPieceKind::HALF_WALL,
+        Vec3::new(0.3, 0.0, 0.2),
+        Quat::from_rotation_y(FRAC_PI_2),
+    );
+    build(
+        &mut app,
+        BuildPieceKind::WINDOW,
+        Vec3::new(0.3, 0.0, 0.2),
+        Quat::from_rotation_y(-FRAC_PI_2),
+    );
+    assert_eq!(rejections(&mut app), []);
+    let mut placed: Vec<_> = pieces(&mut app)
+        .into_iter()
+        .map(|(piece, transform)| (piece.kind.0, transform.translation))
+        .collect();
+    placed.sort_by_key(|(kind, _)| *kind);
+    assert_eq!(
+        placed,
+        [
+            (BuildPieceKind::WINDOW.0, Vec3::new(-1.0, 0.0, 0.0)),
+            (BuildPieceKind::STAIRS.0, Vec3::ZERO),
+            (BuildPieceKind::HALF_WALL.0, Vec3::new(1.0, 0.0, 0.0)),
+        ]
+    );
+
+    build(&mut app, BuildPieceKind(999), Vec3::ZERO, Quat::IDENTITY);
+    assert_eq!(rejections(&mut app), [BuildRejectReason::UnknownPiece]);
+}
+
+#[test]
+fn test_foundations_sink_into_terrain() {
+    let mut app = setup_build_app(10);
+    // Ground with its surface at y = 0, and a mound in the cell at x = 4.
+    fill_stone(&mut app, IVec3::new(-8, -2, -8), IVec3::new(8, -1, 8));
+    let mut world_data = app.world.resource_mut::<WorldData>();
+    world_data.set_voxel(IVec3::new(4, 0, 0), Voxel(world::MaterialId::STONE));
+
+    build(
+        &mut app,
+        BuildPieceKind::FLOOR,
+        Vec3::new(4.0, 0.0, 0.0),
+        Quat::IDENTITY,
+    );
+    assert_eq!(rejections(&mut app), [BuildRejectReason::BlockedByTerrain]);
+    build(
+        &mut app,
+        BuildPieceKind::FOUNDATION,
+        Vec3::new(4.0, 0.0, 0.0),
+        Quat::IDENTITY,
+    );
+    assert_eq!(rejections(&mut app), []);
+    let placed = pieces(&mut app);
+    assert_eq!(placed.len(), 1);
+    assert_eq!(placed[0].0.kind, BuildPieceKind::FOUNDATION);
+}
+
+#[test]
+fn test_doors_open_close_and_lock() {
+    let mut app = setup_build_app(10);
+    build(&mut app, BuildPieceKind::DOOR, Vec3::ZERO, Quat::IDENTITY);
+    assert_eq!(rejections(&mut app), []);
+    let mut query = app.world.query_filtered::<Entity, With<Door>>();
+    let door = query.single(&app.world);
+    let mut leaf_query = app.world.query_filtered::<&Transform, With<DoorLeaf>>();
+    let closed = *leaf_query.single(&app.world);
+
+    send(&mut app, ToggleDoor { target: door });
+    assert!(app.world.get::<Door>(door).unwrap().open);
+    let open = *leaf_query.single(&app.world);
+    assert!(!open.rotation.abs_diff_eq(closed.rotation, 1e-3));
+
+    send(&mut app, ToggleDoor { target: door });
+    send(
+        &mut app,
+        LockDoor {
+            target: door,
+            locked: true,
+        },
+    );
+    // Locked doors don't open.
+    send(&mut app, ToggleDoor { target: door });
+    let state = *app.world.get::<Door>(door).unwrap();
+    assert!(!state.open && state.locked);
+    assert_eq!(*leaf_query.single(&app.world), closed);
+
+    let changes: Vec<_> = drain::<DoorChanged>(&mut app)
+        .into_iter()
+        .map(|event| (event.door.open, event.door.locked))
+        .collect();
+    assert_eq!(changes, [(true, false), (false, false), (false, true)]);
+}
+
+#[test]
+fn test_traps_damage_whatever_enters_them() {
+    let mut app = setup_build_app(10);
+    app.add_event::<DamageEvent>();
+    build(
+        &mut app,
+        BuildPieceKind::SPIKE_TRAP,
+        Vec3::new(4.0, 0.0, 0.0),
+        Quat::IDENTITY,
+    );
+    assert_eq!(rejections(&mut app), []);
+    let victim = app
+        .world
+        .spawn((Health::default(), Transform::from_xyz(0.0, 0.5, 0.0)))
+        .id();
+    app.update();
+    assert!(drain::<TrapTriggered>(&mut app).is_empty());
+
+    app.world.get_mut::<Transform>(victim).unwrap().translation = Vec3::new(4.3, 0.5, 0.2);
+    app.update();
+    let triggered = drain::<TrapTriggered>(&mut app);
+    assert_eq!(triggered.len(), 1);
+    assert_eq!(triggered[0].target, victim);
+    let damage = drain::<DamageEvent>(&mut app);
+    assert_eq!(damage.len(), 1);
+    assert_eq!(damage[0].source, DamageSource::Trap);
+
+    // The trap needs to re-arm before going off again.
+    app.update();
+    assert!(drain::<TrapTriggered>(&mut app).is_empty());
+}
+
+#[test]
+fn test_traps_catch_a_player_walking_onto_them() {
+    let tick = Duration::from_secs_f64(1.0 / 60.0);
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        TransformPlugin,
+        InputPlugin,
+        AssetPlugin::default(),
+        ScenePlugin,
+        RapierPhysicsPlugin::<NoUserData>::default(),
+        MovementPlugin,
+        PlayerPlugin,
+        CameraPlugin,
+        HealthPlugin,
+        BuildingPlugin,
+    ));
+    app.init_resource::<ItemRegistry>();
+    app.insert_resource(Time::<Fixed>::from_duration(tick));
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(tick));
+    app.init_resource::<Assets<Mesh>>();
+    app.init_resource::<Assets<StandardMaterial>>();
+    app.world.spawn((
+        TransformBundle::from_transform(Transform::from_xyz(0.0, -0.5, 0.0)),
+        RigidBody::Fixed,
+        Collider::cuboid(50.0, 0.5, 50.0),
+    ));
+    app.update();
+    let wood = wood_item(&app);
+    give(&mut app, wood, cost(BuildPieceKind::SPIKE_TRAP));
+    build(
+        &mut app,
+        BuildPieceKind::SPIKE_TRAP,
+        Vec3::new(4.0, 0.0, 0.0),
+        Quat::IDENTITY,
+    );
+    assert_eq!(rejections(&mut app), []);
+
+    // The player's origin stays well above the trigger volume, but its feet walk through it.
+    app.world
+        .resource_mut::<ActionState>()
+        .press(InputAction::MoveRight);
+    let mut triggered = Vec::new();
+    for _ in 0..120 {
+        app.update();
+        triggered.extend(drain::<TrapTriggered>(&mut app));
+        if !triggered.is_empty() {
+            break;
+        }
+    }
+    assert_eq!(triggered.len(), 1, "the trap never went off");
+    app.update();
+    let mut query = app
+        .world
+        .query_filtered::<(Entity, &Health), With<Player>>();
+    let (player, health) = query.single(&app.world);
+    assert_eq!(triggered[0].target, player);
+    assert!(health.current < health.max);
+}
+
+#[test]
+fn test_pieces_snap_to_sockets_and_reject_overlaps() {
+    let mut app = setup_build_app(20);
+    build(&mut app, BuildPieceKind::WALL, Vec3::ZERO, Quat::IDENTITY);
+    assert_eq!(rejections(&mut app), []);
+
+    // Aiming just over the wall puts a roof on top of it, where the grid alone wouldn't.
+    setup_aim(&mut app, Vec3::new(0.3, 2.0, 0.4));
+    tap(&mut app, InputAction::Build);
+    app.world.resource_mut::<BuildMode>().piece = BuildPieceKind::ROOF;
+    app.update();
+    let (_, rejection, preview) = ghost(&mut app).unwrap();
+    assert_eq!(rejection, None);
+    assert!(preview
+        .translation
+        .abs_diff_eq(Vec3::new(0.0, 2.0, 0.0), 1e-5));
+    tap(&mut app, InputAction::Fire);
+    assert_eq!(rejections(&mut app), []);
+    let roof = pieces(&mut app)
+        .into_iter()
+        .find(|(piece, _)| piece.kind == BuildPieceKind::ROOF)
+        .unwrap();
+    assert_eq!(roof.1, preview);
+
+    // A foundation on the level above stairs would sink into them.
+    build(
+        &mut app,
+        BuildPieceKind::STAIRS,
+        Vec3::new(4.0, 0.0, 0.0),
+        Quat::IDENTITY,
+    );
+    build(
+        &mut app,
+        BuildPieceKind::FOUNDATION,
+        Vec3::new(4.0, 2.0, 0.0),
+        Quat::IDENTITY,
+    );
+    assert_eq!(rejections(&mut app), [BuildRejectReason::Overlapping]);
+}
+
+/// A wall on the +Z edge of the origin cell, with a floor resting on top of it.
+fn shelter() -> Blueprint {
+    Blueprint {
+        name: "Shelter".into(),
+        pieces: vec![
+            BlueprintPiece {
+                kind: BuildPieceKind::WALL,
+                material: MaterialId::WOOD,
+                translation: Vec3::new(0.0, 0.0, 1.0),
+                rotation: Quat::IDENTITY,
+            },
+            BlueprintPiece {
+                kind: BuildPieceKind::FLOOR,
+                material: MaterialId::WOOD,
+                translation: Vec3::new(0.0, 2.0, 0.0),
+                rotation: Quat::IDENTITY,
+            },
+        ],
+    }
+}
+
+/// Requests `blueprint` at `position` and lets construction run with no delay between pieces.
+fn place_blueprint(app: &mut App, blueprint: Blueprint, position: Vec3, policy: BlueprintPolicy) {
+    app.world.resource_mut::<BlueprintStaging>().interval = 0.0;
+    send(
+        app,
+        PlaceBlueprint {
+            blueprint,
+            position,
+            rotation: Quat::IDENTITY,
+            policy,
+        },
+    );
+    for _ in 0..4 {
+        app.update();
+    }
+}
+
+fn translations(app: &mut App, kind: BuildPieceKind) -> Vec<Vec3> {
+    pieces(app)
+        .into_iter()
+        .filter(|(piece, _)| piece.kind == kind)
+        .map(|(_, transform)| transform.translation)
+        .collect()
+}
+
+#[test]
+fn test_blueprints_capture_and_rebuild_elsewhere() {
+    let mut app = setup_build_app(10);
+    build(&mut app, BuildPieceKind::WALL, Vec3::ZERO, Quat::IDENTITY);
+    build(
+        &mut app,
+        BuildPieceKind::FLOOR,
+        Vec3::new(0.0, 2.0, 0.0),
+        Quat::IDENTITY,
+    );
+    assert_eq!(rejections(&mut app), []);
+    let mut query = app.world.query_filtered::<Entity, With<BuildPiece>>();
+    let built: Vec<_> = query.iter(&app.world).collect();
+    send(
+        &mut app,
+        CaptureBlueprint {
+            name: "Shelter".into(),
+            pieces: built,
+        },
+    );
+    let captured = drain::<BlueprintCaptured>(&mut app)
+        .pop()
+        .unwrap()
+        .blueprint;
+    assert_eq!(captured, shelter());
+
+    let path = std::env::temp_dir()
+        .join(format!("protocol-zero-blueprint-{}", std::process::id()))
+        .join("shelter.ron");
+    captured.save(&path).unwrap();
+    let loaded = Blueprint::load(&path).unwrap();
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    assert_eq!(loaded, captured);
+
+    let wood = wood_left(&mut app);
+    place_blueprint(
+        &mut app,
+        loaded,
+        Vec3::new(6.3, 0.0, 0.2),
+        BlueprintPolicy::AllOrNothing,
+    );
+    assert_eq!(rejections(&mut app), []);
+    let started = drain::<BlueprintStarted>(&mut app);
+    assert_eq!((started[0].pieces, started[0].skipped), (2, 0));
+    let completed = drain::<BlueprintCompleted>(&mut app);
+    assert_eq!((completed[0].placed, completed[0].skipped), (2, 0));
+    assert!(translations(&mut app, BuildPieceKind::WALL).contains(&Vec3::new(6.0, 0.0, 1.0)));
+    assert!(translations(&mut app, BuildPieceKind::FLOOR).contains(&Vec3::new(6.0, 2.0, 0.0)));
+    assert_eq!(
+        wood_left(&mut app),
+        wood - cost(BuildPieceKind::WALL) - cost(BuildPieceKind::FLOOR)
+    );
+}
+
+#[test]
+fn test_blueprint_policies_handle_blocked_pieces() {
+    let mut app = setup_build_app(10);
+    build(&mut app, BuildPieceKind::WALL, Vec3::ZERO, Quat::IDENTITY);
+    assert_eq!(rejections(&mut app), []);
+
+    // The wall's spot is taken, so nothing goes up.
+    place_blueprint(
+        &mut app,
+        shelter(),
+        Vec3::ZERO,
+        BlueprintPolicy::AllOrNothing,
+    );
+    assert_eq!(rejections(&mut app), [BuildRejectReason::Occupied]);
+    assert!(drain::<BlueprintStarted>(&mut app).is_empty());
+    assert_eq!(pieces(&mut app).len(), 1);
+
+    // Skipping it still builds the floor, held up by the wall already there.
+    place_blueprint(
+        &mut app,
+        shelter(),
+        Vec3::ZERO,
+        BlueprintPolicy::SkipBlocked,
+    );
+    assert_eq!(rejections(&mut app), []);
+    let completed = drain::<BlueprintCompleted>(&mut app);
+    assert_eq!((completed[0].placed, completed[0].skipped), (1, 1));
+    assert_eq!(
+        translations(&mut app, BuildPieceKind::FLOOR),
+        [Vec3::new(0.0, 2.0, 0.0)]
+    );
+
+    // The whole blueprint has to be affordable up front.
+    let mut app = setup_build_app(cost(BuildPieceKind::WALL));
+    place_blueprint(
+        &mut app,
+        shelter(),
+        Vec3::ZERO,
+        BlueprintPolicy::AllOrNothing,
+    );
+    assert_eq!(
+        rejections(&mut app),
+        [BuildRejectReason::InsufficientResources]
+    );
+    assert!(pieces(&mut app).is_empty());
+}
+
+#[test]
+fn test_whole_blueprints_are_paid_up_front_and_refund_blocked_pieces() {
+    let floor_cost = cost(BuildPieceKind::FLOOR);
+    let mut app = setup_build_app(cost(BuildPieceKind::WALL) + floor_cost);
+    app.world.resource_mut::<BlueprintStaging>().interval = 1.0;
+    send(
+        &mut app,
+        PlaceBlueprint {
+            blueprint: shelter(),
+            position: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+            policy: BlueprintPolicy::AllOrNothing,
+        },
+    );
+    assert_eq!(drain::<BlueprintStarted>(&mut app).len(), 1);
+    assert_eq!(wood_left(&mut app), 0);
+
+    // With the wall up, something else takes the floor's spot before the floor is due.
+    app.update();
+    assert_eq!(translations(&mut app, BuildPieceKind::WALL).len(), 1);
+    let wood = wood_item(&app);
+    give(&mut app, wood, floor_cost);
+    build(
+        &mut app,
+        BuildPieceKind::FLOOR,
+        Vec3::new(0.0, 2.0, 0.0),
+        Quat::IDENTITY,
+    );
+    assert_eq!(rejections(&mut app), []);
+    assert_eq!(wood_left(&mut app), 0);
+
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+        0.25,
+    )));
+    for _ in 0..5 {
+        app.update();
+    }
+    assert_eq!(rejections(&mut app), [BuildRejectReason::Occupied]);
+    let completed = drain::<BlueprintCompleted>(&mut app);
+    assert_eq!((completed[0].placed, completed[0].skipped), (1, 1));
+    assert_eq!(wood_left(&mut app), floor_cost);
+}
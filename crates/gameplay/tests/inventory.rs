@@ -0,0 +1,362 @@
+use bevy::prelude::*;
+use gameplay::health::{DamageSource, Died};
+use gameplay::inventory::encumbrance::{Encumbrance, EncumbranceTier};
+use gameplay::inventory::grid::{GridPlacement, ItemSize};
+use gameplay::inventory::items::ItemRegistry;
+use gameplay::inventory::mission::{
+    EndMission, ItemLocation, ItemsLost, ItemsTransferred, Mission, MissionOutcome, StartMission,
+    StashTransfer, TransferError, TransferFailed,
+};
+use gameplay::inventory::stack::{InventoryError, ItemContainer, ItemStack};
+use gameplay::inventory::{Inventory, InventoryPlugin, ItemId, Stash};
+use gameplay::player::Player;
+
+const WOOD: ItemId = ItemId(1);
+const METAL: ItemId = ItemId(2);
+const AMMO: ItemId = ItemId(10);
+const PISTOL: ItemId = ItemId(20);
+const MACHETE: ItemId = ItemId(21);
+const BANDAGE: ItemId = ItemId(30);
+
+/// A minimal app with a player and a stash holding `stash`.
+fn setup_inventory_app(stash: &[ItemStack]) -> (App, Entity) {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, InventoryPlugin));
+    let registry = ItemRegistry::default();
+    let mut stash_resource = Stash::default();
+    for stack in stash {
+        stash_resource.add(stack.clone(), &registry).unwrap();
+    }
+    app.insert_resource(stash_resource);
+    let player = app.world.spawn((Player, Inventory::default())).id();
+    app.update();
+    (app, player)
+}
+
+/// Sends `event` and runs a frame.
+fn send<E: Event>(app: &mut App, event: E) {
+    app.world.send_event(event);
+    app.update();
+}
+
+fn drain<E: Event + Clone>(app: &mut App) -> Vec<E> {
+    app.world.resource_mut::<Events<E>>().drain().collect()
+}
+
+fn inventory(app: &mut App) -> Inventory {
+    let mut query = app.world.query_filtered::<&Inventory, With<Player>>();
+    query.single(&app.world).clone()
+}
+
+fn stash_count(app: &App, id: ItemId) -> usize {
+    app.world.resource::<Stash>().count(id)
+}
+
+fn failures(app: &mut App) -> Vec<TransferError> {
+    drain::<TransferFailed>(app)
+        .into_iter()
+        .map(|event| event.error)
+        .collect()
+}
+
+#[test]
+fn test_items_move_between_inventory_and_stash() {
+    let (mut app, _) = setup_inventory_app(&[ItemStack::new(WOOD, 30)]);
+    send(
+        &mut app,
+        StashTransfer {
+            from: ItemLocation::Stash,
+            slot: 0,
+            count: 12,
+        },
+    );
+    assert_eq!(failures(&mut app), []);
+    let moved = drain::<ItemsTransferred>(&mut app);
+    assert_eq!(moved.len(), 1);
+    assert_eq!(
+        (moved[0].from, moved[0].to, moved[0].stack.count),
+        (ItemLocation::Stash, ItemLocation::Inventory, 12)
+    );
+    assert_eq!(inventory(&mut app).count(WOOD), 12);
+    assert_eq!(stash_count(&app, WOOD), 18);
+
+    send(
+        &mut app,
+        StashTransfer {
+            from: ItemLocation::Inventory,
+            slot: 0,
+            count: 13,
+        },
+    );
+    assert_eq!(
+        failures(&mut app),
+        [TransferError::Inventory(InventoryError::InvalidCount(13))]
+    );
+    assert_eq!(stash_count(&app, WOOD), 18);
+
+    // A full inventory takes nothing more.
+    let registry = ItemRegistry::default();
+    let mut stash = Stash::default();
+    stash.add(ItemStack::new(PISTOL, 1), &registry).unwrap();
+    app.insert_resource(stash);
+    let mut query = app.world.query_filtered::<&mut Inventory, With<Player>>();
+    for slot in query.single_mut(&mut app.world).items.iter_mut() {
+        *slot = Some(ItemStack::new(BANDAGE, 1));
+    }
+    send(
+        &mut app,
+        StashTransfer {
+            from: ItemLocation::Stash,
+            slot: 0,
+            count: 1,
+        },
+    );
+    assert_eq!(
+        failures(&mut app),
+        [TransferError::Inventory(InventoryError::NoRoom)]
+    );
+    assert_eq!(stash_count(&app, PISTOL), 1);
+}
+
+#[test]
+fn test_loadout_goes_out_and_comes_back_on_extraction() {
+    let (mut app, _) = setup_inventory_app(&[
+        ItemStack::new(PISTOL, 1),
+        ItemStack::new(AMMO, 100),
+        ItemStack::new(BANDAGE, 2),
+    ]);
+
+    // Asking for more than the stash holds takes nothing.
+    send(
+        &mut app,
+        StartMission {
+            loadout: vec![(PISTOL, 1), (BANDAGE, 3)],
+        },
+    );
+    assert_eq!(
+        failures(&mut app),
+        [TransferError::Inventory(InventoryError::NotEnough {
+            id: BANDAGE,
+            requested: 3,
+            available: 2,
+        })]
+    );
+    assert!(!app.world.resource::<Mission>().active);
+    assert_eq!(stash_count(&app, PISTOL), 1);
+
+    send(
+        &mut app,
+        StartMission {
+            loadout: vec![(PISTOL, 1), (AMMO, 90)],
+        },
+    );
+    assert_eq!(failures(&mut app), []);
+    assert!(app.world.resource::<Mission>().active);
+    // The ammo spans two stacks in the stash.
+    assert_eq!(drain::<ItemsTransferred>(&mut app).len(), 3);
+    assert_eq!(inventory(&mut app).count(AMMO), 90);
+    assert_eq!(stash_count(&app, AMMO), 10);
+
+    // The stash is out of reach until the mission ends.
+    send(
+        &mut app,
+        StashTransfer {
+            from: ItemLocation::Stash,
+            slot: 0,
+            count: 1,
+        },
+    );
+    assert_eq!(failures(&mut app), [TransferError::MissionUnderway]);
+
+    send(
+        &mut app,
+        EndMission {
+            outcome: MissionOutcome::Extracted,
+        },
+    );
+    assert_eq!(failures(&mut app), []);
+    assert!(drain::<ItemsLost>(&mut app).is_empty());
+    assert!(inventory(&mut app).items.iter().all(Option::is_none));
+    assert_eq!(stash_count(&app, AMMO), 100);
+    assert_eq!(stash_count(&app, PISTOL), 1);
+    assert!(!app.world.resource::<Mission>().active);
+}
+
+#[test]
+fn test_death_keeps_only_insured_and_secured_items() {
+    let insured = ItemStack {
+        insured: true,
+        ..ItemStack::new(PISTOL, 1)
+    };
+    let (mut app, player) = setup_inventory_app(&[
+        ItemStack::new(BANDAGE, 1),
+        ItemStack::new(AMMO, 30),
+        insured,
+    ]);
+    send(
+        &mut app,
+        StartMission {
+            loadout: vec![(BANDAGE, 1), (AMMO, 30), (PISTOL, 1)],
+        },
+    );
+    assert_eq!(failures(&mut app), []);
+    drain::<ItemsTransferred>(&mut app);
+    // Only the bandage is in the secure container.
+    let mut query = app.world.query_filtered::<&mut Inventory, With<Player>>();
+    query.single_mut(&mut app.world).secure_slots = 1;
+
+    send(
+        &mut app,
+        Died {
+            entity: player,
+            source: DamageSource::Bullet,
+        },
+    );
+    let lost: Vec<_> = drain::<ItemsLost>(&mut app)
+        .into_iter()
+        .map(|event| (event.stack.id, event.stack.count))
+        .collect();
+    assert_eq!(lost, [(AMMO, 30)]);
+    let returned: Vec<_> = drain::<ItemsTransferred>(&mut app)
+        .into_iter()
+        .map(|event| event.stack.id)
+        .collect();
+    assert_eq!(returned, [BANDAGE, PISTOL]);
+    assert_eq!(stash_count(&app, AMMO), 0);
+    assert_eq!(stash_count(&app, BANDAGE), 1);
+    assert!(!app.world.resource::<Mission>().active);
+
+    // Ending a mission that's already over does nothing.
+    send(
+        &mut app,
+        EndMission {
+            outcome: MissionOutcome::Extracted,
+        },
+    );
+    assert_eq!(failures(&mut app), [TransferError::NoMission]);
+}
+
+#[test]
+fn test_grid_inventories_pack_items_by_footprint() {
+    let registry = ItemRegistry::default();
+    let mut inventory = Inventory::with_grid(3, 2);
+    inventory
+        .add(ItemStack::new(MACHETE, 1), &registry)
+        .unwrap();
+    inventory.add(ItemStack::new(PISTOL, 1), &registry).unwrap();
+    assert_eq!(inventory.placement(0), Some(GridPlacement::at(0, 0)));
+    assert_eq!(inventory.placement(1), Some(GridPlacement::at(0, 1)));
+
+    // One cell is left, which holds one stack of bandages and no more.
+    let bandages = ItemStack::new(BANDAGE, 1);
+    assert_eq!(inventory.room_for(&bandages, &registry), Ok(5));
+    assert_eq!(
+        inventory.add(ItemStack::new(BANDAGE, 6), &registry),
+        Err(InventoryError::NoRoom)
+    );
+    assert_eq!(inventory.count(BANDAGE), 0);
+    inventory
+        .add(ItemStack::new(BANDAGE, 4), &registry)
+        .unwrap();
+    assert_eq!(inventory.placement(2), Some(GridPlacement::at(2, 1)));
+    assert_eq!(
+        inventory.split(2, 2, &registry),
+        Err(InventoryError::NoRoom)
+    );
+
+    // There are free slots, but no free cells.
+    assert!(inventory.items.iter().filter(|slot| slot.is_none()).count() > 0);
+    assert_eq!(
+        inventory.add(ItemStack::new(WOOD, 1), &registry),
+        Err(InventoryError::NoRoom)
+    );
+
+    // Emptying a slot frees its cells.
+    inventory.take(0, 1).unwrap();
+    assert_eq!(inventory.placement(0), None);
+    assert_eq!(inventory.split(2, 2, &registry), Ok(0));
+    assert_eq!(inventory.placement(0), Some(GridPlacement::at(0, 0)));
+}
+
+#[test]
+fn test_grid_items_turn_to_fit_and_can_be_rearranged() {
+    let registry = ItemRegistry::default();
+
+    // A machete is too long for a two-wide grid unless it stands on its end.
+    let mut inventory = Inventory::with_grid(2, 3);
+    inventory
+        .add(ItemStack::new(MACHETE, 1), &registry)
+        .unwrap();
+    assert_eq!(
+        inventory.placement(0),
+        Some(GridPlacement::at(0, 0).turned())
+    );
+
+    // Nothing fits in a grid smaller than the item either way round.
+    let mut small = Inventory::with_grid(2, 2);
+    assert_eq!(small.find_space(ItemSize::new(3, 1)), None);
+    assert_eq!(
+        small.add(ItemStack::new(MACHETE, 1), &registry),
+        Err(InventoryError::NoRoom)
+    );
+
+    // The pistol goes upright at the top right, past the machete.
+    inventory.add(ItemStack::new(PISTOL, 1), &registry).unwrap();
+    assert_eq!(
+        inventory.placement(1),
+        Some(GridPlacement::at(1, 0).turned())
+    );
+    assert!(!inventory.fits(ItemSize::ONE, GridPlacement::at(1, 1)));
+    assert!(inventory.fits(ItemSize::ONE, GridPlacement::at(1, 2)));
+
+    // Moving a stack can overlap where it was, but not other stacks or the edge.
+    assert_eq!(
+        inventory.place(1, GridPlacement::at(0, 0), &registry),
+        Err(InventoryError::NoRoom)
+    );
+    assert_eq!(
+        inventory.place(1, GridPlacement::at(1, 2), &registry),
+        Err(InventoryError::NoRoom)
+    );
+    inventory
+        .place(1, GridPlacement::at(1, 1).turned(), &registry)
+        .unwrap();
+    assert!(inventory.fits(ItemSize::ONE, GridPlacement::at(1, 0)));
+
+    // Moving a whole stack to another slot keeps it where it sits in the grid.
+    inventory.move_stack(1, 5, &registry).unwrap();
+    assert_eq!(
+        inventory.placement(5),
+        Some(GridPlacement::at(1, 1).turned())
+    );
+    assert_eq!(
+        Inventory::default().place(0, GridPlacement::at(0, 0), &registry),
+        Err(InventoryError::EmptySlot(0))
+    );
+}
+
+#[test]
+fn test_carried_weight_sets_the_encumbrance_tier() {
+    let (mut app, player) = setup_inventory_app(&[]);
+    app.world.entity_mut(player).insert(Encumbrance::new(30.0));
+    app.update();
+    assert_eq!(
+        *app.world.get::<Encumbrance>(player).unwrap(),
+        Encumbrance::new(30.0)
+    );
+
+    let registry = ItemRegistry::default();
+    let mut inventory = app.world.get_mut::<Inventory>(player).unwrap();
+    inventory.add(ItemStack::new(METAL, 20), &registry).unwrap();
+    app.update();
+    let encumbrance = *app.world.get::<Encumbrance>(player).unwrap();
+    assert_eq!(encumbrance.weight, 20.0);
+    assert_eq!(encumbrance.tier, EncumbranceTier::Burdened);
+
+    let mut inventory = app.world.get_mut::<Inventory>(player).unwrap();
+    inventory.add(ItemStack::new(METAL, 20), &registry).unwrap();
+    app.update();
+    let encumbrance = app.world.get::<Encumbrance>(player).unwrap();
+    assert_eq!(encumbrance.tier, EncumbranceTier::Overloaded);
+    assert_eq!(encumbrance.tier.speed_multiplier(), 0.5);
+}
@@ -1,12 +1,37 @@
+// The baseline setup builds unit-struct plugins with `default()`.
+#![allow(clippy::default_constructed_unit_structs)]
+
+use bevy::input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel};
 use bevy::input::InputPlugin;
 use bevy::pbr::StandardMaterial;
 use bevy::prelude::*;
 use bevy::render::mesh::Mesh;
 use bevy::scene::ScenePlugin;
+use bevy::time::TimeUpdateStrategy;
 use bevy_rapier3d::prelude::*;
-use gameplay::camera::{CameraPlugin, CameraPerspective, CameraRig};
-use gameplay::movement::{MovementPlugin, MovementState, PlayerInput};
-use gameplay::player::{Player, PlayerPlugin};
+use common::input::{ActionState, InputAction, InputActionsPlugin};
+use gameplay::camera::cinematic::{
+    start_cinematic, CinematicFinished, CinematicKeyframe, CinematicPath, CinematicPlayback,
+};
+use gameplay::camera::effects::CameraShake;
+use gameplay::camera::profile::CameraProfiles;
+use gameplay::camera::{
+    eye_height, CameraPerspective, CameraPlugin, CameraRig, MainCamera, Shoulder,
+};
+use gameplay::climbing::{Climbable, CLIMB_SPEED};
+use gameplay::health::{Died, Health, HealthPlugin};
+use gameplay::movement::{
+    fall_damage, step_horizontal_velocity, CharacterVelocity, Footing, Landed, MovementPlugin,
+    MovementState, PlayerInput, AIR_CONTROL, GROUND_ACCELERATION, GROUND_DECELERATION, JUMP_SPEED,
+    ROLL_MITIGATION, SAFE_IMPACT_SPEED,
+};
+use gameplay::player::{Player, PlayerPlugin, PLAYER_FEET_OFFSET};
+use gameplay::replay::{start_playback, start_recording, stop_recording, Replay, ReplayPlugin};
+use gameplay::stamina::{Stamina, StaminaEvent, StaminaPlugin};
+use gameplay::swimming::{Breath, Submersion, SwimmingPlugin, WaterVolume, TREAD_SUBMERSION};
+use std::time::Duration;
+
+const TICK: f32 = 1.0 / 60.0;
 
 /// A minimal Bevy app setup for testing movement logic.
 fn setup_test_app() -> App {
@@ -21,6 +46,10 @@ fn setup_test_app() -> App {
         MovementPlugin,
         PlayerPlugin,
         CameraPlugin,
+        StaminaPlugin,
+        HealthPlugin,
+        SwimmingPlugin,
+        ReplayPlugin,
     ));
 
     // Configure Rapier for fixed timestep testing
@@ -32,6 +61,12 @@ fn setup_test_app() -> App {
         ..default()
     });
 
+    // Advance time by exactly one fixed tick per update so FixedUpdate runs deterministically.
+    app.insert_resource(Time::<Fixed>::from_seconds(TICK as f64));
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+        TICK as f64,
+    )));
+
     // Manually add the resources needed by the `spawn_player` system,
     // since we aren't using the full rendering plugins.
     app.init_resource::<Assets<Mesh>>();
@@ -46,14 +81,50 @@ fn run_updates(app: &mut App, count: usize) {
     }
 }
 
+/// Spawns a static ground slab whose top face is at `y = 0.5`, right under the spawned player.
+fn spawn_ground(app: &mut App) {
+    app.world.spawn((
+        TransformBundle::from_transform(Transform::from_xyz(0.0, 0.0, 0.0)),
+        RigidBody::Fixed,
+        Collider::cuboid(50.0, 0.5, 50.0),
+    ));
+}
+
+fn player_velocity(app: &mut App) -> CharacterVelocity {
+    let mut query = app
+        .world
+        .query_filtered::<&CharacterVelocity, With<Player>>();
+    *query.get_single(&app.world).unwrap()
+}
+
+fn player_grounded(app: &mut App) -> bool {
+    let mut query = app
+        .world
+        .query_filtered::<&KinematicCharacterControllerOutput, With<Player>>();
+    query
+        .get_single(&app.world)
+        .is_ok_and(|output| output.grounded)
+}
+
+/// Runs ticks until the player has landed on the ground and come to rest.
+fn settle_on_ground(app: &mut App) {
+    for _ in 0..60 {
+        app.update();
+        if player_grounded(app) && player_velocity(app).horizontal() == Vec3::ZERO {
+            return;
+        }
+    }
+    panic!("player never settled on the ground");
+}
+
 #[test]
 fn test_movement_state_changes_to_walking() {
     let mut app = setup_test_app();
 
-    // Simulate a key press
+    // Simulate an action press
     {
-        let mut input = app.world.resource_mut::<ButtonInput<KeyCode>>();
-        input.press(KeyCode::KeyW);
+        let mut input = app.world.resource_mut::<ActionState>();
+        input.press(InputAction::MoveForward);
     }
 
     run_updates(&mut app, 5);
@@ -69,8 +140,8 @@ fn test_movement_state_reverts_to_idle() {
 
     // Frame 1: Press key and update
     {
-        let mut input = app.world.resource_mut::<ButtonInput<KeyCode>>();
-        input.press(KeyCode::KeyW);
+        let mut input = app.world.resource_mut::<ActionState>();
+        input.press(InputAction::MoveForward);
     }
     run_updates(&mut app, 5);
 
@@ -80,8 +151,8 @@ fn test_movement_state_reverts_to_idle() {
 
     // Frame 2: Release key and update
     {
-        let mut input = app.world.resource_mut::<ButtonInput<KeyCode>>();
-        input.release(KeyCode::KeyW);
+        let mut input = app.world.resource_mut::<ActionState>();
+        input.release(InputAction::MoveForward);
     }
     run_updates(&mut app, 5);
 
@@ -94,11 +165,11 @@ fn test_movement_state_reverts_to_idle() {
 fn test_movement_state_changes_to_sprinting() {
     let mut app = setup_test_app();
 
-    // Simulate key presses for moving and sprinting
+    // Simulate actions for moving and sprinting
     {
-        let mut input = app.world.resource_mut::<ButtonInput<KeyCode>>();
-        input.press(KeyCode::KeyW);
-        input.press(KeyCode::ShiftLeft);
+        let mut input = app.world.resource_mut::<ActionState>();
+        input.press(InputAction::MoveForward);
+        input.press(InputAction::Sprint);
     }
 
     run_updates(&mut app, 5);
@@ -113,11 +184,11 @@ fn test_movement_state_changes_to_sprinting() {
 fn test_player_input_resource_is_updated() {
     let mut app = setup_test_app();
 
-    // Simulate multiple key presses
+    // Simulate multiple action presses
     {
-        let mut input = app.world.resource_mut::<ButtonInput<KeyCode>>();
-        input.press(KeyCode::KeyW);
-        input.press(KeyCode::KeyA);
+        let mut input = app.world.resource_mut::<ActionState>();
+        input.press(InputAction::MoveForward);
+        input.press(InputAction::MoveLeft);
     }
 
     run_updates(&mut app, 5);
@@ -150,10 +221,10 @@ fn test_player_transform_is_changed_by_movement() {
         *player_query.get_single(&app.world).unwrap()
     };
 
-    // Simulate key press
+    // Simulate action press
     {
-        let mut input = app.world.resource_mut::<ButtonInput<KeyCode>>();
-        input.press(KeyCode::KeyD);
+        let mut input = app.world.resource_mut::<ActionState>();
+        input.press(InputAction::MoveRight);
     }
 
     // Run updates to process movement
@@ -183,8 +254,8 @@ fn test_camera_perspective_toggles() {
 
     // --- First toggle: to FirstPerson ---
     {
-        let mut input = app.world.resource_mut::<ButtonInput<KeyCode>>();
-        input.press(KeyCode::KeyV);
+        let mut input = app.world.resource_mut::<ActionState>();
+        input.press(InputAction::ToggleCamera);
     }
     run_updates(&mut app, 1); // Run systems, including the toggle system
 
@@ -192,7 +263,11 @@ fn test_camera_perspective_toggles() {
     let next_state = app.world.resource::<NextState<CameraPerspective>>();
     assert_eq!(next_state.0, Some(CameraPerspective::FirstPerson), "State change to FirstPerson should be queued");
 
-    // Run another update to apply the state change and check the final state
+    // Keep holding the action, which is no longer `just_pressed`, while the change is applied.
+    {
+        let mut input = app.world.resource_mut::<ActionState>();
+        input.press(InputAction::ToggleCamera);
+    }
     run_updates(&mut app, 1);
     let first_person_state = app.world.resource::<State<CameraPerspective>>();
     assert_eq!(*first_person_state.get(), CameraPerspective::FirstPerson, "State should be FirstPerson after toggle is applied");
@@ -200,14 +275,14 @@ fn test_camera_perspective_toggles() {
 
     // --- Second toggle: back to ThirdPerson ---
     {
-        // We need to release the key, so that `just_pressed` is fired again on the next press.
-        let mut input = app.world.resource_mut::<ButtonInput<KeyCode>>();
-        input.release(KeyCode::KeyV);
+        // We need to release the action, so that `just_pressed` is fired again on the next press.
+        let mut input = app.world.resource_mut::<ActionState>();
+        input.release(InputAction::ToggleCamera);
     }
     run_updates(&mut app, 1); // Run an update to process the release.
     {
-        let mut input = app.world.resource_mut::<ButtonInput<KeyCode>>();
-        input.press(KeyCode::KeyV);
+        let mut input = app.world.resource_mut::<ActionState>();
+        input.press(InputAction::ToggleCamera);
     }
     run_updates(&mut app, 1); // Run systems to queue the next state change
 
@@ -215,3 +290,1357 @@ fn test_camera_perspective_toggles() {
     let next_state_2 = app.world.resource::<NextState<CameraPerspective>>();
     assert_eq!(next_state_2.0, Some(CameraPerspective::ThirdPerson), "State change to ThirdPerson should be queued");
 }
+
+#[test]
+fn test_ground_acceleration_curve() {
+    let wish = Vec3::X * 5.0;
+    let footing = Footing::Ground { friction: 1.0 };
+    let mut velocity = Vec3::ZERO;
+
+    for tick in 1..=10 {
+        velocity = step_horizontal_velocity(velocity, wish, footing, TICK);
+        let expected = (GROUND_ACCELERATION * TICK * tick as f32).min(5.0);
+        assert!(
+            (velocity.x - expected).abs() < 1e-5,
+            "tick {tick}: expected {expected}, got {}",
+            velocity.x
+        );
+    }
+    // 5 m/s at 40 m/s² takes 7.5 ticks, so full speed is reached on the 8th tick.
+    assert_eq!(velocity, wish);
+}
+
+#[test]
+fn test_ground_deceleration_curve() {
+    let footing = Footing::Ground { friction: 1.0 };
+    let mut velocity = Vec3::Z * 10.0;
+
+    for tick in 1..=13 {
+        velocity = step_horizontal_velocity(velocity, Vec3::ZERO, footing, TICK);
+        let expected = (10.0 - GROUND_DECELERATION * TICK * tick as f32).max(0.0);
+        assert!(
+            (velocity.z - expected).abs() < 1e-5,
+            "tick {tick}: expected {expected}, got {}",
+            velocity.z
+        );
+    }
+    assert_eq!(velocity, Vec3::ZERO);
+}
+
+#[test]
+fn test_low_friction_surface_takes_longer_to_stop() {
+    let ticks_to_stop = |friction: f32| {
+        let mut velocity = Vec3::X * 5.0;
+        let mut ticks = 0;
+        while velocity != Vec3::ZERO {
+            velocity =
+                step_horizontal_velocity(velocity, Vec3::ZERO, Footing::Ground { friction }, TICK);
+            ticks += 1;
+        }
+        ticks
+    };
+
+    assert_eq!(ticks_to_stop(1.0), 6);
+    assert_eq!(ticks_to_stop(0.1), 60);
+}
+
+#[test]
+fn test_airborne_momentum_is_preserved() {
+    let start = Vec3::new(8.0, 0.0, 0.0);
+
+    // Without input, there is no drag in the air.
+    let coasting = (0..30).fold(start, |v, _| {
+        step_horizontal_velocity(v, Vec3::ZERO, Footing::Airborne, TICK)
+    });
+    assert_eq!(coasting, start);
+
+    // Air control steers slowly compared to the ground.
+    let steered = step_horizontal_velocity(start, Vec3::Z * 5.0, Footing::Airborne, TICK);
+    let delta = (steered - start).length();
+    assert!((delta - GROUND_ACCELERATION * AIR_CONTROL * TICK).abs() < 1e-5);
+}
+
+#[test]
+fn test_slide_keeps_momentum_longer_than_walking_stop() {
+    let start = Vec3::X * 10.0;
+    let walking =
+        step_horizontal_velocity(start, Vec3::ZERO, Footing::Ground { friction: 1.0 }, TICK);
+    let sliding =
+        step_horizontal_velocity(start, Vec3::ZERO, Footing::Sliding { friction: 1.0 }, TICK);
+    assert!(sliding.length() > walking.length());
+    assert!(sliding.length() < start.length());
+}
+
+#[test]
+fn test_player_accelerates_over_fixed_ticks() {
+    let mut app = setup_test_app();
+    app.update();
+    spawn_ground(&mut app);
+    settle_on_ground(&mut app);
+
+    app.world
+        .resource_mut::<ActionState>()
+        .press(InputAction::MoveForward);
+
+    let mut expected = Vec3::ZERO;
+    for tick in 1..=10 {
+        app.update();
+        let velocity = player_velocity(&mut app).horizontal();
+        // The rig looks along -Z by default, so forward input accelerates along -Z.
+        expected = step_horizontal_velocity(
+            expected,
+            Vec3::NEG_Z * 5.0,
+            Footing::Ground { friction: 1.0 },
+            TICK,
+        );
+        assert!(
+            (velocity - expected).length() < 1e-4,
+            "tick {tick}: expected {expected}, got {velocity}"
+        );
+    }
+    assert!((player_velocity(&mut app).horizontal().length() - 5.0).abs() < 1e-4);
+}
+
+#[test]
+fn test_jump_preserves_horizontal_momentum() {
+    let mut app = setup_test_app();
+    app.update();
+    spawn_ground(&mut app);
+    settle_on_ground(&mut app);
+
+    {
+        let mut input = app.world.resource_mut::<ActionState>();
+        input.press(InputAction::MoveForward);
+        input.press(InputAction::Sprint);
+    }
+    run_updates(&mut app, 30);
+    let before = player_velocity(&mut app).horizontal();
+
+    app.world
+        .resource_mut::<ActionState>()
+        .press(InputAction::Jump);
+    app.update();
+
+    let after = player_velocity(&mut app);
+    assert!((after.0.y - JUMP_SPEED).abs() < 1e-4);
+    assert!((after.horizontal() - before).length() < 1e-4);
+}
+
+fn player_stamina(app: &mut App) -> Stamina {
+    let mut query = app.world.query_filtered::<&Stamina, With<Player>>();
+    query.get_single(&app.world).unwrap().clone()
+}
+
+#[test]
+fn test_sprint_exhaustion_falls_back_to_walking() {
+    let mut app = setup_test_app();
+    app.update();
+    spawn_ground(&mut app);
+    settle_on_ground(&mut app);
+
+    {
+        let mut input = app.world.resource_mut::<ActionState>();
+        input.press(InputAction::MoveForward);
+        input.press(InputAction::Sprint);
+    }
+    run_updates(&mut app, 5);
+    assert_eq!(
+        *app.world.resource::<State<MovementState>>().get(),
+        MovementState::Sprinting
+    );
+
+    // Sprinting drains 12 stamina per second from a pool of 100, so it runs out after ~8.3s.
+    let mut exhausted_event = false;
+    let mut reader = app.world.resource::<Events<StaminaEvent>>().get_reader();
+    for _ in 0..9 * 60 {
+        app.update();
+        let events = app.world.resource::<Events<StaminaEvent>>();
+        exhausted_event |= reader
+            .read(events)
+            .any(|event| *event == StaminaEvent::Exhausted);
+    }
+
+    assert!(exhausted_event, "An Exhausted event should be emitted");
+    assert!(player_stamina(&mut app).is_exhausted());
+    assert_eq!(
+        *app.world.resource::<State<MovementState>>().get(),
+        MovementState::Walking,
+        "Exhausted player should not be able to keep sprinting"
+    );
+}
+
+#[test]
+fn test_slide_consumes_stamina() {
+    let mut app = setup_test_app();
+    app.update();
+    spawn_ground(&mut app);
+    settle_on_ground(&mut app);
+
+    {
+        let mut input = app.world.resource_mut::<ActionState>();
+        input.press(InputAction::MoveForward);
+        input.press(InputAction::Sprint);
+    }
+    run_updates(&mut app, 30);
+    let before = player_stamina(&mut app).current;
+
+    app.world
+        .resource_mut::<ActionState>()
+        .press(InputAction::Crouch);
+    run_updates(&mut app, 2);
+
+    assert_eq!(
+        *app.world.resource::<State<MovementState>>().get(),
+        MovementState::Sliding
+    );
+    let after = player_stamina(&mut app).current;
+    assert!(
+        before - after >= 15.0,
+        "Sliding should cost stamina (before {before}, after {after})"
+    );
+}
+
+#[test]
+fn test_dive_consumes_stamina_and_launches_forward() {
+    let mut app = setup_test_app();
+    app.update();
+    spawn_ground(&mut app);
+    settle_on_ground(&mut app);
+
+    {
+        let mut input = app.world.resource_mut::<ActionState>();
+        input.press(InputAction::MoveForward);
+        input.press(InputAction::Sprint);
+    }
+    run_updates(&mut app, 30);
+    let before = player_stamina(&mut app).current;
+    let sprint_speed = player_velocity(&mut app).horizontal().length();
+
+    {
+        let mut input = app.world.resource_mut::<ActionState>();
+        input.press(InputAction::Crouch);
+        input.press(InputAction::Jump);
+    }
+    run_updates(&mut app, 2);
+
+    assert_eq!(
+        *app.world.resource::<State<MovementState>>().get(),
+        MovementState::Diving
+    );
+    let after = player_stamina(&mut app).current;
+    assert!(
+        before - after >= 20.0,
+        "Diving should cost stamina (before {before}, after {after})"
+    );
+    let velocity = player_velocity(&mut app);
+    assert!(velocity.0.y > 0.0 && velocity.0.y < JUMP_SPEED);
+    assert!(velocity.horizontal().length() > sprint_speed);
+
+    // The dive lasts until the player lands.
+    {
+        let mut input = app.world.resource_mut::<ActionState>();
+        input.release(InputAction::Jump);
+        input.release(InputAction::Crouch);
+    }
+    for _ in 0..60 {
+        app.update();
+        if *app.world.resource::<State<MovementState>>().get() != MovementState::Diving {
+            break;
+        }
+    }
+    assert_ne!(
+        *app.world.resource::<State<MovementState>>().get(),
+        MovementState::Diving
+    );
+    assert!(player_grounded(&mut app));
+}
+
+#[test]
+fn test_tired_player_cannot_dive() {
+    let mut app = setup_test_app();
+    app.update();
+    spawn_ground(&mut app);
+    settle_on_ground(&mut app);
+
+    {
+        let mut input = app.world.resource_mut::<ActionState>();
+        input.press(InputAction::MoveForward);
+        input.press(InputAction::Sprint);
+    }
+    run_updates(&mut app, 30);
+    {
+        let mut query = app.world.query_filtered::<&mut Stamina, With<Player>>();
+        query.single_mut(&mut app.world).current = 10.0;
+    }
+
+    {
+        let mut input = app.world.resource_mut::<ActionState>();
+        input.press(InputAction::Crouch);
+        input.press(InputAction::Jump);
+    }
+    for _ in 0..10 {
+        app.update();
+        assert_ne!(
+            *app.world.resource::<State<MovementState>>().get(),
+            MovementState::Diving
+        );
+    }
+}
+
+/// Top of the ground slab spawned by `spawn_ground`.
+const GROUND_TOP: f32 = 0.5;
+
+/// Places the player with its feet `height` metres above the ground, at rest.
+fn place_player_above_ground(app: &mut App, height: f32) {
+    let mut query = app
+        .world
+        .query_filtered::<(&mut Transform, &mut CharacterVelocity), With<Player>>();
+    let (mut transform, mut velocity) = query.single_mut(&mut app.world);
+    transform.translation.y = GROUND_TOP + PLAYER_FEET_OFFSET + height;
+    velocity.0 = Vec3::ZERO;
+}
+
+/// Drops the player from `height` metres and returns the `Landed` impact speed and the damage taken.
+fn drop_player(app: &mut App, height: f32) -> (f32, f32) {
+    app.update();
+    spawn_ground(app);
+    settle_on_ground(app);
+    place_player_above_ground(app, height);
+
+    let mut reader = app.world.resource::<Events<Landed>>().get_reader();
+    for _ in 0..10 * 60 {
+        app.update();
+        let events = app.world.resource::<Events<Landed>>();
+        if let Some(landed) = reader.read(events).last().copied() {
+            // Let the damage event be applied.
+            app.update();
+            let mut query = app.world.query_filtered::<&Health, With<Player>>();
+            let health = query.single(&app.world);
+            return (landed.impact_speed, health.max - health.current);
+        }
+    }
+    panic!("player never landed after a {height}m drop");
+}
+
+#[test]
+fn test_fall_damage_curve() {
+    assert_eq!(fall_damage(SAFE_IMPACT_SPEED, 0.0), 0.0);
+    assert!(fall_damage(SAFE_IMPACT_SPEED + 1.0, 0.0) > 0.0);
+    assert_eq!(
+        fall_damage(20.0, ROLL_MITIGATION),
+        fall_damage(20.0, 0.0) * (1.0 - ROLL_MITIGATION)
+    );
+    assert_eq!(fall_damage(30.0, 1.0), 0.0);
+}
+
+#[test]
+fn test_short_drop_is_harmless() {
+    let mut app = setup_test_app();
+    let (impact_speed, damage) = drop_player(&mut app, 3.0);
+
+    // v = sqrt(2gh) ~= 7.7 m/s for a 3m drop.
+    assert!(
+        (impact_speed - 7.7).abs() < 0.5,
+        "impact speed {impact_speed}"
+    );
+    assert_eq!(damage, 0.0);
+}
+
+#[test]
+fn test_high_drop_hurts() {
+    let mut app = setup_test_app();
+    let (impact_speed, damage) = drop_player(&mut app, 10.0);
+
+    // v = sqrt(2gh) ~= 14.0 m/s for a 10m drop.
+    assert!(
+        (impact_speed - 14.0).abs() < 0.5,
+        "impact speed {impact_speed}"
+    );
+    assert!(
+        (damage - fall_damage(impact_speed, 0.0)).abs() < 1e-3,
+        "damage {damage}"
+    );
+    assert!(damage > 20.0 && damage < 35.0, "damage {damage}");
+}
+
+#[test]
+fn test_crouch_roll_mitigates_fall_damage() {
+    let mut app = setup_test_app();
+    app.world
+        .resource_mut::<ActionState>()
+        .press(InputAction::Crouch);
+    let (impact_speed, damage) = drop_player(&mut app, 10.0);
+
+    assert!(damage > 0.0);
+    assert!((damage - fall_damage(impact_speed, ROLL_MITIGATION)).abs() < 1e-3);
+}
+
+#[test]
+fn test_lethal_drop_kills() {
+    let mut app = setup_test_app();
+    let mut reader = app.world.resource::<Events<Died>>().get_reader();
+    let (_, damage) = drop_player(&mut app, 40.0);
+
+    assert_eq!(damage, 100.0);
+    let events = app.world.resource::<Events<Died>>();
+    assert_eq!(reader.read(events).count(), 1);
+}
+
+#[test]
+fn test_landing_in_water_negates_damage() {
+    let mut app = setup_test_app();
+    let mut world_data = world::WorldData::default();
+    // The voxel the player's feet end up in when standing on the ground slab.
+    world_data.set_voxel(IVec3::ZERO, world::Voxel(world::MaterialId::WATER));
+    app.insert_resource(world_data);
+    app.init_resource::<world::MaterialRegistry>();
+
+    let (impact_speed, damage) = drop_player(&mut app, 10.0);
+    assert!(impact_speed > SAFE_IMPACT_SPEED);
+    assert_eq!(damage, 0.0);
+}
+
+/// Spawns a 6m tall ladder volume just in front of the player (the rig looks along -Z).
+fn spawn_ladder(app: &mut App) {
+    app.world.spawn((
+        Climbable,
+        TransformBundle::from_transform(Transform::from_xyz(0.0, GROUND_TOP + 3.0, -0.8)),
+        Collider::cuboid(0.5, 3.0, 0.2),
+        Sensor,
+    ));
+}
+
+fn movement_state(app: &App) -> MovementState {
+    *app.world.resource::<State<MovementState>>().get()
+}
+
+fn player_translation(app: &mut App) -> Vec3 {
+    let mut query = app.world.query_filtered::<&Transform, With<Player>>();
+    query.single(&app.world).translation
+}
+
+/// Walks the player into the ladder until it starts climbing.
+fn mount_ladder(app: &mut App) {
+    app.update();
+    spawn_ground(app);
+    settle_on_ground(app);
+    spawn_ladder(app);
+
+    app.world
+        .resource_mut::<ActionState>()
+        .press(InputAction::MoveForward);
+    for _ in 0..60 {
+        app.update();
+        if movement_state(app) == MovementState::Climbing {
+            return;
+        }
+    }
+    panic!("player never started climbing");
+}
+
+#[test]
+fn test_ladder_climbing_maps_forward_to_up() {
+    let mut app = setup_test_app();
+    mount_ladder(&mut app);
+
+    run_updates(&mut app, 20);
+    assert_eq!(movement_state(&app), MovementState::Climbing);
+    assert!((player_velocity(&mut app).0 - Vec3::Y * CLIMB_SPEED).length() < 1e-5);
+
+    // Letting go of the input holds the player in place without gravity.
+    app.world
+        .resource_mut::<ActionState>()
+        .release(InputAction::MoveForward);
+    run_updates(&mut app, 2);
+    let held = player_translation(&mut app);
+    run_updates(&mut app, 30);
+    assert_eq!(movement_state(&app), MovementState::Climbing);
+    assert!((player_translation(&mut app) - held).length() < 1e-4);
+}
+
+#[test]
+fn test_ladder_dismounts_at_top() {
+    let mut app = setup_test_app();
+    mount_ladder(&mut app);
+
+    let mut topped_out = None;
+    for _ in 0..5 * 60 {
+        app.update();
+        if movement_state(&app) != MovementState::Climbing {
+            topped_out = Some((player_translation(&mut app), player_velocity(&mut app)));
+            break;
+        }
+    }
+
+    let (position, velocity) = topped_out.expect("player should climb off the top");
+    // The ladder's top is at 6.5m, so the capsule's feet must have cleared it.
+    assert!(position.y - PLAYER_FEET_OFFSET > 6.0, "position {position}");
+    assert!(velocity.0.y > 0.0);
+    assert!(
+        velocity.0.z < 0.0,
+        "player should be carried onto the ledge"
+    );
+}
+
+#[test]
+fn test_mantle_over_the_top_consumes_stamina() {
+    let mut app = setup_test_app();
+    mount_ladder(&mut app);
+
+    let mut before = player_stamina(&mut app).current;
+    for _ in 0..5 * 60 {
+        app.update();
+        if movement_state(&app) == MovementState::Mantling {
+            break;
+        }
+        before = player_stamina(&mut app).current;
+    }
+
+    assert_eq!(movement_state(&app), MovementState::Mantling);
+    let after = player_stamina(&mut app).current;
+    assert!(
+        before - after >= 20.0,
+        "Mantling should cost stamina (before {before}, after {after})"
+    );
+
+    // The mantle lasts until the player lands.
+    for _ in 0..3 * 60 {
+        app.update();
+        if movement_state(&app) != MovementState::Mantling {
+            break;
+        }
+    }
+    assert_ne!(movement_state(&app), MovementState::Mantling);
+    assert!(player_grounded(&mut app));
+}
+
+#[test]
+fn test_tired_player_lets_go_instead_of_mantling() {
+    let mut app = setup_test_app();
+    mount_ladder(&mut app);
+    {
+        let mut query = app.world.query_filtered::<&mut Stamina, With<Player>>();
+        query.single_mut(&mut app.world).current = 19.0;
+    }
+
+    let mut let_go = None;
+    for _ in 0..5 * 60 {
+        app.update();
+        assert_ne!(movement_state(&app), MovementState::Mantling);
+        if movement_state(&app) != MovementState::Climbing {
+            let_go = Some((player_translation(&mut app), player_velocity(&mut app)));
+            break;
+        }
+    }
+
+    let (position, velocity) = let_go.expect("player should let go at the top");
+    assert!(position.y - PLAYER_FEET_OFFSET > 6.0, "position {position}");
+    assert!(
+        velocity.0.y <= 0.0,
+        "player should not be pulled over the top"
+    );
+}
+
+#[test]
+fn test_ladder_dismounts_at_bottom() {
+    let mut app = setup_test_app();
+    mount_ladder(&mut app);
+    run_updates(&mut app, 20);
+
+    {
+        let mut input = app.world.resource_mut::<ActionState>();
+        input.release(InputAction::MoveForward);
+        input.press(InputAction::MoveBackward);
+    }
+    for _ in 0..3 * 60 {
+        app.update();
+        if movement_state(&app) != MovementState::Climbing {
+            break;
+        }
+    }
+
+    assert_ne!(movement_state(&app), MovementState::Climbing);
+    assert!(player_grounded(&mut app));
+}
+
+#[test]
+fn test_jump_off_ladder_sideways() {
+    let mut app = setup_test_app();
+    mount_ladder(&mut app);
+    run_updates(&mut app, 20);
+
+    {
+        let mut input = app.world.resource_mut::<ActionState>();
+        input.release(InputAction::MoveForward);
+        input.press(InputAction::MoveRight);
+        input.press(InputAction::Jump);
+    }
+    app.update();
+
+    let velocity = player_velocity(&mut app);
+    assert!(velocity.0.z > 0.0, "jump should push away from the ladder");
+    assert!(velocity.0.x.abs() > 0.0, "jump should go sideways");
+    app.update();
+    assert_ne!(movement_state(&app), MovementState::Climbing);
+}
+
+/// Spawns a 5m deep pool volume on top of the ground slab, around the player.
+fn spawn_pool(app: &mut App) {
+    app.world.spawn((
+        WaterVolume,
+        TransformBundle::from_transform(Transform::from_xyz(0.0, GROUND_TOP + 2.5, 0.0)),
+        Collider::cuboid(10.0, 2.5, 10.0),
+        Sensor,
+    ));
+}
+
+fn player_submersion(app: &mut App) -> Submersion {
+    let mut query = app.world.query_filtered::<&Submersion, With<Player>>();
+    *query.single(&app.world)
+}
+
+#[test]
+fn test_player_floats_and_treads_water_in_pool() {
+    let mut app = setup_test_app();
+    app.update();
+    spawn_ground(&mut app);
+    settle_on_ground(&mut app);
+    spawn_pool(&mut app);
+
+    run_updates(&mut app, 5);
+    assert_eq!(movement_state(&app), MovementState::Swimming);
+    assert!(player_submersion(&mut app).head_underwater());
+
+    // Buoyancy lifts the player until they tread water with their head above the surface.
+    run_updates(&mut app, 10 * 60);
+    assert_eq!(movement_state(&app), MovementState::Swimming);
+    let submersion = player_submersion(&mut app);
+    assert!(
+        (submersion.depth - TREAD_SUBMERSION).abs() < 0.05,
+        "submersion {}",
+        submersion.depth
+    );
+    assert!(!submersion.head_underwater());
+    assert!(player_velocity(&mut app).0.length() < 0.1);
+}
+
+#[test]
+fn test_player_drowns_when_staying_underwater() {
+    let mut app = setup_test_app();
+    app.update();
+    spawn_ground(&mut app);
+    settle_on_ground(&mut app);
+    spawn_pool(&mut app);
+
+    {
+        let mut query = app.world.query_filtered::<&mut Breath, With<Player>>();
+        *query.single_mut(&mut app.world) = Breath::new(1.0);
+    }
+    // Hold crouch to keep diving to the bottom.
+    app.world
+        .resource_mut::<ActionState>()
+        .press(InputAction::Crouch);
+    run_updates(&mut app, 3 * 60);
+
+    let mut query = app.world.query_filtered::<&Health, With<Player>>();
+    let health = query.single(&app.world);
+    assert!(
+        health.current < health.max,
+        "player should take drowning damage"
+    );
+}
+
+#[test]
+fn test_swimming_in_voxel_water() {
+    let mut app = setup_test_app();
+    let mut world_data = world::WorldData::default();
+    for x in -3..3 {
+        for y in 0..5 {
+            for z in -3..3 {
+                world_data.set_voxel(IVec3::new(x, y, z), world::Voxel(world::MaterialId::WATER));
+            }
+        }
+    }
+    app.insert_resource(world_data);
+    app.init_resource::<world::MaterialRegistry>();
+
+    app.update();
+    spawn_ground(&mut app);
+    run_updates(&mut app, 5);
+
+    assert_eq!(movement_state(&app), MovementState::Swimming);
+    assert!(player_submersion(&mut app).depth > 0.9);
+}
+
+/// Plays a scripted session in the movement test scene: walking, sprinting, jumping, strafing
+/// and crouching. Returns the player's position after every update.
+fn play_scripted_session(app: &mut App) -> Vec<Vec3> {
+    use InputAction::*;
+    let script: [(&[InputAction], usize); 6] = [
+        (&[MoveForward], 30),
+        (&[MoveForward, Sprint], 40),
+        (&[MoveForward, Sprint, Jump], 5),
+        (&[MoveRight], 30),
+        (&[MoveLeft, Crouch], 20),
+        (&[], 30),
+    ];
+
+    let mut trajectory = Vec::new();
+    for (held, ticks) in script {
+        for _ in 0..ticks {
+            {
+                let mut actions = app.world.resource_mut::<ActionState>();
+                for action in InputAction::ALL {
+                    if held.contains(&action) {
+                        actions.press(action);
+                    } else {
+                        actions.release(action);
+                    }
+                }
+            }
+            app.update();
+            trajectory.push(player_translation(app));
+        }
+    }
+    trajectory
+}
+
+#[test]
+fn test_replay_reproduces_trajectory_bit_for_bit() {
+    let mut app = setup_test_app();
+    start_recording(&mut app.world, None);
+    app.update();
+    spawn_ground(&mut app);
+    let recorded = play_scripted_session(&mut app);
+    let replay = stop_recording(&mut app.world).unwrap();
+    // The first update only starts the clock, so every later update runs one recorded tick.
+    assert_eq!(replay.frames.len(), recorded.len());
+
+    // Replays go through their file format.
+    let replay = Replay::from_ron(&replay.to_ron().unwrap()).unwrap();
+
+    let mut app = setup_test_app();
+    start_playback(&mut app.world, replay);
+    app.update();
+    spawn_ground(&mut app);
+    let replayed: Vec<Vec3> = (0..recorded.len())
+        .map(|_| {
+            app.update();
+            player_translation(&mut app)
+        })
+        .collect();
+
+    let bits = |positions: &[Vec3]| {
+        positions
+            .iter()
+            .map(|position| position.to_array().map(f32::to_bits))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(bits(&replayed), bits(&recorded));
+    assert!(recorded.last().unwrap().distance(recorded[0]) > 5.0);
+}
+
+/// The player's position, the rig's look angles and the perspective after a tick.
+fn view_snapshot(app: &mut App) -> (Vec3, (f32, f32), CameraPerspective) {
+    let translation = player_translation(app);
+    let mut query = app.world.query::<&CameraRig>();
+    let angles = query.single(&app.world).look_angles();
+    let perspective = *app.world.resource::<State<CameraPerspective>>().get();
+    (translation, angles, perspective)
+}
+
+#[test]
+fn test_replay_reproduces_camera_turns() {
+    use InputAction::{Aim, MoveForward, ToggleCamera};
+
+    let mut app = setup_test_app();
+    start_recording(&mut app.world, None);
+    app.update();
+    spawn_ground(&mut app);
+    let mut recorded = Vec::new();
+    for tick in 0..90 {
+        {
+            let mut actions = app.world.resource_mut::<ActionState>();
+            actions.press(MoveForward);
+            actions.set_value(ToggleCamera, if tick == 30 { 1.0 } else { 0.0 });
+            actions.set_value(Aim, if (50..70).contains(&tick) { 1.0 } else { 0.0 });
+        }
+        if tick < 60 {
+            app.world.send_event(MouseMotion {
+                delta: Vec2::new(15.0, -2.0),
+            });
+        }
+        app.update();
+        recorded.push(view_snapshot(&mut app));
+    }
+    let replay = stop_recording(&mut app.world).unwrap();
+    let replay = Replay::from_ron(&replay.to_ron().unwrap()).unwrap();
+
+    // The player's own devices don't get a say while the replay plays.
+    let mut app = setup_test_app();
+    app.add_plugins(InputActionsPlugin {
+        bindings_path: "does-not-exist/input.ron".into(),
+    });
+    start_playback(&mut app.world, replay);
+    app.update();
+    spawn_ground(&mut app);
+    app.world
+        .resource_mut::<ButtonInput<KeyCode>>()
+        .press(KeyCode::KeyS);
+    let replayed: Vec<_> = (0..recorded.len())
+        .map(|_| {
+            app.world.send_event(MouseMotion {
+                delta: Vec2::new(-40.0, 10.0),
+            });
+            app.update();
+            view_snapshot(&mut app)
+        })
+        .collect();
+
+    // Mouse-look turns the rig between ticks, and playback restores the turn at the start of the
+    // tick that recorded it, so the view only lines up with the recording by the time movement
+    // reads it.
+    let steering = |snapshots: &[(Vec3, (f32, f32), CameraPerspective)]| {
+        snapshots
+            .iter()
+            .map(|(translation, _, perspective)| {
+                (translation.to_array().map(f32::to_bits), *perspective)
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(steering(&replayed), steering(&recorded));
+    assert_eq!(replayed.last(), recorded.last());
+    let (start, end) = (recorded[0], recorded[recorded.len() - 1]);
+    assert_eq!(end.2, CameraPerspective::FirstPerson);
+    assert!(end.1 .0 < start.1 .0 - 1.0, "the camera should have turned");
+    // Turning the camera steered the player off a straight line.
+    assert!((end.0.x - start.0.x).abs() > 1.0);
+}
+
+fn camera_collision_distance(app: &mut App) -> f32 {
+    let mut query = app.world.query::<&CameraRig>();
+    query.single(&app.world).collision_distance()
+}
+
+#[test]
+fn test_camera_pulls_in_when_wall_is_behind_player() {
+    let mut app = setup_test_app();
+    app.update();
+    spawn_ground(&mut app);
+    settle_on_ground(&mut app);
+    assert_eq!(camera_collision_distance(&mut app), 5.0);
+
+    // The default rig looks down at 45° from behind (+Z), so a wall at z = 1.75 blocks its boom.
+    let wall = app
+        .world
+        .spawn((
+            TransformBundle::from_transform(Transform::from_xyz(0.0, 2.0, 2.0)),
+            RigidBody::Fixed,
+            Collider::cuboid(5.0, 5.0, 0.25),
+        ))
+        .id();
+    run_updates(&mut app, 2);
+
+    // The 0.2m camera sphere touches the wall when its center is at z = 1.55.
+    let expected = (1.75 - 0.2) / std::f32::consts::FRAC_PI_4.cos();
+    let distance = camera_collision_distance(&mut app);
+    assert!(
+        (distance - expected).abs() < 0.05,
+        "distance {distance}, expected {expected}"
+    );
+
+    // Once the wall is gone the camera eases back out rather than snapping.
+    app.world.despawn(wall);
+    run_updates(&mut app, 2);
+    let easing = camera_collision_distance(&mut app);
+    assert!(easing > distance && easing < 5.0, "distance {easing}");
+
+    run_updates(&mut app, 3 * 60);
+    assert!((camera_collision_distance(&mut app) - 5.0).abs() < 0.01);
+}
+
+#[test]
+fn test_camera_pulls_in_before_voxel_wall() {
+    let mut app = setup_test_app();
+    let mut world_data = world::WorldData::default();
+    for x in -3..=3 {
+        for y in 0..=6 {
+            world_data.set_voxel(IVec3::new(x, y, 2), world::Voxel(world::MaterialId::STONE));
+        }
+    }
+    app.insert_resource(world_data);
+    app.init_resource::<world::MaterialRegistry>();
+    app.update();
+    spawn_ground(&mut app);
+    settle_on_ground(&mut app);
+
+    // The boom crosses z = 2 about 2.8m out; the camera stays a sphere radius short of that.
+    let distance = camera_collision_distance(&mut app);
+    assert!(distance > 2.3 && distance < 2.8, "distance {distance}");
+}
+
+fn set_perspective(app: &mut App, perspective: CameraPerspective) {
+    app.world
+        .resource_mut::<NextState<CameraPerspective>>()
+        .set(perspective);
+    app.update();
+}
+
+fn move_mouse(app: &mut App, delta: Vec2) {
+    app.world.send_event(MouseMotion { delta });
+    app.update();
+}
+
+fn camera_forward(app: &mut App) -> Vec3 {
+    let mut query = app
+        .world
+        .query_filtered::<&Transform, (With<CameraRig>, Without<Player>)>();
+    query.single(&app.world).forward().into()
+}
+
+fn camera_focus(app: &mut App) -> Vec3 {
+    let mut query = app.world.query::<&CameraRig>();
+    query.single(&app.world).focus
+}
+
+fn player_facing(app: &mut App) -> Vec3 {
+    let mut query = app.world.query_filtered::<&Transform, With<Player>>();
+    query.single(&app.world).rotation * Vec3::Z
+}
+
+/// Spawns the scene with the player standing on the ground and the camera in first person.
+fn setup_first_person(app: &mut App) {
+    app.update();
+    spawn_ground(app);
+    settle_on_ground(app);
+    set_perspective(app, CameraPerspective::FirstPerson);
+}
+
+#[test]
+fn test_first_person_mouse_look_turns_player() {
+    let mut app = setup_test_app();
+    setup_first_person(&mut app);
+
+    // 180 pixels at the default sensitivity turn the view 90° to the right.
+    move_mouse(&mut app, Vec2::new(180.0, 0.0));
+    run_updates(&mut app, 2);
+
+    let forward = camera_forward(&mut app);
+    let horizontal = Vec3::new(forward.x, 0.0, forward.z).normalize();
+    assert!(horizontal.distance(Vec3::X) < 1e-3, "forward {forward}");
+    assert!(player_facing(&mut app).distance(Vec3::X) < 1e-3);
+}
+
+#[test]
+fn test_first_person_pitch_is_clamped() {
+    let mut app = setup_test_app();
+    setup_first_person(&mut app);
+
+    move_mouse(&mut app, Vec2::new(0.0, -10_000.0));
+    let looking_up = camera_forward(&mut app).y;
+    assert!(
+        looking_up > 0.99 && looking_up < 1.0,
+        "forward.y {looking_up}"
+    );
+
+    move_mouse(&mut app, Vec2::new(0.0, 20_000.0));
+    let looking_down = camera_forward(&mut app).y;
+    assert!(
+        looking_down < -0.99 && looking_down > -1.0,
+        "forward.y {looking_down}"
+    );
+}
+
+#[test]
+fn test_first_person_strafing_keeps_facing() {
+    let mut app = setup_test_app();
+    setup_first_person(&mut app);
+    let start = player_translation(&mut app);
+
+    app.world
+        .resource_mut::<ActionState>()
+        .press(InputAction::MoveRight);
+    run_updates(&mut app, 30);
+
+    let moved = player_translation(&mut app) - start;
+    assert!(
+        moved.x > 0.5,
+        "strafing right should move along +X, moved {moved}"
+    );
+    assert!(moved.z.abs() < 1e-3);
+    assert!(player_facing(&mut app).distance(Vec3::NEG_Z) < 1e-3);
+}
+
+#[test]
+fn test_first_person_eye_height_follows_stance() {
+    let mut app = setup_test_app();
+    setup_first_person(&mut app);
+    run_updates(&mut app, 5);
+    let standing = camera_focus(&mut app).y - player_translation(&mut app).y;
+
+    app.world
+        .resource_mut::<ActionState>()
+        .press(InputAction::Crouch);
+    run_updates(&mut app, 60);
+    assert_eq!(movement_state(&app), MovementState::Crouching);
+    let crouching = camera_focus(&mut app).y - player_translation(&mut app).y;
+
+    let feet = -PLAYER_FEET_OFFSET;
+    assert!((standing - (feet + eye_height(MovementState::Idle))).abs() < 0.01);
+    assert!((crouching - (feet + eye_height(MovementState::Crouching))).abs() < 0.01);
+}
+
+#[test]
+fn test_switching_perspective_preserves_view_direction() {
+    let mut app = setup_test_app();
+    app.update();
+    spawn_ground(&mut app);
+    settle_on_ground(&mut app);
+
+    move_mouse(&mut app, Vec2::new(60.0, -20.0));
+    // Let the smoothed third-person view catch up.
+    run_updates(&mut app, 60);
+    let third_person = camera_forward(&mut app);
+
+    set_perspective(&mut app, CameraPerspective::FirstPerson);
+    app.update();
+    assert!(camera_forward(&mut app).distance(third_person) < 1e-5);
+
+    set_perspective(&mut app, CameraPerspective::ThirdPerson);
+    app.update();
+    assert!(camera_forward(&mut app).distance(third_person) < 1e-5);
+}
+
+fn main_camera_translation(app: &mut App) -> Vec3 {
+    let mut query = app.world.query_filtered::<&Transform, With<MainCamera>>();
+    query.single(&app.world).translation
+}
+
+fn camera_rig(app: &mut App) -> &CameraRig {
+    let mut query = app.world.query::<&CameraRig>();
+    query.single(&app.world)
+}
+
+/// Presses `action` for a single update, then keeps holding it so it is only `just_pressed` once.
+fn tap_and_hold(app: &mut App, action: InputAction) {
+    app.world.resource_mut::<ActionState>().press(action);
+    app.update();
+    app.world.resource_mut::<ActionState>().press(action);
+}
+
+#[test]
+fn test_aiming_moves_camera_over_the_shoulder() {
+    let mut app = setup_test_app();
+    app.update();
+    spawn_ground(&mut app);
+    settle_on_ground(&mut app);
+    let profiles = app.world.resource::<CameraProfiles>().clone();
+    assert!(player_facing(&mut app).distance(Vec3::Z) < 1e-3);
+
+    tap_and_hold(&mut app, InputAction::Aim);
+    let blending = main_camera_translation(&mut app);
+    assert!(blending.x > 0.0 && blending.x < profiles.aim.shoulder_offset.x);
+
+    run_updates(&mut app, 60);
+    let aimed = main_camera_translation(&mut app);
+    assert!(camera_rig(&mut app).is_aiming());
+    assert!(aimed.distance(profiles.aim.shoulder_offset + Vec3::Z * profiles.aim.distance) < 0.01);
+    assert!((camera_rig(&mut app).fov() - profiles.aim.fov).abs() < 0.01);
+    // The player turns to face where the camera aims.
+    assert!(player_facing(&mut app).distance(Vec3::NEG_Z) < 1e-3);
+
+    // Aiming slows mouse-look: 180 pixels now turn the view 45° instead of 90°.
+    move_mouse(&mut app, Vec2::new(180.0, 0.0));
+    run_updates(&mut app, 60);
+    let forward = camera_forward(&mut app);
+    let yaw = forward.x.atan2(-forward.z);
+    assert!(
+        (yaw - std::f32::consts::FRAC_PI_4).abs() < 1e-3,
+        "yaw {yaw}"
+    );
+}
+
+#[test]
+fn test_swapping_shoulders_blends_across() {
+    let mut app = setup_test_app();
+    app.update();
+    spawn_ground(&mut app);
+    settle_on_ground(&mut app);
+    let offset = app.world.resource::<CameraProfiles>().aim.shoulder_offset.x;
+
+    tap_and_hold(&mut app, InputAction::Aim);
+    run_updates(&mut app, 60);
+    assert!((main_camera_translation(&mut app).x - offset).abs() < 0.01);
+
+    tap_and_hold(&mut app, InputAction::SwapShoulder);
+    assert_eq!(camera_rig(&mut app).shoulder(), Shoulder::Left);
+    let crossing = main_camera_translation(&mut app).x;
+    assert!(crossing > -offset && crossing < offset, "x {crossing}");
+
+    run_updates(&mut app, 60);
+    assert!((main_camera_translation(&mut app).x + offset).abs() < 0.01);
+}
+
+#[test]
+fn test_camera_profiles_round_trip_through_config_file() {
+    let mut profiles = CameraProfiles::default();
+    profiles.aim.fov = 40.0;
+    profiles.aim.shoulder_offset = Vec3::new(0.8, 0.3, 0.0);
+
+    let path = std::env::temp_dir()
+        .join(format!("protocol-zero-camera-{}", std::process::id()))
+        .join("camera.ron");
+    profiles.save(&path).unwrap();
+    let loaded = CameraProfiles::load(&path).unwrap();
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+    assert_eq!(loaded, profiles);
+}
+
+#[test]
+fn test_camera_shake_is_layered_on_and_settles() {
+    let mut app = setup_test_app();
+    app.update();
+    spawn_ground(&mut app);
+    settle_on_ground(&mut app);
+    let rest = main_camera_translation(&mut app);
+
+    app.world.send_event(CameraShake { trauma: 1.0 });
+    app.update();
+    let shaken = main_camera_translation(&mut app);
+    assert!(shaken.distance(rest) > 1e-3);
+    // The shake never leaks into where the rig places the camera.
+    assert!(shaken.distance(rest) <= 0.15 * 3f32.sqrt() + 1e-3);
+
+    run_updates(&mut app, 90);
+    assert!(main_camera_translation(&mut app).distance(rest) < 1e-4);
+    let mut query = app.world.query_filtered::<&Transform, With<MainCamera>>();
+    assert!(
+        query
+            .single(&app.world)
+            .rotation
+            .angle_between(Quat::IDENTITY)
+            < 1e-4
+    );
+}
+
+fn camera_perspective(app: &mut App) -> CameraPerspective {
+    *app.world.resource::<State<CameraPerspective>>().get()
+}
+
+#[test]
+fn test_free_fly_detaches_camera_and_flies_through_geometry() {
+    let mut app = setup_test_app();
+    app.update();
+    spawn_ground(&mut app);
+    settle_on_ground(&mut app);
+    let player_start = player_translation(&mut app);
+
+    tap_and_hold(&mut app, InputAction::ToggleFreeFly);
+    app.world
+        .resource_mut::<ActionState>()
+        .release(InputAction::ToggleFreeFly);
+    app.update();
+    assert_eq!(camera_perspective(&mut app), CameraPerspective::FreeFly);
+    // Entering free fly starts from where the camera was, well above the player.
+    let start = camera_focus(&mut app);
+    assert!(start.y > player_start.y + 2.0);
+    let forward = camera_forward(&mut app);
+
+    // The default view looks 45° down, so a second of flying forward dives through the ground.
+    app.world
+        .resource_mut::<ActionState>()
+        .press(InputAction::MoveForward);
+    run_updates(&mut app, 60);
+    let flown = camera_focus(&mut app) - start;
+    assert!((flown.length() - 10.0).abs() < 0.2, "flew {flown}");
+    assert!(flown.normalize().distance(forward) < 1e-3);
+    assert!(camera_focus(&mut app).y < 0.0);
+    assert!(player_translation(&mut app).distance(player_start) < 1e-3);
+
+    app.world
+        .resource_mut::<ActionState>()
+        .release(InputAction::MoveForward);
+    tap_and_hold(&mut app, InputAction::ToggleFreeFly);
+    app.update();
+    assert_eq!(camera_perspective(&mut app), CameraPerspective::ThirdPerson);
+    assert!(camera_focus(&mut app).distance(player_translation(&mut app)) < 1e-3);
+}
+
+#[test]
+fn test_cinematic_follows_path_and_finishes() {
+    let mut app = setup_test_app();
+    app.update();
+    spawn_ground(&mut app);
+    settle_on_ground(&mut app);
+    let player_start = player_translation(&mut app);
+
+    let keyframe = |time: f32, position: Vec3, fov: f32| CinematicKeyframe {
+        time,
+        position,
+        look_at: Vec3::ZERO,
+        fov,
+    };
+    let path = CinematicPath::new(
+        vec![
+            keyframe(0.0, Vec3::new(0.0, 5.0, 10.0), 60.0),
+            keyframe(0.5, Vec3::new(8.0, 5.0, 6.0), 50.0),
+            keyframe(1.0, Vec3::new(10.0, 5.0, 0.0), 40.0),
+        ],
+        false,
+    );
+    start_cinematic(&mut app.world, path.clone());
+    let mut finished = app
+        .world
+        .resource::<Events<CinematicFinished>>()
+        .get_reader();
+
+    app.update();
+    assert_eq!(camera_perspective(&mut app), CameraPerspective::Cinematic);
+    let start = path.keyframes()[0].position;
+    assert!(camera_focus(&mut app).distance(start) < 1e-4);
+    assert!(camera_forward(&mut app).distance(-start.normalize()) < 1e-3);
+    assert!(main_camera_translation(&mut app).length() < 1e-4);
+    assert_eq!(camera_rig(&mut app).fov(), 60.0);
+
+    // Each update shows the path one tick further along.
+    run_updates(&mut app, 30);
+    let pose = path.sample(30.0 * TICK).unwrap();
+    assert!(camera_focus(&mut app).distance(pose.position) < 1e-3);
+    assert!((camera_rig(&mut app).fov() - pose.fov).abs() < 1e-3);
+
+    // Pausing holds the camera in place.
+    app.world.resource_mut::<CinematicPlayback>().pause();
+    app.update();
+    let paused = camera_focus(&mut app);
+    run_updates(&mut app, 10);
+    assert_eq!(camera_focus(&mut app), paused);
+    app.world.resource_mut::<CinematicPlayback>().resume();
+
+    let mut finish_count = 0;
+    for _ in 0..60 {
+        app.update();
+        let events = app.world.resource::<Events<CinematicFinished>>();
+        finish_count += finished.read(events).count();
+    }
+    assert_eq!(finish_count, 1);
+    assert_eq!(camera_perspective(&mut app), CameraPerspective::ThirdPerson);
+    assert!(!app.world.contains_resource::<CinematicPlayback>());
+    assert!(player_translation(&mut app).distance(player_start) < 1e-3);
+}
+
+#[test]
+fn test_cinematic_path_round_trips_through_config_file() {
+    let path = CinematicPath::new(
+        vec![CinematicKeyframe {
+            time: 2.0,
+            position: Vec3::new(1.0, 2.0, 3.0),
+            look_at: Vec3::ZERO,
+            fov: 55.0,
+        }],
+        true,
+    );
+
+    let file = std::env::temp_dir()
+        .join(format!("protocol-zero-cinematic-{}", std::process::id()))
+        .join("flyover.ron");
+    path.save(&file).unwrap();
+    let loaded = CinematicPath::load(&file).unwrap();
+    std::fs::remove_dir_all(file.parent().unwrap()).unwrap();
+
+    assert_eq!(loaded, path);
+}
+
+/// Where the camera rig and the player are at one moment.
+struct CameraSnapshot {
+    focus: Vec3,
+    rotation: Quat,
+    offset: Vec3,
+    player: Vec3,
+}
+
+/// Zooms, turns the camera and moves the player at once, then snapshots the camera a third of a
+/// second later, simulated at `fps` frames per second.
+fn camera_transition_at_fps(fps: u32) -> CameraSnapshot {
+    let mut app = setup_test_app();
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+        1.0 / fps as f64,
+    )));
+    app.update();
+    // The test harness steps physics once per update, so pin the player in place and move it by
+    // hand; only the camera should see the frame rate.
+    let mut query = app.world.query_filtered::<Entity, With<Player>>();
+    let player = query.single(&app.world);
+    app.world
+        .entity_mut(player)
+        .remove::<KinematicCharacterController>();
+    run_updates(&mut app, fps as usize);
+
+    app.world.send_event(MouseMotion {
+        delta: Vec2::new(90.0, 30.0),
+    });
+    app.world.send_event(MouseWheel {
+        unit: MouseScrollUnit::Line,
+        y: -2.0,
+        x: 0.0,
+        window: Entity::PLACEHOLDER,
+    });
+    let mut query = app.world.query_filtered::<&mut Transform, With<Player>>();
+    query.single_mut(&mut app.world).translation += Vec3::new(2.0, 0.5, -1.0);
+    run_updates(&mut app, fps as usize / 3);
+
+    let mut rig_query = app
+        .world
+        .query_filtered::<(&CameraRig, &Transform), Without<MainCamera>>();
+    let (rig, rig_transform) = rig_query.single(&app.world);
+    let (focus, rotation) = (rig.focus, rig_transform.rotation);
+    CameraSnapshot {
+        focus,
+        rotation,
+        offset: main_camera_translation(&mut app),
+        player: player_translation(&mut app),
+    }
+}
+
+#[test]
+fn test_camera_smoothing_is_framerate_independent() {
+    let slow = camera_transition_at_fps(30);
+    let fast = camera_transition_at_fps(144);
+
+    // Still mid-transition, so this doesn't just compare two settled cameras.
+    assert!(slow.focus.distance(slow.player) > 0.01);
+    assert!(slow.offset.z < 5.999, "boom {}", slow.offset.z);
+
+    assert!(
+        slow.focus.distance(fast.focus) < 1e-3,
+        "{} vs {}",
+        slow.focus,
+        fast.focus
+    );
+    assert!(slow.rotation.angle_between(fast.rotation) < 1e-3);
+    assert!(
+        slow.offset.distance(fast.offset) < 1e-3,
+        "{} vs {}",
+        slow.offset,
+        fast.offset
+    );
+}
+
+#[test]
+fn test_camera_focus_leads_moving_player() {
+    let mut app = setup_test_app();
+    app.update();
+    spawn_ground(&mut app);
+    settle_on_ground(&mut app);
+
+    app.world
+        .resource_mut::<ActionState>()
+        .press(InputAction::MoveForward);
+    run_updates(&mut app, 60);
+
+    let velocity = player_velocity(&mut app).horizontal();
+    assert!(velocity.length() > 1.0);
+    let lead = camera_focus(&mut app) - player_translation(&mut app);
+    // The focus is predicted ahead of the player rather than trailing behind.
+    assert!(lead.dot(velocity.normalize()) > 0.1, "lead {lead}");
+}
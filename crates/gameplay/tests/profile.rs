@@ -0,0 +1,249 @@
+use bevy::input::InputPlugin;
+use bevy::prelude::*;
+use common::config::ConfigError;
+use common::input::{
+    InputAction, InputActionsPlugin, InputBinding, InputBindings, InputBindingsPath,
+};
+use gameplay::building::blueprint::{Blueprint, BlueprintPiece};
+use gameplay::building::{BuildPieceKind, MaterialId};
+use gameplay::camera::profile::CameraProfiles;
+use gameplay::inventory::items::ItemRegistry;
+use gameplay::inventory::stack::{ItemContainer, ItemStack};
+use gameplay::inventory::{InventoryPlugin, ItemId, Stash};
+use gameplay::profile::{
+    backup_path, Profile, ProfileError, ProfilePlugin, Progress, SaveProfile, PROFILE_BACKUPS,
+    PROFILE_FORMAT_VERSION,
+};
+use std::path::PathBuf;
+
+/// A profile path in its own temporary directory, so tests don't share files.
+fn profile_path(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "protocol-zero-profile-{}-{test}",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir.join("profile.ron")
+}
+
+/// A profile with something in every part of it.
+fn full_profile() -> Profile {
+    let registry = ItemRegistry::default();
+    let mut profile = Profile::default();
+    profile
+        .stash
+        .add(ItemStack::new(ItemId(1), 45), &registry)
+        .unwrap();
+    let mut pistol = ItemStack::new(ItemId(20), 1);
+    pistol.durability = Some(0.75);
+    pistol.insured = true;
+    pistol
+        .data
+        .insert("sight".to_string(), "red_dot".to_string());
+    profile.stash.add(pistol, &registry).unwrap();
+
+    profile.progress.currency = 1_250;
+    profile.progress.blueprints.insert(
+        "Watchtower".into(),
+        Blueprint {
+            name: "Watchtower".into(),
+            pieces: vec![BlueprintPiece {
+                kind: BuildPieceKind::WALL,
+                material: MaterialId::METAL,
+                translation: Vec3::new(0.0, 0.0, 1.0),
+                rotation: Quat::from_rotation_y(std::f32::consts::FRAC_PI_2),
+            }],
+        },
+    );
+    profile
+        .progress
+        .story_flags
+        .insert("met_the_broker".to_string());
+
+    profile.settings.bindings.rebind(
+        InputAction::Jump,
+        InputBinding::Key(KeyCode::Space),
+        InputBinding::Key(KeyCode::KeyJ),
+    );
+    profile.settings.camera.explore.fov = 90.0;
+    profile
+}
+
+fn currency(profile: Result<Profile, ProfileError>) -> u64 {
+    profile.unwrap().progress.currency
+}
+
+#[test]
+fn test_full_profile_round_trips_through_save_file() {
+    let path = profile_path("round-trip");
+    let profile = full_profile();
+    profile.save(&path).unwrap();
+    let source = std::fs::read_to_string(&path).unwrap();
+    let loaded = Profile::load(&path).unwrap();
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+    assert!(source.starts_with(&format!(
+        "// protocol-zero profile v{PROFILE_FORMAT_VERSION} "
+    )));
+    assert_eq!(loaded, profile);
+    assert_eq!(loaded.stash.count(ItemId(1)), 45);
+    assert_eq!(
+        loaded.settings.bindings.bindings(InputAction::Jump)[0],
+        InputBinding::Key(KeyCode::KeyJ)
+    );
+}
+
+#[test]
+fn test_saves_keep_backups_to_recover_damaged_profiles() {
+    let path = profile_path("backups");
+    let mut profile = Profile::default();
+    for saved in 1..=5 {
+        profile.progress.currency = saved;
+        profile.save(&path).unwrap();
+    }
+
+    // The newest saves are kept, newest first, and nothing is left half written.
+    assert_eq!(currency(Profile::load(&path)), 5);
+    for n in 1..=PROFILE_BACKUPS {
+        assert_eq!(
+            currency(Profile::load(&backup_path(&path, n))),
+            5 - n as u64
+        );
+    }
+    assert!(!backup_path(&path, PROFILE_BACKUPS + 1).exists());
+    let files = std::fs::read_dir(path.parent().unwrap()).unwrap().count();
+    assert_eq!(files, 1 + PROFILE_BACKUPS);
+
+    // A damaged profile is caught and the newest good backup is used instead.
+    let source = std::fs::read_to_string(&path).unwrap();
+    std::fs::write(&path, &source[..source.len() - 10]).unwrap();
+    assert!(matches!(
+        Profile::load(&path),
+        Err(ProfileError::ChecksumMismatch)
+    ));
+    std::fs::write(backup_path(&path, 1), "garbage").unwrap();
+    assert_eq!(currency(Profile::recover(&path)), 3);
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    assert!(matches!(
+        Profile::recover(&path),
+        Err(ProfileError::Config(ConfigError::Io(err))) if err.kind() == std::io::ErrorKind::NotFound
+    ));
+}
+
+#[test]
+fn test_plugin_restores_and_saves_the_profile() {
+    let path = profile_path("plugin");
+    let profile = full_profile();
+    profile.save(&path).unwrap();
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        InventoryPlugin,
+        ProfilePlugin { path: path.clone() },
+    ));
+    app.update();
+    assert_eq!(*app.world.resource::<Stash>(), profile.stash);
+    assert_eq!(*app.world.resource::<Progress>(), profile.progress);
+    assert_eq!(
+        *app.world.resource::<InputBindings>(),
+        profile.settings.bindings
+    );
+    assert_eq!(app.world.resource::<CameraProfiles>().explore.fov, 90.0);
+
+    app.world.resource_mut::<Progress>().currency += 50;
+    app.world
+        .resource_mut::<Stash>()
+        .remove(ItemId(1), 5)
+        .unwrap();
+    app.world.send_event(SaveProfile);
+    app.update();
+    let saved = Profile::load(&path).unwrap();
+    let previous = Profile::load(&backup_path(&path, 1)).unwrap();
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+    assert_eq!(saved.progress.currency, 1_300);
+    assert_eq!(saved.stash.count(ItemId(1)), 40);
+    assert_eq!(saved.settings, profile.settings);
+    assert_eq!(previous, profile);
+
+    // Without a profile the game starts a new one.
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        InventoryPlugin,
+        ProfilePlugin { path: path.clone() },
+    ));
+    app.update();
+    assert_eq!(*app.world.resource::<Progress>(), Progress::default());
+    assert_eq!(*app.world.resource::<Stash>(), Stash::default());
+    assert!(!path.exists());
+}
+
+#[test]
+fn test_profile_owns_the_settings_it_restores() {
+    let path = profile_path("settings");
+    let bindings_path = path.with_file_name("input.ron");
+    let jump = |app: &App| {
+        app.world
+            .resource::<InputBindings>()
+            .bindings(InputAction::Jump)[0]
+    };
+
+    // A new profile starts from the bindings in their own config file.
+    let mut bindings = InputBindings::default();
+    bindings.rebind(
+        InputAction::Jump,
+        InputBinding::Key(KeyCode::Space),
+        InputBinding::Key(KeyCode::KeyJ),
+    );
+    bindings.save(&bindings_path).unwrap();
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        InputPlugin,
+        InputActionsPlugin {
+            bindings_path: bindings_path.clone(),
+        },
+        InventoryPlugin,
+        ProfilePlugin { path: path.clone() },
+    ));
+    app.update();
+    assert_eq!(jump(&app), InputBinding::Key(KeyCode::KeyJ));
+    assert!(!app.world.contains_resource::<InputBindingsPath>());
+    app.world.send_event(SaveProfile);
+    app.update();
+    std::fs::remove_file(&bindings_path).unwrap();
+
+    // Whichever plugin comes first, the profile's bindings are used, and changing them saves the
+    // profile rather than the bindings' config file.
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        InputPlugin,
+        InventoryPlugin,
+        ProfilePlugin { path: path.clone() },
+        InputActionsPlugin {
+            bindings_path: bindings_path.clone(),
+        },
+    ));
+    app.update();
+    assert_eq!(jump(&app), InputBinding::Key(KeyCode::KeyJ));
+    app.world.resource_mut::<InputBindings>().rebind(
+        InputAction::Jump,
+        InputBinding::Key(KeyCode::KeyJ),
+        InputBinding::Key(KeyCode::KeyK),
+    );
+    app.world.resource_mut::<CameraProfiles>().explore.fov = 80.0;
+    app.update();
+    let saved = Profile::load(&path).unwrap();
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+    assert_eq!(
+        saved.settings.bindings.bindings(InputAction::Jump)[0],
+        InputBinding::Key(KeyCode::KeyK)
+    );
+    assert_eq!(saved.settings.camera.explore.fov, 80.0);
+    assert!(!bindings_path.exists());
+}
@@ -19,12 +19,66 @@ pub const CHUNK_DEPTH: usize = 32;
 #[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
 pub struct MaterialId(pub u16);
 
+impl MaterialId {
+    /// Empty space.
+    pub const AIR: Self = Self(0);
+    /// The default ground material.
+    pub const STONE: Self = Self(1);
+    /// A low-friction surface.
+    pub const ICE: Self = Self(2);
+    /// A non-solid liquid.
+    pub const WATER: Self = Self(3);
+    /// A solid material that can be climbed.
+    pub const LADDER: Self = Self(4);
+}
+
 /// Defines the properties of a voxel material.
 /// This will be expanded later to include things like HP, resistances, etc.
 #[derive(Component, Debug, Clone)]
 pub struct Material {
     pub name: String,
     pub is_solid: bool,
+    /// Multiplier applied to ground acceleration and deceleration when standing on this material.
+    /// `1.0` is regular footing, values close to `0.0` are slippery.
+    pub friction: f32,
+    /// The fraction of fall damage absorbed when landing on or in this material.
+    pub impact_absorption: f32,
+    /// Whether characters can climb the faces of voxels made of this material.
+    pub is_climbable: bool,
+    /// Whether this material is a liquid that characters can swim in.
+    pub is_fluid: bool,
+}
+
+impl Material {
+    /// Creates a solid material with regular friction.
+    pub fn solid(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            is_solid: true,
+            friction: 1.0,
+            impact_absorption: 0.0,
+            is_climbable: false,
+            is_fluid: false,
+        }
+    }
+
+    /// Sets the friction of this material.
+    pub fn with_friction(mut self, friction: f32) -> Self {
+        self.friction = friction;
+        self
+    }
+
+    /// Sets whether this material can be climbed.
+    pub fn with_climbable(mut self, is_climbable: bool) -> Self {
+        self.is_climbable = is_climbable;
+        self
+    }
+
+    /// Sets the fraction of fall damage this material absorbs.
+    pub fn with_impact_absorption(mut self, impact_absorption: f32) -> Self {
+        self.impact_absorption = impact_absorption;
+        self
+    }
 }
 
 /// Represents a single voxel in the world.
@@ -61,6 +115,65 @@ pub struct WorldData {
     pub chunks: HashMap<IVec3, Chunk>,
 }
 
+/// The seed the world was generated from, recorded in replays so they can rebuild the same world.
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct WorldSeed(pub u64);
+
+/// A resource that maps material IDs to their properties.
+#[derive(Resource, Debug, Clone)]
+pub struct MaterialRegistry {
+    materials: HashMap<MaterialId, Material>,
+}
+
+impl Default for MaterialRegistry {
+    fn default() -> Self {
+        let mut registry = Self {
+            materials: HashMap::new(),
+        };
+        registry.register(
+            MaterialId::AIR,
+            Material {
+                name: "air".to_string(),
+                is_solid: false,
+                friction: 1.0,
+                impact_absorption: 0.0,
+                is_climbable: false,
+                is_fluid: false,
+            },
+        );
+        registry.register(MaterialId::STONE, Material::solid("stone"));
+        registry.register(MaterialId::ICE, Material::solid("ice").with_friction(0.1));
+        registry.register(
+            MaterialId::WATER,
+            Material {
+                name: "water".to_string(),
+                is_solid: false,
+                friction: 1.0,
+                impact_absorption: 1.0,
+                is_climbable: false,
+                is_fluid: true,
+            },
+        );
+        registry.register(
+            MaterialId::LADDER,
+            Material::solid("ladder").with_climbable(true),
+        );
+        registry
+    }
+}
+
+impl MaterialRegistry {
+    /// Registers (or replaces) the properties of a material.
+    pub fn register(&mut self, id: MaterialId, material: Material) {
+        self.materials.insert(id, material);
+    }
+
+    /// Gets the properties of a material, if it is registered.
+    pub fn get(&self, id: MaterialId) -> Option<&Material> {
+        self.materials.get(&id)
+    }
+}
+
 // --- Coordinate Conversion ---
 
 /// Converts world coordinates (e.g., from a transform) to global voxel coordinates.
@@ -117,6 +230,33 @@ impl WorldData {
             voxel;
         chunk.is_dirty = true;
     }
+
+    /// Gets the material of the voxel containing the given world-space position.
+    /// Returns `None` if the chunk is not loaded.
+    pub fn material_at(&self, world_pos: Vec3) -> Option<MaterialId> {
+        self.get_voxel(world_to_global_voxel(world_pos))
+            .map(|voxel| voxel.0)
+    }
+
+    /// How far along `direction` from `origin` the first solid voxel is, if within
+    /// `max_distance`. The ray is sampled every `step` meters.
+    pub fn solid_hit_distance(
+        &self,
+        materials: &MaterialRegistry,
+        origin: Vec3,
+        direction: Vec3,
+        max_distance: f32,
+        step: f32,
+    ) -> Option<f32> {
+        let steps = (max_distance / step).ceil() as usize;
+        (1..=steps)
+            .map(|i| (i as f32 * step).min(max_distance))
+            .find(|&distance| {
+                self.material_at(origin + direction * distance)
+                    .and_then(|id| materials.get(id))
+                    .is_some_and(|material| material.is_solid)
+            })
+    }
 }
 
 #[cfg(test)]
@@ -239,4 +379,19 @@ mod tests {
         // Chunk should now exist
         assert!(world_data.chunks.contains_key(&chunk_coord));
     }
+
+    #[test]
+    fn test_material_at_and_registry_lookup() {
+        let mut world_data = WorldData::default();
+        world_data.set_voxel(IVec3::new(0, -1, 0), Voxel(MaterialId::ICE));
+
+        let material = world_data.material_at(Vec3::new(0.5, -0.1, 0.5));
+        assert_eq!(material, Some(MaterialId::ICE));
+
+        let registry = MaterialRegistry::default();
+        let ice = registry.get(MaterialId::ICE).unwrap();
+        let stone = registry.get(MaterialId::STONE).unwrap();
+        assert!(ice.friction < stone.friction);
+        assert!(!registry.get(MaterialId::AIR).unwrap().is_solid);
+    }
 }